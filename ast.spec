@@ -0,0 +1,97 @@
+// The syntax tree nodes. build.rs turns this file into the node types of src/ast.rs.
+//
+// Every unindented line starts a node kind that becomes an enum with a visitor, and the
+// indented `Name: Type field, ...` lines below it are its nodes. `Expr` fields are boxed,
+// `T?` is an `Option<T>` and `T*` a `Vec<T>`. `//` lines right above a kind or a node become
+// its comment, `use` and `#[...]` lines are copied to the top and onto every type.
+
+use super::{BinaryOperator, Body, CatchClause, EnumVariant, LiteralValue, MatchArm, NamedArgument, NodeId, Parameter, PostfixOperator, Store, TypeAnnotation, UnaryOperator};
+use crate::lexer::Span;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+
+// We'll use an enum approach instead of trait objects
+Expr
+    // Compound assignments to a name like `x += 1` are desugared by the parser into
+    // `x = x + 1`. The span points at the assigned name
+    Assign: NodeId id, String name, Span span, Expr value
+    // The span of an operator node points at the operator itself
+    Binary: NodeId id, Expr left, BinaryOperator operator, Span span, Expr right
+    // The span points at the opening parenthesis of the arguments
+    Call: NodeId id, Expr callee, Span span, Expr* arguments, NamedArgument* named
+    // `condition ? then_branch : else_branch`, the span points at the `?`
+    Conditional: NodeId id, Expr condition, Span span, Expr then_branch, Expr else_branch
+    // `object.name`, the span of the property nodes points at the name
+    Get: NodeId id, Expr object, String name, Span span
+    Grouping: NodeId id, Expr expression, Span span
+    // `xs[i]`, the span of the index nodes points at the opening bracket
+    Index: NodeId id, Expr object, Span span, Expr index
+    // `xs[i] = value` and `xs[i] += value`
+    IndexSet: NodeId id, Expr object, Span span, Expr index, Store store, Expr value
+    // `fun (a, b) { ... }` and `(a, b) => a + b`, an arrow body is wrapped in a return. The span
+    // points at `fun` or at the opening parenthesis
+    Lambda: NodeId id, Parameter* params, TypeAnnotation? return_type, Body body, Span span
+    // `[a, b, c]`, the span points at the opening bracket
+    List: NodeId id, Expr* elements, Span span
+    Literal: NodeId id, LiteralValue value, Span span
+    // `match subject { pattern if guard => value, ... }`, the span points at `match`
+    Match: NodeId id, Expr subject, MatchArm* arms, Span span
+    // `{k: v, ...}`, `keys[i]` maps to `values[i]`. The span points at the opening brace
+    Map: NodeId id, Expr* keys, Expr* values, Span span
+    // `x++` and `x--`, the prefix forms are desugared into assignments. On a list element or a
+    // field they are stores, see `Store`
+    Postfix: NodeId id, String name, PostfixOperator operator, Span span
+    // `object.name = value` and `object.name -= value`
+    Set: NodeId id, Expr object, String name, Span span, Store store, Expr value
+    // `xs[start:end]`, a bound that is left out means the start or the end of the list
+    Slice: NodeId id, Expr object, Span span, Expr? start, Expr? end
+    // `super.method`, the superclass's method bound to `this`. The span points at `super`
+    Super: NodeId id, String method, Span span
+    This: NodeId id, Span span
+    Unary: NodeId id, UnaryOperator operator, Span span, Expr right
+    Variable: NodeId id, String name, Span span
+
+Stmt
+    // The span of a statement points at its keyword or opening brace, an expression statement
+    // uses the span of its expression
+    Block: NodeId id, Stmt* statements, Span span
+    // `break;` or `break outer;`, without a label it leaves the innermost loop
+    Break: NodeId id, String? label, Span span
+    // `class B < A { ... }`, the span points at the class name
+    Class: NodeId id, String name, Variable? superclass, Function* methods, Span span
+    // `continue;` or `continue outer;`, it still runs the increment of a `for`
+    Continue: NodeId id, String? label, Span span
+    // `const NAME = value;` can't be assigned to again, which the resolver checks. The span
+    // points at the name
+    Const: NodeId id, String name, TypeAnnotation? annotation, Expr initializer, Span span
+    // `enum Shape { Circle(r), Rect(w, h), Empty }`, the span points at the enum name
+    Enum: NodeId id, String name, EnumVariant* variants, Span span
+    // `export` in front of a top-level declaration lets other modules import its name
+    Export: NodeId id, Stmt declaration, Span span
+    Expression: NodeId id, Expr expression, Span span
+    // `for (item in items) body` or `for (key, value in map) body`, the span points at `for`
+    ForIn: NodeId id, String? label, String* names, Expr iterable, Stmt body, Span span
+    // A named function declaration, the span points at the name
+    Function: NodeId id, String name, Parameter* params, TypeAnnotation? return_type, Body body, Span span
+    If: NodeId id, Expr condition, Stmt then_branch, Stmt? else_branch, Span span
+    // `import "util.tk" as util;` binds the whole module to the alias, `from "math.tk" import
+    // sqrt;` binds the listed names. The path is relative to the importing file and the span
+    // points at it
+    Import: NodeId id, String path, String? alias, String* names, Span span
+    Print: NodeId id, Expr expression, Span span
+    Return: NodeId id, Expr? value, Span span
+    // `throw value;` unwinds to the nearest enclosing `catch`
+    Throw: NodeId id, Expr value, Span span
+    // `try { } catch (e) { } finally { }`, at least one of the clauses is there. The finally
+    // body runs however the rest ends, an empty one is the same as none
+    Try: NodeId id, Stmt* body, CatchClause? catch_clause, Stmt* finally_body, Span span
+    // `outer: while (condition) body`. A `for` loop becomes a block with its initializer and a
+    // while loop that runs the increment after every pass of the body, `continue` included
+    While: NodeId id, String? label, Expr condition, Stmt body, Expr? increment, Span span
+    // Declared without an initializer the variable starts out as nil. The span points at the
+    // name
+    Var: NodeId id, String name, TypeAnnotation? annotation, Expr? initializer, Span span
+    // `yield value;` hands the value to whoever resumed the generator and suspends it there.
+    // A function with one anywhere in its body is a generator
+    Yield: NodeId id, Expr value, Span span
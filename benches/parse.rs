@@ -0,0 +1,130 @@
+// Measures parsing a generated 100k-line program into the AST. Run with
+// `cargo bench --bench parse`
+extern crate programming_language;
+
+use programming_language::lexer::{Scanner, Token, TokenType};
+use programming_language::parser::Parser;
+use programming_language::tools::generate_programs::{Grammar, ProgramGenerator};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const LINES: u64 = 100_000;
+const DEPTH: usize = 8;
+const RUNS: usize = 5;
+
+// Counts what the parser allocates, the numbers are only read between runs
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        LIVE_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+struct Measurement {
+    time: Duration,
+    allocations: usize,
+    bytes: usize,
+}
+
+// Runs `parse` a few times and keeps the fastest run, the allocations and the bytes still held
+// by the tree are the same every time
+fn measure<T, F: FnMut() -> T>(mut parse: F) -> Measurement {
+    let mut best = Duration::MAX;
+    let mut allocations = 0;
+    let mut bytes = 0;
+
+    for _ in 0..RUNS {
+        let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+        let bytes_before = LIVE_BYTES.load(Ordering::Relaxed);
+        let start = Instant::now();
+        let tree = parse();
+        best = best.min(start.elapsed());
+        allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+        bytes = LIVE_BYTES.load(Ordering::Relaxed) - bytes_before;
+        drop(tree);
+    }
+
+    Measurement {
+        time: best,
+        allocations,
+        bytes,
+    }
+}
+
+fn report(name: &str, measurement: &Measurement) {
+    println!(
+        "{:<8} {:>10.2?} {:>12} {:>12.1} MiB",
+        name,
+        measurement.time,
+        measurement.allocations,
+        measurement.bytes as f64 / (1024.0 * 1024.0)
+    );
+}
+
+fn main() {
+    let grammar_path = concat!(env!("CARGO_MANIFEST_DIR"), "/grammar.txt");
+    let grammar = fs::read_to_string(grammar_path).expect("Could not read grammar.txt");
+    let grammar = Grammar::parse(&grammar).expect("Invalid grammar");
+
+    // One declaration per line, scanned up front so only parsing is timed. The scanner looks up
+    // characters from the start of the source, so every line is scanned on its own and the
+    // tokens are put back together into one program, numbered by their line
+    let mut tokens = Vec::new();
+    for line in 0..LINES {
+        let declaration = ProgramGenerator::new(&grammar, line, DEPTH).generate_rule("declaration");
+        let mut scanner = Scanner::new(declaration);
+        tokens.extend(
+            scanner
+                .scan_tokens()
+                .iter()
+                .filter(|token| token.token_type != TokenType::EOF)
+                .cloned()
+                .map(|token| Token {
+                    line: line as u32 + 1,
+                    ..token
+                }),
+        );
+    }
+    tokens.push(Token::new(
+        TokenType::EOF,
+        String::new(),
+        None,
+        LINES as u32,
+        1,
+    ));
+    println!("{} lines, {} tokens", LINES, tokens.len());
+    println!(
+        "{:<8} {:>10} {:>12} {:>16}",
+        "AST", "TIME", "ALLOCATIONS", "RETAINED"
+    );
+
+    let boxed = measure(|| {
+        Parser::new(&tokens)
+            .parse_program()
+            .expect("the generated program should parse")
+    });
+    report("boxed", &boxed);
+}
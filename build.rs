@@ -0,0 +1,22 @@
+// Generates the syntax tree node types from ast.spec, see src/tools/generate_structs.rs
+#[path = "src/tools/generate_structs.rs"]
+mod generate_structs;
+
+use generate_structs::{generate_ast, AstSpec};
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=ast.spec");
+    println!("cargo:rerun-if-changed=src/tools/generate_structs.rs");
+
+    let spec = fs::read_to_string("ast.spec").expect("Could not read ast.spec");
+    let spec = AstSpec::parse(&spec).unwrap_or_else(|e| panic!("Invalid ast.spec: {}", e));
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    let file = File::create(Path::new(&out_dir).join("ast_nodes.rs"))
+        .expect("Could not create ast_nodes.rs");
+    generate_ast(&mut BufWriter::new(file), &spec).expect("Could not write ast_nodes.rs");
+}
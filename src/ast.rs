@@ -1,52 +1,309 @@
-use crate::lexer::Token;
-use std::fmt::Debug;
+use crate::generator;
+use crate::lexer::{Span, TokenType};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Debug};
+use std::ops::Deref;
+use std::sync::Arc;
 
-// We'll use an enum approach instead of trait objects
-#[derive(Debug)]
-pub enum Expr<'a> {
-    Binary(Binary<'a>),
-    Grouping(Grouping<'a>),
-    Literal(Literal),
-    Unary(Unary<'a>),
+// The node types, their visitors and `accept` are generated from ast.spec by build.rs
+mod nodes {
+    include!(concat!(env!("OUT_DIR"), "/ast_nodes.rs"));
 }
+pub use self::nodes::*;
 
-pub trait ExprVisitor {
-    type Output;
-    fn visit_binary_expr(&mut self, expr: &Binary) -> Self::Output;
-    fn visit_grouping_expr(&mut self, expr: &Grouping) -> Self::Output;
-    fn visit_literal_expr(&mut self, expr: &Literal) -> Self::Output;
-    fn visit_unary_expr(&mut self, expr: &Unary) -> Self::Output;
+// The AST owns everything it needs, so it can outlive the tokens it was parsed from. Every
+// node gets an id from the parser that side tables (resolution, types) can use as a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NodeId(pub u32);
+
+// A parameter of a function or lambda, the span points at its name. The default is evaluated
+// on each call that leaves the parameter out, with the parameters before it in scope, and a
+// rest parameter, `...rest`, collects the positional arguments left over into a list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    pub annotation: Option<TypeAnnotation>,
+    pub default: Option<Expr>,
+    pub rest: bool,
+    pub span: Span,
 }
 
-impl Expr<'_> {
-    pub fn accept<V: ExprVisitor>(&self, visitor: &mut V) -> V::Output {
-        match self {
-            Expr::Binary(b) => visitor.visit_binary_expr(b),
-            Expr::Grouping(g) => visitor.visit_grouping_expr(g),
-            Expr::Literal(l) => visitor.visit_literal_expr(l),
-            Expr::Unary(u) => visitor.visit_unary_expr(u),
+// The statements of a function or lambda. Every function value made from them shares them with
+// the tree, and whether they yield, which makes a call start a generator, is worked out once
+// when the body is built
+#[derive(Clone, PartialEq)]
+pub struct Body {
+    statements: Arc<Vec<Stmt>>,
+    is_generator: bool,
+}
+
+impl Body {
+    pub fn is_generator(&self) -> bool {
+        self.is_generator
+    }
+
+    // The statements to rebuild the body from, copied only if a function value still holds them
+    pub fn into_statements(self) -> Vec<Stmt> {
+        Arc::try_unwrap(self.statements).unwrap_or_else(|shared| (*shared).clone())
+    }
+
+    // Changes in place are renames and the like, which don't add or take away a `yield`
+    pub fn statements_mut(&mut self) -> &mut Vec<Stmt> {
+        Arc::make_mut(&mut self.statements)
+    }
+}
+
+impl From<Vec<Stmt>> for Body {
+    fn from(statements: Vec<Stmt>) -> Body {
+        Body {
+            is_generator: generator::yields(&statements),
+            statements: Arc::new(statements),
         }
     }
 }
 
-#[derive(Debug)]
-pub struct Binary<'a> {
-    pub left: Box<Expr<'a>>,
-    pub operator: &'a Token,
-    pub right: Box<Expr<'a>>,
+impl Deref for Body {
+    type Target = [Stmt];
+
+    fn deref(&self) -> &[Stmt] {
+        &self.statements
+    }
+}
+
+// Printed and stored as the list of statements it is
+impl Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.statements, f)
+    }
+}
+
+impl Serialize for Body {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.statements.as_slice().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Body {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Body, D::Error> {
+        Vec::deserialize(deserializer).map(Body::from)
+    }
+}
+
+// `name: value` in the arguments of a call, after the positional ones. The span points at the
+// name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedArgument {
+    pub name: String,
+    pub value: Expr,
+    pub span: Span,
+}
+
+// The type written after a name, `x: Int`, or after the parameters, `-> Num`. Only the name is
+// kept, what it means is up to the type checker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeAnnotation {
+    pub name: String,
+    pub span: Span,
 }
 
-#[derive(Debug)]
-pub struct Grouping<'a> {
-    pub expression: Box<Expr<'a>>,
+impl fmt::Display for TypeAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// `catch (name) { body }`, the span points at the name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatchClause {
+    pub name: String,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+// `Rect(w, h)` in an enum declaration, a variant without fields leaves out the parentheses.
+// The span points at its name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnumVariant {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub span: Span,
+}
+
+// One arm of a match, the guard and the value are evaluated with the pattern's bindings in
+// scope
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub value: Expr,
+}
+
+// What a match arm compares its subject against. A name binds the value, `_` matches anything
+// without binding it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    Wildcard(Span),
+    Literal(LiteralValue, Span),
+    Binding(String, Span),
+    // `a | b`, the first alternative that matches wins
+    Or(#[serde(deserialize_with = "alternatives")] Vec<Pattern>),
+    // `[first, second, ..rest]`, without a rest the list has to have exactly that length. The
+    // rest is a binding or a wildcard
+    List {
+        elements: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+        span: Span,
+    },
+    // `Point{x, y: 0}`, `x` alone is short for `x: x`. The span points at the class name
+    Instance {
+        class: String,
+        fields: Vec<(String, Pattern)>,
+        span: Span,
+    },
+    // `Shape.Rect(w, _)` matches one variant of an enum and its fields by position, `Shape.Empty`
+    // has none. The span points at the enum name
+    Variant {
+        enumeration: String,
+        variant: String,
+        fields: Vec<Pattern>,
+        span: Span,
+    },
+}
+
+// A tree read from JSON could hold an or pattern the parser never builds
+fn alternatives<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Pattern>, D::Error> {
+    let alternatives = Vec::deserialize(deserializer)?;
+    if alternatives.is_empty() {
+        return Err(de::Error::custom(
+            "an or pattern needs at least one alternative",
+        ));
+    }
+    Ok(alternatives)
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard(span)
+            | Pattern::Literal(_, span)
+            | Pattern::Binding(_, span)
+            | Pattern::List { span, .. }
+            | Pattern::Instance { span, .. }
+            | Pattern::Variant { span, .. } => *span,
+            Pattern::Or(alternatives) => alternatives[0].span(),
+        }
+    }
+
+    // Whether the pattern matches every value, so the arms after it can't be reached
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard(_) | Pattern::Binding(..) => true,
+            Pattern::Or(alternatives) => alternatives.iter().any(Pattern::is_irrefutable),
+            _ => false,
+        }
+    }
+
+    // The names the pattern binds, in the order they are written
+    pub fn bindings(&self, names: &mut Vec<String>) {
+        match self {
+            Pattern::Wildcard(_) | Pattern::Literal(..) => {}
+            Pattern::Binding(name, _) => names.push(name.clone()),
+            Pattern::Or(alternatives) => {
+                for alternative in alternatives {
+                    alternative.bindings(names);
+                }
+            }
+            Pattern::List { elements, rest, .. } => {
+                for element in elements.iter().chain(rest.as_deref()) {
+                    element.bindings(names);
+                }
+            }
+            Pattern::Instance { fields, .. } => {
+                for (_, field) in fields {
+                    field.bindings(names);
+                }
+            }
+            Pattern::Variant { fields, .. } => {
+                for field in fields {
+                    field.bindings(names);
+                }
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct Literal {
-    pub value: LiteralValue,
+// Prints the pattern the way it is written in the source
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Wildcard(_) => write!(f, "_"),
+            Pattern::Literal(LiteralValue::String(s), _) => write!(f, "\"{}\"", s),
+            Pattern::Literal(LiteralValue::Float(n), _) => write!(f, "{:?}", n),
+            Pattern::Literal(LiteralValue::Integer(n), _) => write!(f, "{}", n),
+            Pattern::Literal(LiteralValue::Boolean(b), _) => write!(f, "{}", b),
+            Pattern::Literal(LiteralValue::Nil, _) => write!(f, "nil"),
+            Pattern::Binding(name, _) => write!(f, "{}", name),
+            Pattern::Or(alternatives) => {
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{}", alternative)?;
+                }
+                Ok(())
+            }
+            Pattern::List { elements, rest, .. } => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                match rest {
+                    Some(rest) if elements.is_empty() => write!(f, "..{}", rest)?,
+                    Some(rest) => write!(f, ", ..{}", rest)?,
+                    None => {}
+                }
+                write!(f, "]")
+            }
+            Pattern::Instance { class, fields, .. } => {
+                write!(f, "{}{{", class)?;
+                for (i, (name, field)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match field {
+                        Pattern::Binding(binding, _) if binding == name => write!(f, "{}", name)?,
+                        field => write!(f, "{}: {}", name, field)?,
+                    }
+                }
+                write!(f, "}}")
+            }
+            Pattern::Variant {
+                enumeration,
+                variant,
+                fields,
+                ..
+            } => {
+                write!(f, "{}.{}", enumeration, variant)?;
+                if fields.is_empty() {
+                    return Ok(());
+                }
+                write!(f, "(")?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", field)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum LiteralValue {
     Integer(i64),
     Float(f64),
@@ -55,8 +312,274 @@ pub enum LiteralValue {
     Nil,
 }
 
-#[derive(Debug)]
-pub struct Unary<'a> {
-    pub operator: &'a Token,
-    pub right: Box<Expr<'a>>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    // `key in map` and `element in list`
+    In,
+    // `0..10` leaves out the end, `0..=10` includes it
+    Range,
+    RangeInclusive,
+    Comma,
+}
+
+impl BinaryOperator {
+    pub fn from_token_type(token_type: &TokenType) -> Option<BinaryOperator> {
+        let operator = match token_type {
+            TokenType::Plus => BinaryOperator::Add,
+            TokenType::Minus => BinaryOperator::Subtract,
+            TokenType::Star => BinaryOperator::Multiply,
+            TokenType::Slash => BinaryOperator::Divide,
+            TokenType::Percent => BinaryOperator::Modulo,
+            TokenType::StarStar => BinaryOperator::Power,
+            TokenType::EqualAndEqual => BinaryOperator::Equal,
+            TokenType::BangAndEqual => BinaryOperator::NotEqual,
+            TokenType::Greater => BinaryOperator::Greater,
+            TokenType::GreaterOrEqual => BinaryOperator::GreaterEqual,
+            TokenType::Less => BinaryOperator::Less,
+            TokenType::LessOrEqual => BinaryOperator::LessEqual,
+            TokenType::Ampersand => BinaryOperator::BitAnd,
+            TokenType::Pipe => BinaryOperator::BitOr,
+            TokenType::Caret => BinaryOperator::BitXor,
+            TokenType::LessLess => BinaryOperator::ShiftLeft,
+            TokenType::GreaterGreater => BinaryOperator::ShiftRight,
+            TokenType::In => BinaryOperator::In,
+            TokenType::DotDot => BinaryOperator::Range,
+            TokenType::DotDotEqual => BinaryOperator::RangeInclusive,
+            TokenType::Comma => BinaryOperator::Comma,
+            _ => return None,
+        };
+        Some(operator)
+    }
+
+    // The operator as it is written in the source code
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Power => "**",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::BitAnd => "&",
+            BinaryOperator::BitOr => "|",
+            BinaryOperator::BitXor => "^",
+            BinaryOperator::ShiftLeft => "<<",
+            BinaryOperator::ShiftRight => ">>",
+            BinaryOperator::In => "in",
+            BinaryOperator::Range => "..",
+            BinaryOperator::RangeInclusive => "..=",
+            BinaryOperator::Comma => ",",
+        }
+    }
+}
+
+// How `xs[i] = value` and `object.name = value` store the value. A compound assignment like
+// `xs[i] += value` stores the old value combined with it, the list and the index are only
+// evaluated once. `++xs[i]` is `xs[i] += 1`, and `xs[i]++` is too but gives back the old value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Store {
+    Assign,
+    Compound(BinaryOperator),
+    Postfix(BinaryOperator),
+}
+
+impl Store {
+    pub fn from_token_type(token_type: &TokenType) -> Option<Store> {
+        let store = match token_type {
+            TokenType::Equal => Store::Assign,
+            TokenType::PlusEqual => Store::Compound(BinaryOperator::Add),
+            TokenType::MinusEqual => Store::Compound(BinaryOperator::Subtract),
+            TokenType::StarEqual => Store::Compound(BinaryOperator::Multiply),
+            TokenType::SlashEqual => Store::Compound(BinaryOperator::Divide),
+            TokenType::PercentEqual => Store::Compound(BinaryOperator::Modulo),
+            _ => return None,
+        };
+        Some(store)
+    }
+
+    pub fn symbol(&self) -> String {
+        match self {
+            Store::Assign => String::from("="),
+            Store::Compound(operator) => format!("{}=", operator.symbol()),
+            Store::Postfix(operator) => operator.symbol().repeat(2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UnaryOperator {
+    Not,
+    Negate,
+    BitNot,
+}
+
+impl UnaryOperator {
+    pub fn from_token_type(token_type: &TokenType) -> Option<UnaryOperator> {
+        match token_type {
+            TokenType::Bang => Some(UnaryOperator::Not),
+            TokenType::Minus => Some(UnaryOperator::Negate),
+            TokenType::Tilde => Some(UnaryOperator::BitNot),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOperator::Not => "!",
+            UnaryOperator::Negate => "-",
+            UnaryOperator::BitNot => "~",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PostfixOperator {
+    Increment,
+    Decrement,
+}
+
+impl PostfixOperator {
+    pub fn from_token_type(token_type: &TokenType) -> Option<PostfixOperator> {
+        match token_type {
+            TokenType::PlusPlus => Some(PostfixOperator::Increment),
+            TokenType::MinusMinus => Some(PostfixOperator::Decrement),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            PostfixOperator::Increment => "++",
+            PostfixOperator::Decrement => "--",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_printer::AstPrinter;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    const EXPR_KINDS: &[&str] = &[
+        "Assign",
+        "Binary",
+        "Call",
+        "Conditional",
+        "Get",
+        "Grouping",
+        "Index",
+        "IndexSet",
+        "Lambda",
+        "List",
+        "Literal",
+        "Map",
+        "Match",
+        "Postfix",
+        "Set",
+        "Slice",
+        "Super",
+        "This",
+        "Unary",
+        "Variable",
+    ];
+    const STMT_KINDS: &[&str] = &[
+        "Block",
+        "Break",
+        "Class",
+        "Const",
+        "Continue",
+        "Enum",
+        "Export",
+        "Expression",
+        "ForIn",
+        "Function",
+        "If",
+        "Import",
+        "Print",
+        "Return",
+        "Throw",
+        "Try",
+        "Var",
+        "While",
+        "Yield",
+    ];
+
+    #[test]
+    fn every_node_round_trips_through_json() {
+        let code = "import \"u.tk\" as u; from \"m.tk\" import sqrt, pi; \
+                    export var a = 1; var b; const c: Int = 2 * 3; { print (a + -b) ? 1.5 : \"s\"; b = a++, nil; } \
+                    a += true; \
+                    fun f(x) { return (y) => x(y); } f(fun () { yield 1; }); \
+                    var xs = [1, 2]; xs[0] = xs[1:][0]; xs[1] *= 2; xs[0]++; {\"k\": 0 in xs}; \
+                    class P { init(x: Int) { this.x = x; } } var t: P = P(1); \
+                    class Q < P { init() { super.init(0); } } \
+                    try { throw Q(); } catch (e) { print e; } finally { print 1; } \
+                    var g = fun (n: Num) -> Num { return n; }; \
+                    enum S { C(r), E } print match S.C(1) { S.C(r) => r, S.E => 0 }; \
+                    l: for (var i = 0; i < 2; i++) { if (i) break l; else continue; } \
+                    for (k, v in {\"a\": 0..=2}) print v; \
+                    print match P(a) { P{x: 1 | -2} if a => [], [h, ..] => h, _ => P(0).x };";
+        let mut scanner = Scanner::new(code.to_string());
+        let statements = Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .expect("program should parse");
+
+        let json = serde_json::to_string(&statements).expect("serialize failed");
+        for kind in EXPR_KINDS.iter().chain(STMT_KINDS) {
+            assert!(
+                json.contains(&format!("{{\"{}\":", kind)),
+                "{} is not covered",
+                kind
+            );
+        }
+
+        let loaded: Vec<Stmt> = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(loaded, statements);
+        assert_eq!(
+            AstPrinter.print_program(&loaded),
+            AstPrinter.print_program(&statements)
+        );
+    }
+
+    #[test]
+    fn trees_the_parser_cant_build_are_rejected_when_loaded() {
+        let error = serde_json::from_str::<Pattern>("{\"Or\": []}")
+            .expect_err("an empty or pattern should not load");
+        assert!(error
+            .to_string()
+            .contains("an or pattern needs at least one alternative"));
+    }
+
+    // Fails to compile, rather than to run, once a node holds something like an `Rc`
+    #[test]
+    fn trees_can_be_sent_to_and_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Expr>();
+        assert_send_sync::<Stmt>();
+    }
 }
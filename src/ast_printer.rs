@@ -1,29 +1,315 @@
-use crate::ast::{Binary, Expr, ExprVisitor, Grouping, Literal, LiteralValue, Unary};
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Conditional, Const, Continue, Enum, Export, Expr,
+    ExprVisitor, Expression, ForIn, Function, Get, Grouping, If, Import, Index, IndexSet, Lambda,
+    List, Literal, LiteralValue, Map, Match, Parameter, Pattern, Postfix, Print, Return, Set,
+    Slice, Stmt, StmtVisitor, Store, Super, This, Throw, Try, TypeAnnotation, Unary, Var, Variable,
+    While, Yield,
+};
 
 pub struct AstPrinter;
 
 impl ExprVisitor for AstPrinter {
     type Output = String;
+    fn visit_assign_expr(&mut self, expr: &Assign) -> Self::Output {
+        self.parenthesize(&format!("= {}", expr.name), &[&expr.value])
+    }
+
     fn visit_binary_expr(&mut self, expr: &Binary) -> Self::Output {
-        self.parenthesize(&expr.operator.lexeme, &[&expr.left, &expr.right])
+        self.parenthesize(expr.operator.symbol(), &[&expr.left, &expr.right])
+    }
+
+    // Named arguments come last, `(call f 1 b: 2)`
+    fn visit_call_expr(&mut self, expr: &Call) -> Self::Output {
+        let mut builder = format!("(call {}", expr.callee.accept(self));
+        for argument in &expr.arguments {
+            builder.push(' ');
+            builder.push_str(&argument.accept(self));
+        }
+        for argument in &expr.named {
+            builder.push_str(&format!(
+                " {}: {}",
+                argument.name,
+                argument.value.accept(self)
+            ));
+        }
+        builder.push(')');
+        builder
+    }
+
+    fn visit_conditional_expr(&mut self, expr: &Conditional) -> Self::Output {
+        self.parenthesize(
+            "?",
+            &[&expr.condition, &expr.then_branch, &expr.else_branch],
+        )
     }
-    
+
+    fn visit_get_expr(&mut self, expr: &Get) -> Self::Output {
+        self.parenthesize(&format!(". {}", expr.name), &[&expr.object])
+    }
+
     fn visit_grouping_expr(&mut self, expr: &Grouping) -> Self::Output {
         self.parenthesize("group", &[&expr.expression])
     }
-    
+
+    fn visit_index_expr(&mut self, expr: &Index) -> Self::Output {
+        self.parenthesize("index", &[&expr.object, &expr.index])
+    }
+
+    fn visit_index_set_expr(&mut self, expr: &IndexSet) -> Self::Output {
+        let target = self.parenthesize("index", &[&expr.object, &expr.index]);
+        self.store(expr.store, target, &expr.value)
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &Lambda) -> Self::Output {
+        self.function("fun", &expr.params, &expr.return_type, &expr.body)
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> Self::Output {
+        let elements: Vec<&Expr> = expr.elements.iter().collect();
+        self.parenthesize("list", &elements)
+    }
+
     fn visit_literal_expr(&mut self, expr: &Literal) -> Self::Output {
-        match &expr.value {
-            LiteralValue::Nil => String::from("nil"),
-            LiteralValue::Boolean(b) => b.to_string(),
-            LiteralValue::Float(n) => n.to_string(),
-            LiteralValue::Integer(n) => n.to_string(),
-            LiteralValue::String(s) => s.clone(),
+        literal(&expr.value)
+    }
+
+    // The keys and values alternate, `(map k1 v1 k2 v2)`
+    fn visit_map_expr(&mut self, expr: &Map) -> Self::Output {
+        let entries: Vec<&Expr> = expr
+            .keys
+            .iter()
+            .zip(&expr.values)
+            .flat_map(|(key, value)| vec![key, value])
+            .collect();
+        self.parenthesize("map", &entries)
+    }
+
+    // `(match x (case pattern value) (case pattern (if guard) value))`
+    fn visit_match_expr(&mut self, expr: &Match) -> Self::Output {
+        let mut builder = format!("(match {}", expr.subject.accept(self));
+
+        for arm in &expr.arms {
+            builder.push_str(&format!(" (case {}", pattern(&arm.pattern)));
+            if let Some(guard) = &arm.guard {
+                builder.push_str(&format!(" (if {})", guard.accept(self)));
+            }
+            builder.push_str(&format!(" {})", arm.value.accept(self)));
         }
+
+        builder.push(')');
+        builder
+    }
+
+    fn visit_postfix_expr(&mut self, expr: &Postfix) -> Self::Output {
+        format!("(post{} {})", expr.operator.symbol(), expr.name)
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> Self::Output {
+        let target = self.parenthesize(&format!(". {}", expr.name), &[&expr.object]);
+        self.store(expr.store, target, &expr.value)
+    }
+
+    // `(slice xs a : b)`, a bound that was left out is left out here too
+    fn visit_slice_expr(&mut self, expr: &Slice) -> Self::Output {
+        let mut builder = format!("(slice {}", expr.object.accept(self));
+        if let Some(start) = &expr.start {
+            builder.push(' ');
+            builder.push_str(&start.accept(self));
+        }
+        builder.push_str(" :");
+        if let Some(end) = &expr.end {
+            builder.push(' ');
+            builder.push_str(&end.accept(self));
+        }
+        builder.push(')');
+        builder
     }
-    
+
+    fn visit_super_expr(&mut self, expr: &Super) -> Self::Output {
+        format!("(super {})", expr.method)
+    }
+
+    fn visit_this_expr(&mut self, _expr: &This) -> Self::Output {
+        String::from("this")
+    }
+
     fn visit_unary_expr(&mut self, expr: &Unary) -> Self::Output {
-        self.parenthesize(&expr.operator.lexeme, &[&expr.right])
+        self.parenthesize(expr.operator.symbol(), &[&expr.right])
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) -> Self::Output {
+        expr.name.clone()
+    }
+}
+
+impl StmtVisitor for AstPrinter {
+    type Output = String;
+    fn visit_block_stmt(&mut self, stmt: &Block) -> Self::Output {
+        let mut builder = String::from("(block");
+
+        for statement in &stmt.statements {
+            builder.push(' ');
+            builder.push_str(&statement.accept(self));
+        }
+
+        builder.push(')');
+        builder
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &Break) -> Self::Output {
+        labelled("break", &stmt.label)
+    }
+
+    // `(class Name < Superclass (fun method (a b) body...) ...)`
+    fn visit_class_stmt(&mut self, stmt: &Class) -> Self::Output {
+        let mut builder = format!("(class {}", stmt.name);
+        if let Some(superclass) = &stmt.superclass {
+            builder.push_str(&format!(" < {}", superclass.name));
+        }
+
+        for method in &stmt.methods {
+            builder.push(' ');
+            builder.push_str(&self.visit_function_stmt(method));
+        }
+
+        builder.push(')');
+        builder
+    }
+
+    fn visit_const_stmt(&mut self, stmt: &Const) -> Self::Output {
+        let name = format!("const {}", annotated(&stmt.name, &stmt.annotation));
+        self.parenthesize(&name, &[&stmt.initializer])
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &Continue) -> Self::Output {
+        labelled("continue", &stmt.label)
+    }
+
+    // `(enum Shape (Circle r) (Rect w h) Empty)`
+    fn visit_enum_stmt(&mut self, stmt: &Enum) -> Self::Output {
+        let mut builder = format!("(enum {}", stmt.name);
+        for variant in &stmt.variants {
+            if variant.fields.is_empty() {
+                builder.push_str(&format!(" {}", variant.name));
+            } else {
+                builder.push_str(&format!(" ({} {})", variant.name, variant.fields.join(" ")));
+            }
+        }
+        builder.push(')');
+        builder
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &Export) -> Self::Output {
+        format!("(export {})", stmt.declaration.accept(self))
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> Self::Output {
+        self.parenthesize(";", &[&stmt.expression])
+    }
+
+    // `(for outer: key value in map body)`, the label is optional
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) -> Self::Output {
+        let mut builder = String::from("(for");
+        if let Some(label) = &stmt.label {
+            builder.push_str(&format!(" {}:", label));
+        }
+        builder.push_str(&format!(
+            " {} in {} {})",
+            stmt.names.join(" "),
+            stmt.iterable.accept(self),
+            stmt.body.accept(self)
+        ));
+        builder
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) -> Self::Output {
+        let name = format!("fun {}", stmt.name);
+        self.function(&name, &stmt.params, &stmt.return_type, &stmt.body)
+    }
+
+    // `(if condition then else)`, without an else when there is none
+    fn visit_if_stmt(&mut self, stmt: &If) -> Self::Output {
+        let mut builder = format!(
+            "(if {} {}",
+            stmt.condition.accept(self),
+            stmt.then_branch.accept(self)
+        );
+        if let Some(else_branch) = &stmt.else_branch {
+            builder.push_str(&format!(" {}", else_branch.accept(self)));
+        }
+        builder.push(')');
+        builder
+    }
+
+    // `(import "util.tk" as util)` or `(from "math.tk" import sqrt pi)`
+    fn visit_import_stmt(&mut self, stmt: &Import) -> Self::Output {
+        match &stmt.alias {
+            Some(alias) => format!("(import \"{}\" as {})", stmt.path, alias),
+            None => format!("(from \"{}\" import {})", stmt.path, stmt.names.join(" ")),
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) -> Self::Output {
+        self.parenthesize("print", &[&stmt.expression])
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) -> Self::Output {
+        match &stmt.value {
+            Some(value) => self.parenthesize("return", &[value]),
+            None => String::from("(return)"),
+        }
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &Throw) -> Self::Output {
+        self.parenthesize("throw", &[&stmt.value])
+    }
+
+    // `(try (block body...) (catch e body...) (finally body...))`, a missing clause is left out
+    fn visit_try_stmt(&mut self, stmt: &Try) -> Self::Output {
+        let mut builder = format!("(try {}", self.sequence("block", &stmt.body));
+        if let Some(clause) = &stmt.catch_clause {
+            let head = format!("catch {}", clause.name);
+            builder.push_str(&format!(" {}", self.sequence(&head, &clause.body)));
+        }
+        if !stmt.finally_body.is_empty() {
+            builder.push_str(&format!(
+                " {}",
+                self.sequence("finally", &stmt.finally_body)
+            ));
+        }
+        builder.push(')');
+        builder
+    }
+
+    // `(var x: Int 1)`, the type and the initializer are both optional
+    fn visit_var_stmt(&mut self, stmt: &Var) -> Self::Output {
+        let name = format!("var {}", annotated(&stmt.name, &stmt.annotation));
+        match &stmt.initializer {
+            Some(initializer) => self.parenthesize(&name, &[initializer]),
+            None => format!("({})", name),
+        }
+    }
+
+    // `(while outer: condition body (step increment))`, the label and the step are optional
+    fn visit_while_stmt(&mut self, stmt: &While) -> Self::Output {
+        let mut builder = String::from("(while");
+        if let Some(label) = &stmt.label {
+            builder.push_str(&format!(" {}:", label));
+        }
+        builder.push_str(&format!(
+            " {} {}",
+            stmt.condition.accept(self),
+            stmt.body.accept(self)
+        ));
+        if let Some(increment) = &stmt.increment {
+            builder.push_str(&format!(" (step {})", increment.accept(self)));
+        }
+        builder.push(')');
+        builder
+    }
+
+    fn visit_yield_stmt(&mut self, stmt: &Yield) -> Self::Output {
+        self.parenthesize("yield", &[&stmt.value])
     }
 }
 
@@ -31,20 +317,211 @@ impl AstPrinter {
     pub fn print(&mut self, expr: &Expr) -> String {
         expr.accept(self)
     }
-    
-    fn parenthesize(&mut self, name: &str, exprs: &[&Box<Expr>]) -> String {
+
+    // One line per top-level statement
+    pub fn print_program(&mut self, statements: &[Stmt]) -> String {
+        statements
+            .iter()
+            .map(|statement| statement.accept(self))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // `(= target value)` and `(+= target value)`, `(post++ target)` leaves out the one it adds
+    fn store(&mut self, store: Store, target: String, value: &Expr) -> String {
+        match store {
+            Store::Postfix(_) => format!("(post{} {})", store.symbol(), target),
+            _ => format!("({} {} {})", store.symbol(), target, value.accept(self)),
+        }
+    }
+
+    // `(fun (a b) body...)`, named functions put their name after `fun`. Annotated ones read
+    // `(fun (a: Int b = 1 ...rest) -> Int body...)`
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[Parameter],
+        return_type: &Option<TypeAnnotation>,
+        body: &[Stmt],
+    ) -> String {
+        let params: Vec<String> = params.iter().map(|param| self.parameter(param)).collect();
+        let mut builder = format!("({} ({})", name, params.join(" "));
+        if let Some(return_type) = return_type {
+            builder.push_str(&format!(" -> {}", return_type));
+        }
+
+        for statement in body {
+            builder.push(' ');
+            builder.push_str(&statement.accept(self));
+        }
+
+        builder.push(')');
+        builder
+    }
+
+    fn parameter(&mut self, param: &Parameter) -> String {
+        let mut builder = annotated(&param.name, &param.annotation);
+        if param.rest {
+            builder.insert_str(0, "...");
+        }
+        if let Some(default) = &param.default {
+            builder.push_str(&format!(" = {}", default.accept(self)));
+        }
+        builder
+    }
+
+    fn sequence(&mut self, head: &str, statements: &[Stmt]) -> String {
+        let mut builder = format!("({}", head);
+        for statement in statements {
+            builder.push(' ');
+            builder.push_str(&statement.accept(self));
+        }
+        builder.push(')');
+        builder
+    }
+
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> String {
         let mut builder = String::new();
-        
+
         builder.push('(');
         builder.push_str(name);
-        
+
         for expr in exprs {
             builder.push(' ');
             builder.push_str(&expr.accept(self));
         }
-        
+
         builder.push(')');
         builder
     }
 }
 
+fn annotated(name: &str, annotation: &Option<TypeAnnotation>) -> String {
+    match annotation {
+        Some(annotation) => format!("{}: {}", name, annotation),
+        None => name.to_string(),
+    }
+}
+
+// `(break)` or `(break outer)`
+fn labelled(keyword: &str, label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("({} {})", keyword, label),
+        None => format!("({})", keyword),
+    }
+}
+
+// Floats keep their fraction and strings their quotes so the output can be read back
+pub(crate) fn literal(value: &LiteralValue) -> String {
+    match value {
+        LiteralValue::Nil => String::from("nil"),
+        LiteralValue::Boolean(b) => b.to_string(),
+        LiteralValue::Float(n) => format!("{:?}", n),
+        LiteralValue::Integer(n) => n.to_string(),
+        LiteralValue::String(s) => format!("\"{}\"", s),
+    }
+}
+
+// Patterns print like the expressions they look like, `(| a b)`, `(list a (.. rest))`,
+// `(instance Point (x x) (y _))` and `(variant Shape Rect w _)`
+fn pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard(_) => String::from("_"),
+        Pattern::Literal(value, _) => literal(value),
+        Pattern::Binding(name, _) => name.clone(),
+        Pattern::Or(alternatives) => {
+            let alternatives: Vec<String> = alternatives.iter().map(self::pattern).collect();
+            format!("(| {})", alternatives.join(" "))
+        }
+        Pattern::List { elements, rest, .. } => {
+            let mut builder = String::from("(list");
+            for element in elements {
+                builder.push(' ');
+                builder.push_str(&self::pattern(element));
+            }
+            if let Some(rest) = rest {
+                builder.push_str(&format!(" (.. {})", self::pattern(rest)));
+            }
+            builder.push(')');
+            builder
+        }
+        Pattern::Instance { class, fields, .. } => {
+            let mut builder = format!("(instance {}", class);
+            for (name, field) in fields {
+                builder.push_str(&format!(" ({} {})", name, self::pattern(field)));
+            }
+            builder.push(')');
+            builder
+        }
+        Pattern::Variant {
+            enumeration,
+            variant,
+            fields,
+            ..
+        } => {
+            let mut builder = format!("(variant {} {}", enumeration, variant);
+            for field in fields {
+                builder.push(' ');
+                builder.push_str(&self::pattern(field));
+            }
+            builder.push(')');
+            builder
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::Scanner;
+    use parser::Parser;
+
+    #[test]
+    fn a_representative_program_prints_the_same_tree() {
+        let code = "import \"shapes\" as shapes; from \"util\" import pad, trim;\n\
+                    export const LIMIT: Int = 1 << 4;\n\
+                    enum Shape { Circle(r), Square(side), Empty }\n\
+                    class Box < Base {\n\
+                        init(size = 1, ...rest) { super.init(); this.size = size; }\n\
+                        area() -> Int { return this.size ** 2; }\n\
+                    }\n\
+                    fun walk(xs: List) {\n\
+                        for (i, x in xs) { if (x % 2 == 0) continue; else yield x; }\n\
+                    }\n\
+                    var m = {\"a\": [1, 2.5, nil], b: true};\n\
+                    outer: while (!done) { m[\"a\"][1] += xs[1:]; box.size--; break outer; }\n\
+                    for (var i = 0; i < 3; ++i) print i, -i;\n\
+                    try { throw shapes.error(\"x\", code: 2); }\n\
+                    catch (e) { print e; } finally { print ~0; }\n\
+                    var area = match s {\n\
+                        Shape.Circle(r) if r > 0 => r * r,\n\
+                        [1, ..rest] | Box { size } => 0, _ => -1\n\
+                    };\n\
+                    var f = (n) => n & 1 ? n : fun (k) { return k ^ n; };";
+        let mut scanner = Scanner::new(code.to_string());
+        let statements = Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .expect("program should parse");
+
+        assert_eq!(
+            AstPrinter.print_program(&statements),
+            "(import \"shapes\" as shapes)\n\
+             (from \"util\" import pad trim)\n\
+             (export (const LIMIT: Int (<< 1 4)))\n\
+             (enum Shape (Circle r) (Square side) Empty)\n\
+             (class Box < Base (fun init (size = 1 ...rest) (; (call (super init))) (; (= (. size \
+             this) size))) (fun area () -> Int (return (** (. size this) 2))))\n\
+             (fun walk (xs: List) (for i x in xs (block (if (== (% x 2) 0) (continue) (yield \
+             x)))))\n\
+             (var m (map \"a\" (list 1 2.5 nil) b true))\n\
+             (while outer: (! done) (block (; (+= (index (index m \"a\") 1) (slice xs 1 :))) (; \
+             (post-- (. size box))) (break outer)))\n\
+             (block (var i 0) (while (< i 3) (print (, i (- i))) (step (= i (+ i 1)))))\n\
+             (try (block (throw (call (. error shapes) \"x\" code: 2))) (catch e (print e)) \
+             (finally (print (~ 0))))\n\
+             (var area (match s (case (variant Shape Circle r) (if (> r 0)) (* r r)) (case (| \
+             (list 1 (.. rest)) (instance Box (size size))) 0) (case _ (- 1))))\n\
+             (var f (fun (n) (return (? (& n 1) n (fun (k) (return (^ k n)))))))"
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use crate::function::{Arity, Function};
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// A class value, calling it creates an instance and runs `init` on it when there is one.
+// Methods the class doesn't define are looked up in its superclass
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<String, Rc<Function>>,
+}
+
+impl Class {
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    // Whether the class is called `name` or inherits from a class that is
+    pub fn is_a(&self, name: &str) -> bool {
+        self.name == name
+            || self
+                .superclass
+                .as_ref()
+                .is_some_and(|class| class.is_a(name))
+    }
+
+    // Takes the arguments of `init`, a class without one takes none
+    pub fn arity(&self) -> Arity {
+        self.find_method("init")
+            .map_or_else(|| Arity::of(&[]), |init| init.declaration.arity.clone())
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+// Fields are created by assigning to them, reading one that was never set is an error
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: HashMap<String, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Instance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    // A field shadows a method of the same name, a method comes back bound to the instance
+    pub fn get(instance: &Rc<RefCell<Instance>>, name: &str) -> Option<Value> {
+        if let Some(value) = instance.borrow().fields.get(name) {
+            return Some(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(name)?;
+        let this = Value::Instance(Rc::clone(instance));
+        Some(Value::Function(Rc::new(method.bind(this))))
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} instance>", self.class.name)
+    }
+}
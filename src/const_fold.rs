@@ -0,0 +1,337 @@
+use crate::ast::{
+    Binary, Block, Body, CatchClause, Class, Conditional, Const, Enum, Expr, ForIn, Function,
+    Grouping, Import, Lambda, Literal, LiteralValue, Match, MatchArm, NodeId, Parameter, Stmt, Try,
+    Unary, Var, Variable,
+};
+use crate::fold::{self, Fold};
+use crate::interpreter::Interpreter;
+use crate::lexer::Span;
+use crate::value::Value;
+use std::collections::HashMap;
+
+// Replaces operators whose operands are all literals with the literal they make, and uses of
+// constants whose initializers fold down to a literal with that literal. The operators run on
+// the interpreter, so a folded program prints what it would have. Anything that fails there,
+// like a division by zero, is left for the run to report
+pub fn fold_constants(statements: Vec<Stmt>) -> Vec<Stmt> {
+    let mut folder = ConstantFolder {
+        interpreter: Interpreter::new(),
+        scopes: vec![HashMap::new()],
+    };
+    folder.body(Vec::new(), statements)
+}
+
+struct ConstantFolder {
+    interpreter: Interpreter,
+    // The names declared in each scope around the current node, innermost last. A constant
+    // with a folded initializer holds its value, every other name holds None and hides the
+    // constants of the outer scopes
+    scopes: Vec<HashMap<String, Option<LiteralValue>>>,
+}
+
+impl ConstantFolder {
+    fn declare(&mut self, name: &str, value: Option<LiteralValue>) {
+        self.scopes
+            .last_mut()
+            .expect("there is always a global scope")
+            .insert(name.to_string(), value);
+    }
+
+    // Folds a part of the tree inside a new scope that declares the names
+    fn scoped<T>(&mut self, names: &[String], fold: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes
+            .push(names.iter().map(|name| (name.clone(), None)).collect());
+        let folded = fold(self);
+        self.scopes.pop();
+        folded
+    }
+
+    // Folds statements that make a scope of their own. Everything they declare hides the outer
+    // constants from the start, a closure written before a local declaration sees the local
+    // when it runs
+    fn body(&mut self, mut names: Vec<String>, statements: Vec<Stmt>) -> Vec<Stmt> {
+        declared_names(&statements, &mut names);
+        self.scoped(&names, |folder| fold::fold_program(folder, statements))
+    }
+
+    // The defaults see the parameters before them, the body also sees its own declarations
+    fn function(&mut self, params: Vec<Parameter>, body: Body) -> (Vec<Parameter>, Body) {
+        let names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+        let params = self.scoped(&names, |folder| fold::fold_params(folder, params));
+        let body = self.body(names, body.into_statements());
+        (params, body.into())
+    }
+
+    // The literal a folded operator gives, when its operands have all become literals
+    fn literal(&mut self, expr: Expr, id: NodeId, span: Span) -> Expr {
+        if !is_constant(&expr) {
+            return expr;
+        }
+        let value = match self.interpreter.evaluate(&expr) {
+            Ok(Value::Integer(n)) => LiteralValue::Integer(n),
+            Ok(Value::Float(n)) => LiteralValue::Float(n),
+            Ok(Value::String(s)) => LiteralValue::String(s),
+            Ok(Value::Boolean(b)) => LiteralValue::Boolean(b),
+            Ok(Value::Nil) => LiteralValue::Nil,
+            _ => return expr,
+        };
+        Expr::Literal(Literal { id, value, span })
+    }
+}
+
+// The names the statements declare in their own scope
+fn declared_names(statements: &[Stmt], names: &mut Vec<String>) {
+    for statement in statements {
+        match statement {
+            Stmt::Class(class) => names.push(class.name.clone()),
+            Stmt::Const(constant) => names.push(constant.name.clone()),
+            Stmt::Enum(enumeration) => names.push(enumeration.name.clone()),
+            Stmt::Function(function) => names.push(function.name.clone()),
+            Stmt::Var(var) => names.push(var.name.clone()),
+            Stmt::Import(import) => names.extend(import.alias.iter().chain(&import.names).cloned()),
+            Stmt::Export(export) => {
+                declared_names(std::slice::from_ref(&export.declaration), names)
+            }
+            _ => {}
+        }
+    }
+}
+
+// Whether the expression is made of literals and operators only, so evaluating it can't
+// depend on anything else
+fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(_) => true,
+        Expr::Grouping(grouping) => is_constant(&grouping.expression),
+        Expr::Unary(unary) => is_constant(&unary.right),
+        Expr::Binary(binary) => is_constant(&binary.left) && is_constant(&binary.right),
+        Expr::Conditional(conditional) => {
+            is_constant(&conditional.condition)
+                && is_constant(&conditional.then_branch)
+                && is_constant(&conditional.else_branch)
+        }
+        _ => false,
+    }
+}
+
+impl Fold for ConstantFolder {
+    fn fold_binary(&mut self, expr: Binary) -> Expr {
+        let (id, span) = (expr.id, expr.span);
+        let folded = fold::walk_binary(self, expr);
+        self.literal(folded, id, span)
+    }
+
+    fn fold_conditional(&mut self, expr: Conditional) -> Expr {
+        let (id, span) = (expr.id, expr.span);
+        let folded = fold::walk_conditional(self, expr);
+        self.literal(folded, id, span)
+    }
+
+    fn fold_grouping(&mut self, expr: Grouping) -> Expr {
+        let (id, span) = (expr.id, expr.span);
+        let folded = fold::walk_grouping(self, expr);
+        self.literal(folded, id, span)
+    }
+
+    fn fold_lambda(&mut self, expr: Lambda) -> Expr {
+        let (params, body) = self.function(expr.params, expr.body);
+        Expr::Lambda(Lambda {
+            params,
+            body,
+            ..expr
+        })
+    }
+
+    fn fold_match(&mut self, expr: Match) -> Expr {
+        let subject = Box::new(self.fold_expr(*expr.subject));
+        let arms = expr
+            .arms
+            .into_iter()
+            .map(|arm| {
+                let mut names = Vec::new();
+                arm.pattern.bindings(&mut names);
+                self.scoped(&names, |folder| MatchArm {
+                    pattern: arm.pattern,
+                    guard: arm.guard.map(|guard| folder.fold_expr(guard)),
+                    value: folder.fold_expr(arm.value),
+                })
+            })
+            .collect();
+        Expr::Match(Match {
+            subject,
+            arms,
+            ..expr
+        })
+    }
+
+    fn fold_unary(&mut self, expr: Unary) -> Expr {
+        let (id, span) = (expr.id, expr.span);
+        let folded = fold::walk_unary(self, expr);
+        self.literal(folded, id, span)
+    }
+
+    fn fold_variable(&mut self, expr: Variable) -> Expr {
+        let value = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&expr.name))
+            .cloned()
+            .flatten();
+        match value {
+            Some(value) => Expr::Literal(Literal {
+                id: expr.id,
+                value,
+                span: expr.span,
+            }),
+            None => Expr::Variable(expr),
+        }
+    }
+
+    fn fold_block(&mut self, stmt: Block) -> Stmt {
+        Stmt::Block(Block {
+            statements: self.body(Vec::new(), stmt.statements),
+            ..stmt
+        })
+    }
+
+    fn fold_class(&mut self, stmt: Class) -> Stmt {
+        self.declare(&stmt.name, None);
+        let methods = stmt
+            .methods
+            .into_iter()
+            .map(|method| {
+                let (params, body) = self.function(method.params, method.body);
+                Function {
+                    params,
+                    body,
+                    ..method
+                }
+            })
+            .collect();
+        Stmt::Class(Class { methods, ..stmt })
+    }
+
+    // The declaration stays, so the constant can still be exported or looked up by code that
+    // was folded before it
+    fn fold_const(&mut self, stmt: Const) -> Stmt {
+        let folded = fold::walk_const(self, stmt);
+        if let Stmt::Const(constant) = &folded {
+            let value = match &*constant.initializer {
+                Expr::Literal(literal) => Some(literal.value.clone()),
+                _ => None,
+            };
+            self.declare(&constant.name, value);
+        }
+        folded
+    }
+
+    fn fold_enum(&mut self, stmt: Enum) -> Stmt {
+        self.declare(&stmt.name, None);
+        Stmt::Enum(stmt)
+    }
+
+    fn fold_for_in(&mut self, stmt: ForIn) -> Stmt {
+        let ForIn {
+            id,
+            label,
+            names,
+            iterable,
+            body,
+            span,
+        } = stmt;
+        let iterable = Box::new(self.fold_expr(*iterable));
+        let body = Box::new(self.scoped(&names, |folder| folder.fold_stmt(*body)));
+        Stmt::ForIn(ForIn {
+            id,
+            label,
+            names,
+            iterable,
+            body,
+            span,
+        })
+    }
+
+    fn fold_function(&mut self, stmt: Function) -> Stmt {
+        self.declare(&stmt.name, None);
+        let (params, body) = self.function(stmt.params, stmt.body);
+        Stmt::Function(Function {
+            params,
+            body,
+            ..stmt
+        })
+    }
+
+    fn fold_import(&mut self, stmt: Import) -> Stmt {
+        for name in stmt.alias.iter().chain(&stmt.names) {
+            self.declare(name, None);
+        }
+        Stmt::Import(stmt)
+    }
+
+    fn fold_try(&mut self, stmt: Try) -> Stmt {
+        let Try {
+            id,
+            body,
+            catch_clause,
+            finally_body,
+            span,
+        } = stmt;
+        let body = self.body(Vec::new(), body);
+        let catch_clause = catch_clause.map(|clause| {
+            let CatchClause { name, body, span } = clause;
+            let body = self.body(vec![name.clone()], body);
+            CatchClause { name, body, span }
+        });
+        let finally_body = self.body(Vec::new(), finally_body);
+        Stmt::Try(Try {
+            id,
+            body,
+            catch_clause,
+            finally_body,
+            span,
+        })
+    }
+
+    fn fold_var(&mut self, stmt: Var) -> Stmt {
+        let folded = fold::walk_var(self, stmt);
+        if let Stmt::Var(var) = &folded {
+            self.declare(&var.name, None);
+        }
+        folded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_printer::AstPrinter;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    #[test]
+    fn constants_fold_into_their_uses_unless_shadowed() {
+        let code = "const WIDTH = 2 * (3 + 4); const AREA = WIDTH * WIDTH - 1;\n\
+                    print AREA + 1; print -WIDTH / 0;\n\
+                    fun f(WIDTH) { return WIDTH + AREA; }\n\
+                    const LABEL = \"w\" + \"=\"; var x = 1; const NEXT = x + WIDTH;\n\
+                    { fun g() { return AREA; } var AREA = 5; print g(); }";
+        let mut scanner = Scanner::new(code.to_string());
+        let statements = Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .expect("program should parse");
+
+        assert_eq!(
+            AstPrinter.print_program(&fold_constants(statements)),
+            "(const WIDTH 14)\n\
+             (const AREA 195)\n\
+             (print 196)\n\
+             (print (/ -14 0))\n\
+             (fun f (WIDTH) (return (+ WIDTH 195)))\n\
+             (const LABEL \"w=\")\n\
+             (var x 1)\n\
+             (const NEXT (+ x 14))\n\
+             (block (fun g () (return AREA)) (var AREA 5) (print (call g)))"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use crate::ast::EnumVariant;
+use crate::value::Value;
+use std::fmt;
+use std::rc::Rc;
+
+// An enum value, its variants are reached through it like `Shape.Circle`
+#[derive(Debug)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<EnumVariant>,
+}
+
+impl Enum {
+    pub fn variant(&self, name: &str) -> Option<usize> {
+        self.variants
+            .iter()
+            .position(|variant| variant.name == name)
+    }
+}
+
+impl fmt::Display for Enum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<enum {}>", self.name)
+    }
+}
+
+// One variant of an enum together with the values of its fields. Two of them are equal when
+// they are the same variant of the same enum with equal fields
+#[derive(Debug)]
+pub struct Tagged {
+    pub enumeration: Rc<Enum>,
+    pub variant: usize,
+    pub values: Vec<Value>,
+}
+
+impl Tagged {
+    pub fn name(&self) -> &str {
+        &self.enumeration.variants[self.variant].name
+    }
+
+    pub fn get(&self, field: &str) -> Option<Value> {
+        let fields = &self.enumeration.variants[self.variant].fields;
+        let index = fields.iter().position(|name| name == field)?;
+        Some(self.values[index].clone())
+    }
+}
+
+impl PartialEq for Tagged {
+    fn eq(&self, other: &Tagged) -> bool {
+        Rc::ptr_eq(&self.enumeration, &other.enumeration)
+            && self.variant == other.variant
+            && self.values == other.values
+    }
+}
+
+// `Shape.Rect(1, 2)`, a variant without fields prints without the parentheses
+impl fmt::Display for Tagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.enumeration.name, self.name())?;
+        if self.values.is_empty() {
+            return Ok(());
+        }
+        write!(f, "(")?;
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", value.repr())?;
+        }
+        write!(f, ")")
+    }
+}
@@ -0,0 +1,76 @@
+use crate::interpreter::RuntimeError;
+use crate::lexer::Span;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+// One scope of variables, lookups that miss here continue in the enclosing scope
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    // The names declared with `const`, the resolver already rejects assigning to them but a
+    // tree that skipped it is stopped here
+    constants: HashSet<String>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment {
+            values: HashMap::new(),
+            constants: HashSet::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            constants: HashSet::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    // Declaring a name that already exists in this scope replaces it
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.constants.remove(name);
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn define_constant(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+        self.constants.insert(name.to_string());
+    }
+
+    pub fn get(&self, name: &str, span: Span) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.values.get(name) {
+            return Ok(value.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name, span),
+            None => Err(undefined(name, span)),
+        }
+    }
+
+    pub fn assign(&mut self, name: &str, span: Span, value: Value) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(name) {
+            if self.constants.contains(name) {
+                let message = format!("Can't assign to constant '{}'.", name);
+                return Err(RuntimeError::new(span, &message));
+            }
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, span, value),
+            None => Err(undefined(name, span)),
+        }
+    }
+}
+
+fn undefined(name: &str, span: Span) -> RuntimeError {
+    RuntimeError::name_error(span, &format!("Undefined variable '{}'.", name))
+}
@@ -0,0 +1,583 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, CatchClause, Class, Conditional, Const, Continue, Enum,
+    Export, Expr, Expression, ForIn, Function, Get, Grouping, If, Import, Index, IndexSet, Lambda,
+    List, Literal, Map, Match, MatchArm, NamedArgument, Parameter, Postfix, Print, Return, Set,
+    Slice, Stmt, Super, This, Throw, Try, Unary, Var, Variable, While, Yield,
+};
+
+// Rebuilds a tree node by node, taking every node by value and returning its replacement.
+// Every method defaults to folding the children and keeping the node as it is, so a pass
+// only overrides the nodes it cares about. A node can be replaced by a different kind, which
+// is why the expression methods return an `Expr`. Overrides that still want the default
+// recursion call the matching `walk_` function
+pub trait Fold {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        walk_expr(self, expr)
+    }
+
+    fn fold_assign(&mut self, expr: Assign) -> Expr {
+        walk_assign(self, expr)
+    }
+
+    fn fold_binary(&mut self, expr: Binary) -> Expr {
+        walk_binary(self, expr)
+    }
+
+    fn fold_call(&mut self, expr: Call) -> Expr {
+        walk_call(self, expr)
+    }
+
+    fn fold_conditional(&mut self, expr: Conditional) -> Expr {
+        walk_conditional(self, expr)
+    }
+
+    fn fold_get(&mut self, expr: Get) -> Expr {
+        walk_get(self, expr)
+    }
+
+    fn fold_grouping(&mut self, expr: Grouping) -> Expr {
+        walk_grouping(self, expr)
+    }
+
+    fn fold_index(&mut self, expr: Index) -> Expr {
+        walk_index(self, expr)
+    }
+
+    fn fold_index_set(&mut self, expr: IndexSet) -> Expr {
+        walk_index_set(self, expr)
+    }
+
+    fn fold_lambda(&mut self, expr: Lambda) -> Expr {
+        walk_lambda(self, expr)
+    }
+
+    fn fold_list(&mut self, expr: List) -> Expr {
+        walk_list(self, expr)
+    }
+
+    fn fold_literal(&mut self, expr: Literal) -> Expr {
+        Expr::Literal(expr)
+    }
+
+    fn fold_map(&mut self, expr: Map) -> Expr {
+        walk_map(self, expr)
+    }
+
+    fn fold_match(&mut self, expr: Match) -> Expr {
+        walk_match(self, expr)
+    }
+
+    fn fold_postfix(&mut self, expr: Postfix) -> Expr {
+        Expr::Postfix(expr)
+    }
+
+    fn fold_set(&mut self, expr: Set) -> Expr {
+        walk_set(self, expr)
+    }
+
+    fn fold_slice(&mut self, expr: Slice) -> Expr {
+        walk_slice(self, expr)
+    }
+
+    fn fold_super(&mut self, expr: Super) -> Expr {
+        Expr::Super(expr)
+    }
+
+    fn fold_this(&mut self, expr: This) -> Expr {
+        Expr::This(expr)
+    }
+
+    fn fold_unary(&mut self, expr: Unary) -> Expr {
+        walk_unary(self, expr)
+    }
+
+    fn fold_variable(&mut self, expr: Variable) -> Expr {
+        Expr::Variable(expr)
+    }
+
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        walk_stmt(self, stmt)
+    }
+
+    fn fold_block(&mut self, stmt: Block) -> Stmt {
+        walk_block(self, stmt)
+    }
+
+    fn fold_break(&mut self, stmt: Break) -> Stmt {
+        Stmt::Break(stmt)
+    }
+
+    fn fold_class(&mut self, stmt: Class) -> Stmt {
+        walk_class(self, stmt)
+    }
+
+    fn fold_const(&mut self, stmt: Const) -> Stmt {
+        walk_const(self, stmt)
+    }
+
+    fn fold_continue(&mut self, stmt: Continue) -> Stmt {
+        Stmt::Continue(stmt)
+    }
+
+    fn fold_enum(&mut self, stmt: Enum) -> Stmt {
+        Stmt::Enum(stmt)
+    }
+
+    fn fold_export(&mut self, stmt: Export) -> Stmt {
+        walk_export(self, stmt)
+    }
+
+    fn fold_expression(&mut self, stmt: Expression) -> Stmt {
+        walk_expression(self, stmt)
+    }
+
+    fn fold_for_in(&mut self, stmt: ForIn) -> Stmt {
+        walk_for_in(self, stmt)
+    }
+
+    fn fold_function(&mut self, stmt: Function) -> Stmt {
+        walk_function(self, stmt)
+    }
+
+    fn fold_if(&mut self, stmt: If) -> Stmt {
+        walk_if(self, stmt)
+    }
+
+    fn fold_import(&mut self, stmt: Import) -> Stmt {
+        Stmt::Import(stmt)
+    }
+
+    fn fold_print(&mut self, stmt: Print) -> Stmt {
+        walk_print(self, stmt)
+    }
+
+    fn fold_return(&mut self, stmt: Return) -> Stmt {
+        walk_return(self, stmt)
+    }
+
+    fn fold_throw(&mut self, stmt: Throw) -> Stmt {
+        walk_throw(self, stmt)
+    }
+
+    fn fold_try(&mut self, stmt: Try) -> Stmt {
+        walk_try(self, stmt)
+    }
+
+    fn fold_var(&mut self, stmt: Var) -> Stmt {
+        walk_var(self, stmt)
+    }
+
+    fn fold_while(&mut self, stmt: While) -> Stmt {
+        walk_while(self, stmt)
+    }
+
+    fn fold_yield(&mut self, stmt: Yield) -> Stmt {
+        walk_yield(self, stmt)
+    }
+}
+
+pub fn fold_program<F: Fold + ?Sized>(folder: &mut F, statements: Vec<Stmt>) -> Vec<Stmt> {
+    statements
+        .into_iter()
+        .map(|statement| folder.fold_stmt(statement))
+        .collect()
+}
+
+// Only the defaults of the parameters are expressions
+pub fn fold_params<F: Fold + ?Sized>(folder: &mut F, params: Vec<Parameter>) -> Vec<Parameter> {
+    params
+        .into_iter()
+        .map(|param| Parameter {
+            default: param.default.map(|default| folder.fold_expr(default)),
+            ..param
+        })
+        .collect()
+}
+
+pub fn walk_expr<F: Fold + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+    match expr {
+        Expr::Assign(a) => folder.fold_assign(a),
+        Expr::Binary(b) => folder.fold_binary(b),
+        Expr::Call(c) => folder.fold_call(c),
+        Expr::Conditional(c) => folder.fold_conditional(c),
+        Expr::Get(g) => folder.fold_get(g),
+        Expr::Grouping(g) => folder.fold_grouping(g),
+        Expr::Index(i) => folder.fold_index(i),
+        Expr::IndexSet(i) => folder.fold_index_set(i),
+        Expr::Lambda(l) => folder.fold_lambda(l),
+        Expr::List(l) => folder.fold_list(l),
+        Expr::Literal(l) => folder.fold_literal(l),
+        Expr::Map(m) => folder.fold_map(m),
+        Expr::Match(m) => folder.fold_match(m),
+        Expr::Postfix(p) => folder.fold_postfix(p),
+        Expr::Set(s) => folder.fold_set(s),
+        Expr::Slice(s) => folder.fold_slice(s),
+        Expr::Super(s) => folder.fold_super(s),
+        Expr::This(t) => folder.fold_this(t),
+        Expr::Unary(u) => folder.fold_unary(u),
+        Expr::Variable(v) => folder.fold_variable(v),
+    }
+}
+
+fn fold_boxed<F: Fold + ?Sized>(folder: &mut F, expr: Expr) -> Box<Expr> {
+    Box::new(folder.fold_expr(expr))
+}
+
+pub fn walk_assign<F: Fold + ?Sized>(folder: &mut F, expr: Assign) -> Expr {
+    Expr::Assign(Assign {
+        value: fold_boxed(folder, *expr.value),
+        ..expr
+    })
+}
+
+pub fn walk_binary<F: Fold + ?Sized>(folder: &mut F, expr: Binary) -> Expr {
+    Expr::Binary(Binary {
+        left: fold_boxed(folder, *expr.left),
+        right: fold_boxed(folder, *expr.right),
+        ..expr
+    })
+}
+
+pub fn walk_call<F: Fold + ?Sized>(folder: &mut F, expr: Call) -> Expr {
+    Expr::Call(Call {
+        callee: fold_boxed(folder, *expr.callee),
+        arguments: expr
+            .arguments
+            .into_iter()
+            .map(|argument| folder.fold_expr(argument))
+            .collect(),
+        named: expr
+            .named
+            .into_iter()
+            .map(|argument| NamedArgument {
+                value: folder.fold_expr(argument.value),
+                ..argument
+            })
+            .collect(),
+        ..expr
+    })
+}
+
+pub fn walk_conditional<F: Fold + ?Sized>(folder: &mut F, expr: Conditional) -> Expr {
+    Expr::Conditional(Conditional {
+        condition: fold_boxed(folder, *expr.condition),
+        then_branch: fold_boxed(folder, *expr.then_branch),
+        else_branch: fold_boxed(folder, *expr.else_branch),
+        ..expr
+    })
+}
+
+pub fn walk_get<F: Fold + ?Sized>(folder: &mut F, expr: Get) -> Expr {
+    Expr::Get(Get {
+        object: fold_boxed(folder, *expr.object),
+        ..expr
+    })
+}
+
+pub fn walk_grouping<F: Fold + ?Sized>(folder: &mut F, expr: Grouping) -> Expr {
+    Expr::Grouping(Grouping {
+        expression: fold_boxed(folder, *expr.expression),
+        ..expr
+    })
+}
+
+pub fn walk_index<F: Fold + ?Sized>(folder: &mut F, expr: Index) -> Expr {
+    Expr::Index(Index {
+        object: fold_boxed(folder, *expr.object),
+        index: fold_boxed(folder, *expr.index),
+        ..expr
+    })
+}
+
+pub fn walk_index_set<F: Fold + ?Sized>(folder: &mut F, expr: IndexSet) -> Expr {
+    Expr::IndexSet(IndexSet {
+        object: fold_boxed(folder, *expr.object),
+        index: fold_boxed(folder, *expr.index),
+        value: fold_boxed(folder, *expr.value),
+        ..expr
+    })
+}
+
+pub fn walk_lambda<F: Fold + ?Sized>(folder: &mut F, expr: Lambda) -> Expr {
+    Expr::Lambda(Lambda {
+        params: fold_params(folder, expr.params),
+        body: fold_program(folder, expr.body.into_statements()).into(),
+        ..expr
+    })
+}
+
+pub fn walk_list<F: Fold + ?Sized>(folder: &mut F, expr: List) -> Expr {
+    Expr::List(List {
+        elements: expr
+            .elements
+            .into_iter()
+            .map(|element| folder.fold_expr(element))
+            .collect(),
+        ..expr
+    })
+}
+
+pub fn walk_map<F: Fold + ?Sized>(folder: &mut F, expr: Map) -> Expr {
+    Expr::Map(Map {
+        keys: expr
+            .keys
+            .into_iter()
+            .map(|key| folder.fold_expr(key))
+            .collect(),
+        values: expr
+            .values
+            .into_iter()
+            .map(|value| folder.fold_expr(value))
+            .collect(),
+        ..expr
+    })
+}
+
+pub fn walk_match<F: Fold + ?Sized>(folder: &mut F, expr: Match) -> Expr {
+    Expr::Match(Match {
+        subject: fold_boxed(folder, *expr.subject),
+        arms: expr
+            .arms
+            .into_iter()
+            .map(|arm| MatchArm {
+                pattern: arm.pattern,
+                guard: arm.guard.map(|guard| folder.fold_expr(guard)),
+                value: folder.fold_expr(arm.value),
+            })
+            .collect(),
+        ..expr
+    })
+}
+
+pub fn walk_set<F: Fold + ?Sized>(folder: &mut F, expr: Set) -> Expr {
+    Expr::Set(Set {
+        object: fold_boxed(folder, *expr.object),
+        value: fold_boxed(folder, *expr.value),
+        ..expr
+    })
+}
+
+pub fn walk_slice<F: Fold + ?Sized>(folder: &mut F, expr: Slice) -> Expr {
+    Expr::Slice(Slice {
+        object: fold_boxed(folder, *expr.object),
+        start: expr.start.map(|start| fold_boxed(folder, *start)),
+        end: expr.end.map(|end| fold_boxed(folder, *end)),
+        ..expr
+    })
+}
+
+pub fn walk_unary<F: Fold + ?Sized>(folder: &mut F, expr: Unary) -> Expr {
+    Expr::Unary(Unary {
+        right: fold_boxed(folder, *expr.right),
+        ..expr
+    })
+}
+
+pub fn walk_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: Stmt) -> Stmt {
+    match stmt {
+        Stmt::Block(b) => folder.fold_block(b),
+        Stmt::Break(b) => folder.fold_break(b),
+        Stmt::Class(c) => folder.fold_class(c),
+        Stmt::Const(c) => folder.fold_const(c),
+        Stmt::Continue(c) => folder.fold_continue(c),
+        Stmt::Enum(e) => folder.fold_enum(e),
+        Stmt::Export(e) => folder.fold_export(e),
+        Stmt::Expression(e) => folder.fold_expression(e),
+        Stmt::ForIn(f) => folder.fold_for_in(f),
+        Stmt::Function(f) => folder.fold_function(f),
+        Stmt::If(i) => folder.fold_if(i),
+        Stmt::Import(i) => folder.fold_import(i),
+        Stmt::Print(p) => folder.fold_print(p),
+        Stmt::Return(r) => folder.fold_return(r),
+        Stmt::Throw(t) => folder.fold_throw(t),
+        Stmt::Try(t) => folder.fold_try(t),
+        Stmt::Var(v) => folder.fold_var(v),
+        Stmt::While(w) => folder.fold_while(w),
+        Stmt::Yield(y) => folder.fold_yield(y),
+    }
+}
+
+pub fn walk_block<F: Fold + ?Sized>(folder: &mut F, stmt: Block) -> Stmt {
+    Stmt::Block(Block {
+        statements: fold_program(folder, stmt.statements),
+        ..stmt
+    })
+}
+
+// Methods aren't statements of their own, so only their bodies are folded
+pub fn walk_class<F: Fold + ?Sized>(folder: &mut F, stmt: Class) -> Stmt {
+    Stmt::Class(Class {
+        methods: stmt
+            .methods
+            .into_iter()
+            .map(|method| Function {
+                params: fold_params(folder, method.params),
+                body: fold_program(folder, method.body.into_statements()).into(),
+                ..method
+            })
+            .collect(),
+        ..stmt
+    })
+}
+
+pub fn walk_const<F: Fold + ?Sized>(folder: &mut F, stmt: Const) -> Stmt {
+    Stmt::Const(Const {
+        initializer: fold_boxed(folder, *stmt.initializer),
+        ..stmt
+    })
+}
+
+pub fn walk_export<F: Fold + ?Sized>(folder: &mut F, stmt: Export) -> Stmt {
+    Stmt::Export(Export {
+        declaration: Box::new(folder.fold_stmt(*stmt.declaration)),
+        ..stmt
+    })
+}
+
+pub fn walk_expression<F: Fold + ?Sized>(folder: &mut F, stmt: Expression) -> Stmt {
+    Stmt::Expression(Expression {
+        expression: fold_boxed(folder, *stmt.expression),
+        ..stmt
+    })
+}
+
+pub fn walk_for_in<F: Fold + ?Sized>(folder: &mut F, stmt: ForIn) -> Stmt {
+    Stmt::ForIn(ForIn {
+        iterable: fold_boxed(folder, *stmt.iterable),
+        body: Box::new(folder.fold_stmt(*stmt.body)),
+        ..stmt
+    })
+}
+
+pub fn walk_function<F: Fold + ?Sized>(folder: &mut F, stmt: Function) -> Stmt {
+    Stmt::Function(Function {
+        params: fold_params(folder, stmt.params),
+        body: fold_program(folder, stmt.body.into_statements()).into(),
+        ..stmt
+    })
+}
+
+pub fn walk_if<F: Fold + ?Sized>(folder: &mut F, stmt: If) -> Stmt {
+    Stmt::If(If {
+        condition: fold_boxed(folder, *stmt.condition),
+        then_branch: Box::new(folder.fold_stmt(*stmt.then_branch)),
+        else_branch: stmt
+            .else_branch
+            .map(|else_branch| Box::new(folder.fold_stmt(*else_branch))),
+        ..stmt
+    })
+}
+
+pub fn walk_print<F: Fold + ?Sized>(folder: &mut F, stmt: Print) -> Stmt {
+    Stmt::Print(Print {
+        expression: fold_boxed(folder, *stmt.expression),
+        ..stmt
+    })
+}
+
+pub fn walk_return<F: Fold + ?Sized>(folder: &mut F, stmt: Return) -> Stmt {
+    Stmt::Return(Return {
+        value: stmt.value.map(|value| fold_boxed(folder, *value)),
+        ..stmt
+    })
+}
+
+pub fn walk_throw<F: Fold + ?Sized>(folder: &mut F, stmt: Throw) -> Stmt {
+    Stmt::Throw(Throw {
+        value: fold_boxed(folder, *stmt.value),
+        ..stmt
+    })
+}
+
+pub fn walk_try<F: Fold + ?Sized>(folder: &mut F, stmt: Try) -> Stmt {
+    Stmt::Try(Try {
+        body: fold_program(folder, stmt.body),
+        catch_clause: stmt.catch_clause.map(|clause| CatchClause {
+            body: fold_program(folder, clause.body),
+            ..clause
+        }),
+        finally_body: fold_program(folder, stmt.finally_body),
+        ..stmt
+    })
+}
+
+pub fn walk_var<F: Fold + ?Sized>(folder: &mut F, stmt: Var) -> Stmt {
+    Stmt::Var(Var {
+        initializer: stmt
+            .initializer
+            .map(|initializer| fold_boxed(folder, *initializer)),
+        ..stmt
+    })
+}
+
+pub fn walk_while<F: Fold + ?Sized>(folder: &mut F, stmt: While) -> Stmt {
+    Stmt::While(While {
+        condition: fold_boxed(folder, *stmt.condition),
+        body: Box::new(folder.fold_stmt(*stmt.body)),
+        increment: stmt
+            .increment
+            .map(|increment| fold_boxed(folder, *increment)),
+        ..stmt
+    })
+}
+
+pub fn walk_yield<F: Fold + ?Sized>(folder: &mut F, stmt: Yield) -> Stmt {
+    Stmt::Yield(Yield {
+        value: fold_boxed(folder, *stmt.value),
+        ..stmt
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{BinaryOperator, LiteralValue};
+    use crate::ast_printer::AstPrinter;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    // Folds `+` between two integer literals, the children are folded first so whole chains
+    // collapse
+    struct AddConstants;
+
+    impl Fold for AddConstants {
+        fn fold_binary(&mut self, expr: Binary) -> Expr {
+            let folded = match walk_binary(self, expr) {
+                Expr::Binary(binary) => binary,
+                other => return other,
+            };
+
+            match (&*folded.left, folded.operator, &*folded.right) {
+                (Expr::Literal(left), BinaryOperator::Add, Expr::Literal(right)) => {
+                    match (&left.value, &right.value) {
+                        (LiteralValue::Integer(a), LiteralValue::Integer(b)) => {
+                            Expr::Literal(Literal {
+                                id: folded.id,
+                                value: LiteralValue::Integer(a + b),
+                                span: folded.span,
+                            })
+                        }
+                        _ => Expr::Binary(folded),
+                    }
+                }
+                _ => Expr::Binary(folded),
+            }
+        }
+    }
+
+    #[test]
+    fn overridden_methods_rewrite_nested_nodes() {
+        let code = "var a = 1 + 2 + 3; { print -(a + 4 + 5); }";
+        let mut scanner = Scanner::new(code.to_string());
+        let statements = Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .expect("program should parse");
+
+        let folded = fold_program(&mut AddConstants, statements);
+
+        assert_eq!(
+            AstPrinter.print_program(&folded),
+            "(var a 6)\n(block (print (- (group (+ (+ a 4) 5)))))"
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::ast::{Body, Parameter};
+use crate::environment::Environment;
+use crate::interpreter::RuntimeError;
+use crate::lexer::Span;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+// A function value, both named declarations and lambdas become one. The closure is the scope it
+// was created in, a method's closure is the scope that binds `this`
+pub struct Function {
+    pub declaration: Rc<Declaration>,
+    pub closure: Rc<RefCell<Environment>>,
+    // `init` methods hand back their instance instead of what they return
+    pub is_initializer: bool,
+}
+
+impl Function {
+    // The same function inside a new scope that defines `this` as the instance
+    pub fn bind(&self, instance: Value) -> Function {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define("this", instance);
+        Function {
+            declaration: Rc::clone(&self.declaration),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+// What the function values made from one declaration have in common, so binding a method to an
+// instance only makes the scope for `this`. The body is shared with the tree, which lets the
+// function outlive the statements it came from
+pub struct Declaration {
+    pub name: Option<String>,
+    pub params: Vec<Parameter>,
+    pub arity: Arity,
+    pub body: Body,
+}
+
+impl Declaration {
+    pub fn new(name: Option<String>, params: &[Parameter], body: &Body) -> Rc<Declaration> {
+        Rc::new(Declaration {
+            name,
+            params: params.to_vec(),
+            arity: Arity::of(params),
+            body: body.clone(),
+        })
+    }
+}
+
+// The closure can hold the function itself, so it is left out
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.declaration.name {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<fn>"),
+        }
+    }
+}
+
+// What a call has to pass, worked out from the parameters once. The resolver makes sure
+// those with a default come after those without one and that only the last one collects the
+// rest, but a tree that skipped it still binds without surprises
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arity {
+    pub names: Vec<String>,
+    pub defaults: Vec<bool>,
+    pub rest: bool,
+}
+
+// Where a parameter gets its value from on one call
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slot {
+    // The positional arguments are counted first, then the named ones in the order they are
+    // written
+    Argument(usize),
+    Default,
+    // The positional arguments from this one on for the rest parameter, none when fewer were
+    // passed than the parameters before it
+    Rest(usize),
+}
+
+impl Arity {
+    pub fn of(params: &[Parameter]) -> Arity {
+        let rest = params.last().is_some_and(|param| param.rest);
+        let fixed = &params[..params.len() - rest as usize];
+        Arity {
+            names: params.iter().map(|param| param.name.clone()).collect(),
+            defaults: fixed.iter().map(|param| param.default.is_some()).collect(),
+            rest,
+        }
+    }
+
+    // The positional arguments fill the parameters in order and the rest parameter takes those
+    // left over, a named one fills the parameter with its name. Gives a slot for each
+    // parameter, or the message for a call that doesn't fit
+    pub fn bind(&self, positional: usize, named: &[&str]) -> Result<Vec<Slot>, String> {
+        let fixed = self.defaults.len();
+        let given = positional + named.len();
+        if given < self.required() || (!self.rest && positional > fixed) {
+            return Err(self.expected(given));
+        }
+
+        let mut slots: Vec<Option<Slot>> = (0..fixed)
+            .map(|i| (i < positional).then_some(Slot::Argument(i)))
+            .collect();
+        for (i, name) in named.iter().enumerate() {
+            let index = match self.names.iter().position(|param| param == name) {
+                Some(index) if index < fixed => index,
+                Some(_) => {
+                    return Err(format!("Can't pass the rest parameter '{}' by name.", name))
+                }
+                None => return Err(format!("No parameter named '{}'.", name)),
+            };
+            if slots[index].is_some() {
+                return Err(format!("Argument '{}' is passed more than once.", name));
+            }
+            slots[index] = Some(Slot::Argument(positional + i));
+        }
+
+        let mut bound = Vec::with_capacity(self.names.len());
+        for (i, slot) in slots.into_iter().enumerate() {
+            match slot {
+                Some(slot) => bound.push(slot),
+                None if self.defaults[i] => bound.push(Slot::Default),
+                None => return Err(format!("Missing argument for '{}'.", self.names[i])),
+            }
+        }
+        if self.rest {
+            bound.push(Slot::Rest(fixed.min(positional)));
+        }
+        Ok(bound)
+    }
+
+    fn required(&self) -> usize {
+        self.defaults.iter().filter(|default| !**default).count()
+    }
+
+    fn expected(&self, got: usize) -> String {
+        let (required, fixed) = (self.required(), self.defaults.len());
+        if self.rest {
+            format!("Expected at least {} arguments but got {}.", required, got)
+        } else if required == fixed {
+            format!("Expected {} arguments but got {}.", fixed, got)
+        } else {
+            format!(
+                "Expected {} to {} arguments but got {}.",
+                required, fixed, got
+            )
+        }
+    }
+}
+
+// A function built into the interpreter, the span points at the call for its errors
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&[Value], Span) -> Result<Value, RuntimeError>,
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
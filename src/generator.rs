@@ -0,0 +1,228 @@
+use crate::ast::{Body, ForIn, Stmt, Try, While};
+use crate::environment::Environment;
+use crate::interpreter::{Iteration, Unwind};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::slice;
+
+// What calling a function whose body yields gives back. The body runs on a stack of frames
+// instead of the interpreter's own, so it can stop at a `yield` and pick up from there on the
+// next call to `next()`
+pub struct Generator {
+    pub name: Option<String>,
+    // Taken out while the body runs, and left empty once it has finished
+    pub frames: RefCell<Option<Vec<Frame>>>,
+}
+
+impl Generator {
+    pub fn new(name: Option<String>, body: &Body, environment: Environment) -> Generator {
+        let frame = Frame::block(Statements::of(body), Rc::new(RefCell::new(environment)));
+        Generator {
+            name,
+            frames: RefCell::new(Some(vec![frame])),
+        }
+    }
+}
+
+// The frames are left out, they can hold the generator itself
+impl fmt::Debug for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl fmt::Display for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<generator {}>", name),
+            None => write!(f, "<generator>"),
+        }
+    }
+}
+
+// How far the body got inside one of its statements, the innermost is on top of the stack
+pub enum Frame {
+    // The statements of a block, a function body or a branch, run one at a time in its scope
+    Block {
+        statements: Statements,
+        next: usize,
+        environment: Rc<RefCell<Environment>>,
+    },
+    // Checks the condition again once the body is done, after running the increment
+    While {
+        stmt: Statement,
+        body: Statements,
+        ran_body: bool,
+        environment: Rc<RefCell<Environment>>,
+    },
+    ForIn {
+        stmt: Statement,
+        body: Statements,
+        iteration: Iteration,
+        environment: Rc<RefCell<Environment>>,
+    },
+    // Whatever was unwinding when the finally body started goes on once it is done
+    Try {
+        stmt: Statement,
+        stage: Stage,
+        pending: Option<Unwind>,
+        environment: Rc<RefCell<Environment>>,
+    },
+}
+
+impl Frame {
+    pub fn block(statements: Statements, environment: Rc<RefCell<Environment>>) -> Frame {
+        Frame::Block {
+            statements,
+            next: 0,
+            environment,
+        }
+    }
+}
+
+// Statements somewhere in a generator's body. The frames share the body with the function and
+// find what they run again from it, through the index of each statement it is nested in
+#[derive(Clone)]
+pub struct Statements {
+    body: Body,
+    path: Vec<(usize, Nested)>,
+}
+
+impl Statements {
+    pub fn of(body: &Body) -> Statements {
+        Statements {
+            body: body.clone(),
+            path: Vec::new(),
+        }
+    }
+
+    pub fn get(&self) -> &[Stmt] {
+        self.path
+            .iter()
+            .fold(&self.body, |statements, &(index, nested)| {
+                nested.of(&statements[index])
+            })
+    }
+
+    // The statements that the one at `index` holds
+    pub fn nested(&self, index: usize, nested: Nested) -> Statements {
+        let mut path = self.path.clone();
+        path.push((index, nested));
+        Statements {
+            body: self.body.clone(),
+            path,
+        }
+    }
+
+    pub fn statement(&self, index: usize) -> Statement {
+        Statement {
+            statements: self.clone(),
+            index,
+        }
+    }
+}
+
+// The loop or try statement a frame runs the parts of
+#[derive(Clone)]
+pub struct Statement {
+    statements: Statements,
+    index: usize,
+}
+
+impl Statement {
+    pub fn nested(&self, nested: Nested) -> Statements {
+        self.statements.nested(self.index, nested)
+    }
+
+    pub fn as_while(&self) -> &While {
+        match &self.statements.get()[self.index] {
+            Stmt::While(stmt) => stmt,
+            other => unreachable!("a while frame runs a while loop, not {:?}", other),
+        }
+    }
+
+    pub fn as_for_in(&self) -> &ForIn {
+        match &self.statements.get()[self.index] {
+            Stmt::ForIn(stmt) => stmt,
+            other => unreachable!("a for-in frame runs a for-in loop, not {:?}", other),
+        }
+    }
+
+    pub fn as_try(&self) -> &Try {
+        match &self.statements.get()[self.index] {
+            Stmt::Try(stmt) => stmt,
+            other => unreachable!("a try frame runs a try statement, not {:?}", other),
+        }
+    }
+}
+
+// Which of the statements inside a statement, a branch or a loop body on its own counts as
+// a list of one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Nested {
+    Block,
+    Then,
+    Else,
+    Loop,
+    Try,
+    Catch,
+    Finally,
+}
+
+impl Nested {
+    fn of(self, statement: &Stmt) -> &[Stmt] {
+        match (self, statement) {
+            (Nested::Block, Stmt::Block(block)) => &block.statements,
+            (Nested::Then, Stmt::If(stmt)) => slice::from_ref(&stmt.then_branch),
+            (Nested::Else, Stmt::If(stmt)) => {
+                stmt.else_branch.as_deref().map_or(&[], slice::from_ref)
+            }
+            (Nested::Loop, Stmt::While(stmt)) => slice::from_ref(&stmt.body),
+            (Nested::Loop, Stmt::ForIn(stmt)) => slice::from_ref(&stmt.body),
+            (Nested::Try, Stmt::Try(stmt)) => &stmt.body,
+            (Nested::Catch, Stmt::Try(stmt)) => stmt
+                .catch_clause
+                .as_ref()
+                .map_or(&[], |clause| &clause.body),
+            (Nested::Finally, Stmt::Try(stmt)) => &stmt.finally_body,
+            (nested, statement) => unreachable!("{:?} has no {:?} statements", statement, nested),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Start,
+    Body,
+    Catch,
+    Finally,
+}
+
+// Whether a function body yields, which makes calling the function start a generator. The
+// bodies of functions declared inside it yield for themselves
+pub fn yields(statements: &[Stmt]) -> bool {
+    statements.iter().any(statement_yields)
+}
+
+fn statement_yields(statement: &Stmt) -> bool {
+    match statement {
+        Stmt::Yield(_) => true,
+        Stmt::Block(block) => yields(&block.statements),
+        Stmt::If(stmt) => {
+            statement_yields(&stmt.then_branch)
+                || stmt.else_branch.as_deref().is_some_and(statement_yields)
+        }
+        Stmt::While(stmt) => statement_yields(&stmt.body),
+        Stmt::ForIn(stmt) => statement_yields(&stmt.body),
+        Stmt::Try(stmt) => {
+            yields(&stmt.body)
+                || stmt
+                    .catch_clause
+                    .as_ref()
+                    .is_some_and(|clause| yields(&clause.body))
+                || yields(&stmt.finally_body)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,2597 @@
+use crate::ast::{
+    Assign, Binary, BinaryOperator, Block, Break, Call, Class, Conditional, Const, Continue, Enum,
+    Export, Expr, ExprVisitor, Expression, ForIn, Function, Get, Grouping, If, Import, Index,
+    IndexSet, Lambda, List, Literal, Map, Match, MatchArm, Pattern, Postfix, PostfixOperator,
+    Print, Return, Set, Slice, Stmt, StmtVisitor, Store, Super, This, Throw, Try, Unary,
+    UnaryOperator, Var, Variable, While, Yield,
+};
+use crate::class;
+use crate::enumeration::{self, Tagged};
+use crate::environment::Environment;
+use crate::function::{self, Declaration, Slot};
+use crate::generator::{self, Frame, Generator, Nested, Stage, Statements};
+use crate::lexer::{Scanner, Span};
+use crate::map;
+use crate::module::{Module, ModuleLoader};
+use crate::natives::define_natives;
+use crate::parser::Parser;
+use crate::pipeline::check_program;
+use crate::prelude;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
+
+pub struct Interpreter {
+    environment: Rc<RefCell<Environment>>,
+    // The natives and the prelude, every module's globals are enclosed by them
+    builtins: Rc<RefCell<Environment>>,
+    modules: ModuleLoader,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let mut builtins = Environment::new();
+        define_natives(&mut builtins);
+        let builtins = Rc::new(RefCell::new(builtins));
+
+        let mut interpreter = Interpreter {
+            environment: Rc::clone(&builtins),
+            builtins,
+            modules: ModuleLoader::default(),
+        };
+        interpreter
+            .interpret(&prelude::statements())
+            .expect("the prelude should run");
+        interpreter.environment = interpreter.globals();
+        interpreter
+    }
+
+    // Runs the file at `path`, the modules it imports are found next to it
+    pub fn for_script(path: &Path) -> Self {
+        Interpreter {
+            modules: ModuleLoader::for_script(path),
+            ..Interpreter::new()
+        }
+    }
+
+    // Runs a whole program, stopping at the first runtime error
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        for statement in statements {
+            self.execute(statement).map_err(Unwind::into_error)?;
+        }
+        Ok(())
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        expr.accept(self)
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        stmt.accept(self)
+    }
+
+    // The previous scope is restored even when a statement fails or returns
+    fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Environment,
+    ) -> Result<(), Unwind> {
+        let previous = Rc::clone(&self.environment);
+        self.environment = Rc::new(RefCell::new(environment));
+
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
+
+        self.environment = previous;
+        result
+    }
+
+    // The arguments are bound in a new scope inside the function's closure, where the defaults
+    // of the parameters left out are evaluated in order. The body gets a scope of its own
+    fn call(
+        &mut self,
+        function: &function::Function,
+        arguments: Vec<Value>,
+        named: Vec<(String, Value)>,
+        span: Span,
+    ) -> Result<Value, RuntimeError> {
+        let declaration = &function.declaration;
+        let slots = {
+            let names: Vec<&str> = named.iter().map(|(name, _)| name.as_str()).collect();
+            declaration
+                .arity
+                .bind(arguments.len(), &names)
+                .map_err(|message| RuntimeError::type_error(span, &message))?
+        };
+
+        let positional = arguments.len();
+        let mut values: Vec<Option<Value>> = arguments
+            .into_iter()
+            .chain(named.into_iter().map(|(_, value)| value))
+            .map(Some)
+            .collect();
+        let parameters = Rc::new(RefCell::new(Environment::with_enclosing(Rc::clone(
+            &function.closure,
+        ))));
+        for (param, slot) in declaration.params.iter().zip(slots) {
+            let value = match slot {
+                Slot::Argument(i) => values[i].take().expect("an argument fills one parameter"),
+                Slot::Rest(start) => {
+                    let rest = values[start..positional].iter_mut().flat_map(Option::take);
+                    Value::List(Rc::new(RefCell::new(rest.collect())))
+                }
+                Slot::Default => {
+                    let default = param.default.as_ref().expect("the parameter has a default");
+                    let previous = mem::replace(&mut self.environment, Rc::clone(&parameters));
+                    let value = self.evaluate(default);
+                    self.environment = previous;
+                    value?
+                }
+            };
+            parameters.borrow_mut().define(&param.name, value);
+        }
+
+        let environment = Environment::with_enclosing(parameters);
+        if declaration.body.is_generator() {
+            let generator =
+                Generator::new(declaration.name.clone(), &declaration.body, environment);
+            return Ok(Value::Generator(Rc::new(generator)));
+        }
+
+        let result = match self.execute_block(&declaration.body, environment) {
+            Ok(()) => Value::Nil,
+            Err(Unwind::Return(value, _)) => value,
+            Err(unwind) => return Err(unwind.into_error()),
+        };
+
+        // An initializer hands back the instance, even from an early `return;`
+        if function.is_initializer {
+            return function.closure.borrow().get("this", span);
+        }
+        Ok(result)
+    }
+
+    // Lists, ranges and maps are iterated directly. An instance with an `iter()` method is
+    // iterated through what that returns, and one with a `next()` method is its own iterator
+    fn iteration(&mut self, iterable: Value, span: Span) -> Result<Iteration, RuntimeError> {
+        let iterable = match iterable {
+            Value::Instance(instance) if instance.borrow().class.find_method("iter").is_some() => {
+                let iter = class::Instance::get(&instance, "iter");
+                self.call_method(iter, span)?
+            }
+            other => other,
+        };
+
+        match iterable {
+            Value::List(list) => Ok(Iteration::List(list, 0)),
+            Value::Generator(generator) => Ok(Iteration::Generator(generator)),
+            Value::Range(start, end) => Ok(Iteration::Range(start, end)),
+            Value::Map(map) => {
+                let entries: Vec<(Value, Value)> = map
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                Ok(Iteration::Entries(entries.into_iter()))
+            }
+            Value::Instance(instance) => match class::Instance::get(&instance, "next") {
+                Some(Value::Function(next)) => Ok(Iteration::Iterator(next)),
+                _ => Err(RuntimeError::type_error(
+                    span,
+                    &format!(
+                        "'{}' instances need an 'iter' or a 'next' method to be iterated.",
+                        instance.borrow().class.name
+                    ),
+                )),
+            },
+            other => Err(RuntimeError::type_error(
+                span,
+                &format!("Can't iterate over a {}.", other.type_name()),
+            )),
+        }
+    }
+
+    // The next item, and its value when iterating a map. An iterator's `next()` returns nil
+    // once it is done
+    fn next_item(
+        &mut self,
+        iteration: &mut Iteration,
+        span: Span,
+    ) -> Result<Option<(Value, Option<Value>)>, RuntimeError> {
+        let item = match iteration {
+            // The length is checked on every pass, so the body can grow or shrink the list
+            Iteration::List(list, index) => {
+                let item = list.borrow().get(*index).cloned();
+                *index += 1;
+                item
+            }
+            Iteration::Range(start, end) if *start < *end => {
+                *start += 1;
+                Some(Value::Integer(*start - 1))
+            }
+            Iteration::Range(..) => None,
+            Iteration::Entries(entries) => {
+                return Ok(entries.next().map(|(key, value)| (key, Some(value))))
+            }
+            Iteration::Iterator(next) => match self.call(next, Vec::new(), Vec::new(), span)? {
+                Value::Nil => None,
+                item => Some(item),
+            },
+            // Unlike `next()` a generator can hand nil to the loop, only finishing ends it
+            Iteration::Generator(generator) => self.resume(generator, span)?,
+        };
+        Ok(item.map(|item| (item, None)))
+    }
+
+    // The iteration of a for-in loop, only a map has a value to go with each key
+    fn for_in_iteration(&mut self, stmt: &ForIn) -> Result<Iteration, RuntimeError> {
+        let iterable = self.evaluate(&stmt.iterable)?;
+        let span = stmt.iterable.span();
+        let iteration = self.iteration(iterable, span)?;
+        if stmt.names.len() == 2 && !matches!(iteration, Iteration::Entries(_)) {
+            let message = "Only maps can be iterated with a key and a value.";
+            return Err(RuntimeError::type_error(span, message));
+        }
+        Ok(iteration)
+    }
+
+    // Every pass gets a new scope, so closures made in the body keep the item of their pass
+    fn pass_scope(&self, names: &[String], item: Value, value: Option<Value>) -> Environment {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        environment.define(&names[0], item);
+        if let (Some(name), Some(value)) = (names.get(1), value) {
+            environment.define(name, value);
+        }
+        environment
+    }
+
+    // Runs a generator's body up to its next `yield`, None once the body has finished. A
+    // `return` finishes it too, the value it gives is dropped. So does an error, after it has
+    // reached the caller
+    fn resume(&mut self, generator: &Generator, span: Span) -> Result<Option<Value>, RuntimeError> {
+        let mut frames = generator
+            .frames
+            .borrow_mut()
+            .take()
+            .ok_or_else(|| RuntimeError::new(span, "Generator is already running."))?;
+        let previous = Rc::clone(&self.environment);
+        let result = self.run_frames(&mut frames);
+        self.environment = previous;
+
+        let result = match result {
+            Ok(Some(value)) => {
+                *generator.frames.borrow_mut() = Some(frames);
+                return Ok(Some(value));
+            }
+            Ok(None) | Err(Unwind::Return(..)) => Ok(None),
+            Err(unwind) => Err(unwind.into_error()),
+        };
+        *generator.frames.borrow_mut() = Some(Vec::new());
+        result
+    }
+
+    fn run_frames(&mut self, frames: &mut Vec<Frame>) -> Result<Option<Value>, Unwind> {
+        while let Some(frame) = frames.last_mut() {
+            match self.step(frame) {
+                Ok(Step::Next) => {}
+                Ok(Step::Push(frame)) => frames.push(frame),
+                Ok(Step::Pop) => {
+                    frames.pop();
+                }
+                Ok(Step::Yield(value)) => return Ok(Some(value)),
+                Err(unwind) => self.unwind(frames, unwind)?,
+            }
+        }
+        Ok(None)
+    }
+
+    // Moves the frame on top of a generator's stack one statement or one pass further
+    fn step(&mut self, frame: &mut Frame) -> Result<Step, Unwind> {
+        match frame {
+            Frame::Block {
+                statements,
+                next,
+                environment,
+            } => {
+                let index = *next;
+                if index == statements.get().len() {
+                    return Ok(Step::Pop);
+                }
+                *next += 1;
+                self.environment = Rc::clone(environment);
+                self.begin(statements, index)
+            }
+            Frame::While {
+                stmt,
+                body,
+                ran_body,
+                environment,
+            } => {
+                let stmt = stmt.as_while();
+                self.environment = Rc::clone(environment);
+                if let (true, Some(increment)) = (*ran_body, &stmt.increment) {
+                    self.evaluate(increment)?;
+                }
+                if !self.evaluate(&stmt.condition)?.is_truthy() {
+                    return Ok(Step::Pop);
+                }
+                *ran_body = true;
+                let body = Frame::block(body.clone(), Rc::clone(environment));
+                Ok(Step::Push(body))
+            }
+            Frame::ForIn {
+                stmt,
+                body,
+                iteration,
+                environment,
+            } => {
+                let stmt = stmt.as_for_in();
+                self.environment = Rc::clone(environment);
+                match self.next_item(iteration, stmt.iterable.span())? {
+                    Some((item, value)) => {
+                        let scope = self.pass_scope(&stmt.names, item, value);
+                        let body = Frame::block(body.clone(), Rc::new(RefCell::new(scope)));
+                        Ok(Step::Push(body))
+                    }
+                    None => Ok(Step::Pop),
+                }
+            }
+            Frame::Try {
+                stmt,
+                stage,
+                pending,
+                environment,
+            } => {
+                let statements = match stage {
+                    Stage::Start => {
+                        *stage = Stage::Body;
+                        stmt.nested(Nested::Try)
+                    }
+                    Stage::Body | Stage::Catch => {
+                        *stage = Stage::Finally;
+                        stmt.nested(Nested::Finally)
+                    }
+                    Stage::Finally => {
+                        return match pending.take() {
+                            Some(unwind) => Err(unwind),
+                            None => Ok(Step::Pop),
+                        }
+                    }
+                };
+                let scope = Environment::with_enclosing(Rc::clone(environment));
+                let block = Frame::block(statements, Rc::new(RefCell::new(scope)));
+                Ok(Step::Push(block))
+            }
+        }
+    }
+
+    // Starts a statement of a generator's body. One with a `yield` inside gets a frame of its
+    // own to stop in, the rest run to the end right away
+    fn begin(&mut self, statements: &Statements, index: usize) -> Result<Step, Unwind> {
+        let statement = &statements.get()[index];
+        if !generator::yields(std::slice::from_ref(statement)) {
+            self.execute(statement)?;
+            return Ok(Step::Next);
+        }
+
+        let environment = Rc::clone(&self.environment);
+        let frame = match statement {
+            Stmt::Yield(stmt) => return Ok(Step::Yield(self.evaluate(&stmt.value)?)),
+            Stmt::Block(_) => {
+                let scope = Environment::with_enclosing(environment);
+                Frame::block(
+                    statements.nested(index, Nested::Block),
+                    Rc::new(RefCell::new(scope)),
+                )
+            }
+            Stmt::If(stmt) => {
+                let branch = if self.evaluate(&stmt.condition)?.is_truthy() {
+                    Nested::Then
+                } else if stmt.else_branch.is_some() {
+                    Nested::Else
+                } else {
+                    return Ok(Step::Next);
+                };
+                Frame::block(statements.nested(index, branch), environment)
+            }
+            Stmt::While(_) => Frame::While {
+                stmt: statements.statement(index),
+                body: statements.nested(index, Nested::Loop),
+                ran_body: false,
+                environment,
+            },
+            Stmt::ForIn(stmt) => Frame::ForIn {
+                iteration: self.for_in_iteration(stmt)?,
+                stmt: statements.statement(index),
+                body: statements.nested(index, Nested::Loop),
+                environment,
+            },
+            Stmt::Try(_) => Frame::Try {
+                stmt: statements.statement(index),
+                stage: Stage::Start,
+                pending: None,
+                environment,
+            },
+            other => unreachable!(
+                "only statements that hold others can yield, not {:?}",
+                other
+            ),
+        };
+        Ok(Step::Push(frame))
+    }
+
+    // Pops frames until one takes the unwind: a loop its `break` or `continue`, a try its error
+    // or anything on its way to the finally body. Past the last frame it ends the body
+    fn unwind(&mut self, frames: &mut Vec<Frame>, mut unwind: Unwind) -> Result<(), Unwind> {
+        while let Some(mut frame) = frames.pop() {
+            let label = match &frame {
+                Frame::While { stmt, .. } => Some(stmt.as_while().label.clone()),
+                Frame::ForIn { stmt, .. } => Some(stmt.as_for_in().label.clone()),
+                _ => None,
+            };
+            if let Some(label) = label {
+                unwind = match keeps_looping(&label, Err(unwind)) {
+                    Ok(true) => {
+                        frames.push(frame);
+                        return Ok(());
+                    }
+                    Ok(false) => return Ok(()),
+                    Err(unwind) => unwind,
+                };
+                continue;
+            }
+
+            let block = match &mut frame {
+                Frame::Try {
+                    stmt,
+                    stage,
+                    pending,
+                    environment,
+                } if *stage != Stage::Finally => {
+                    let mut scope = Environment::with_enclosing(Rc::clone(environment));
+                    let statements = match (unwind, &stmt.as_try().catch_clause) {
+                        (Unwind::Error(error), Some(clause)) if *stage == Stage::Body => {
+                            match self.caught(error) {
+                                Ok(value) => {
+                                    scope.define(&clause.name, value);
+                                    *stage = Stage::Catch;
+                                    stmt.nested(Nested::Catch)
+                                }
+                                Err(error) => {
+                                    unwind = Unwind::Error(error);
+                                    continue;
+                                }
+                            }
+                        }
+                        (other, _) => {
+                            *pending = Some(other);
+                            *stage = Stage::Finally;
+                            stmt.nested(Nested::Finally)
+                        }
+                    };
+                    Frame::block(statements, Rc::new(RefCell::new(scope)))
+                }
+                _ => continue,
+            };
+            frames.push(frame);
+            frames.push(block);
+            return Ok(());
+        }
+        Err(unwind)
+    }
+
+    fn call_method(&mut self, method: Option<Value>, span: Span) -> Result<Value, RuntimeError> {
+        match method {
+            Some(Value::Function(function)) => self.call(&function, Vec::new(), Vec::new(), span),
+            _ => Err(RuntimeError::type_error(
+                span,
+                "Can only call functions and classes.",
+            )),
+        }
+    }
+
+    // A new instance, `init` runs on it with the arguments of the call
+    fn instantiate(
+        &mut self,
+        class: &Rc<class::Class>,
+        arguments: Vec<Value>,
+        named: Vec<(String, Value)>,
+        span: Span,
+    ) -> Result<Value, RuntimeError> {
+        let instance = class::Instance::new(Rc::clone(class));
+        let instance = Value::Instance(Rc::new(RefCell::new(instance)));
+
+        match class.find_method("init") {
+            Some(init) => self.call(&init.bind(instance), arguments, named, span),
+            None => {
+                check_arity(0, arguments.len() + named.len(), span)?;
+                Ok(instance)
+            }
+        }
+    }
+
+    // Runs an arm whose pattern matched in a scope that holds its bindings. A guard that turns
+    // out false gives None, so the next arm gets its turn
+    fn evaluate_arm(
+        &mut self,
+        arm: &MatchArm,
+        bindings: Vec<(String, Value)>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        for (name, value) in bindings {
+            environment.define(&name, value);
+        }
+        let previous = mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+
+        let guard = match &arm.guard {
+            Some(guard) => self.evaluate(guard).map(|guard| guard.is_truthy()),
+            None => Ok(true),
+        };
+        let result = match guard {
+            Ok(true) => self.evaluate(&arm.value).map(Some),
+            Ok(false) => Ok(None),
+            Err(error) => Err(error),
+        };
+
+        self.environment = previous;
+        result
+    }
+
+    // A module runs the first time it's imported, later imports share its exports
+    fn import(&mut self, path: &str, span: Span) -> Result<Rc<Module>, RuntimeError> {
+        let file = self.modules.resolve(path).canonicalize().map_err(|e| {
+            RuntimeError::new(span, &format!("Could not read module '{}': {}.", path, e))
+        })?;
+        if let Some(module) = self.modules.get(&file) {
+            return Ok(module);
+        }
+
+        self.modules
+            .enter(&file, path)
+            .map_err(|cycle| RuntimeError::new(span, &format!("Import cycle: {}.", cycle)))?;
+        let module = self.load_module(&file, path, span);
+        self.modules.leave(module.as_ref().ok());
+        module
+    }
+
+    // The module gets globals of its own, so its names don't leak into the importer
+    fn load_module(
+        &mut self,
+        file: &Path,
+        path: &str,
+        span: Span,
+    ) -> Result<Rc<Module>, RuntimeError> {
+        let code = fs::read_to_string(file).map_err(|e| {
+            RuntimeError::new(span, &format!("Could not read module '{}': {}.", path, e))
+        })?;
+        let mut scanner = Scanner::new(code);
+        let statements = Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .ok_or_else(|| {
+                RuntimeError::new(span, &format!("Could not parse module '{}'.", path))
+            })?;
+
+        // A module is checked like the script that imports it, its warnings are only printed
+        let checked = check_program(statements);
+        for warning in &checked.warnings {
+            eprintln!("{}", warning);
+        }
+        let statements = checked.statements.map_err(|errors| {
+            let message = format!("Module '{}' has errors:\n{}", path, errors.join("\n"));
+            RuntimeError::new(span, &message)
+        })?;
+
+        let globals = self.globals();
+        let previous = mem::replace(&mut self.environment, Rc::clone(&globals));
+        let result = self.interpret(&statements);
+        self.environment = previous;
+        result?;
+
+        let mut exports = HashMap::new();
+        for statement in &statements {
+            if let Stmt::Export(export) = statement {
+                let name = declared_name(&export.declaration);
+                exports.insert(name.to_string(), globals.borrow().get(name, span)?);
+            }
+        }
+        Ok(Rc::new(Module {
+            name: path.to_string(),
+            exports,
+        }))
+    }
+
+    fn globals(&self) -> Rc<RefCell<Environment>> {
+        let globals = Environment::with_enclosing(Rc::clone(&self.builtins));
+        Rc::new(RefCell::new(globals))
+    }
+
+    // A thrown value is caught as it is, the interpreter's own errors become instances of the
+    // prelude's error classes
+    fn caught(&mut self, error: RuntimeError) -> Result<Value, RuntimeError> {
+        if let Some(value) = error.thrown {
+            return Ok(value);
+        }
+
+        let class = match self
+            .builtins
+            .borrow()
+            .get(error.kind.class_name(), error.span)?
+        {
+            Value::Class(class) => class,
+            _ => unreachable!("the prelude defines the error classes"),
+        };
+        let value = self.instantiate(
+            &class,
+            vec![Value::String(error.message)],
+            Vec::new(),
+            error.span,
+        )?;
+        set_line(&value, error.span);
+        Ok(value)
+    }
+
+    fn lookup(&self, name: &str, span: Span) -> Result<Value, RuntimeError> {
+        self.environment.borrow().get(name, span)
+    }
+
+    fn assign(&mut self, name: &str, span: Span, value: Value) -> Result<(), RuntimeError> {
+        self.environment.borrow_mut().assign(name, span, value)
+    }
+
+    // What a store puts in place and what it gives back. `op=` combines the old value with the
+    // new one, the old one is read before the new one is evaluated, the same as in
+    // `x = x op value`. `xs[i]++` gives back the old value, which has to be a number as for `x++`
+    fn stored(
+        &self,
+        store: Store,
+        old: Option<Value>,
+        value: Value,
+        span: Span,
+    ) -> Result<(Value, Value), RuntimeError> {
+        match (store, old) {
+            (Store::Compound(operator), Some(old)) => {
+                let new = self.arithmetic(operator, span, old, value)?;
+                Ok((new.clone(), new))
+            }
+            (Store::Postfix(_), Some(old)) if number(&old).is_none() => {
+                Err(RuntimeError::type_error(span, "Operand must be a number."))
+            }
+            (Store::Postfix(operator), Some(old)) => {
+                let new = self.arithmetic(operator, span, old.clone(), value)?;
+                Ok((new, old))
+            }
+            _ => Ok((value.clone(), value)),
+        }
+    }
+
+    fn arithmetic(
+        &self,
+        operator: BinaryOperator,
+        span: Span,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, RuntimeError> {
+        match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => self.integer_arithmetic(operator, span, a, b),
+            (Value::String(a), Value::String(b)) if operator == BinaryOperator::Add => {
+                Ok(Value::String(a + &b))
+            }
+            (left, right) => match (number(&left), number(&right)) {
+                (Some(a), Some(b)) => self.float_arithmetic(operator, span, a, b),
+                _ if operator == BinaryOperator::Add => Err(RuntimeError::type_error(
+                    span,
+                    "Operands must be two numbers or two strings.",
+                )),
+                _ => Err(RuntimeError::type_error(span, "Operands must be numbers.")),
+            },
+        }
+    }
+
+    fn integer_arithmetic(
+        &self,
+        operator: BinaryOperator,
+        span: Span,
+        a: i64,
+        b: i64,
+    ) -> Result<Value, RuntimeError> {
+        let result = match operator {
+            BinaryOperator::Add => a.checked_add(b),
+            BinaryOperator::Subtract => a.checked_sub(b),
+            BinaryOperator::Multiply => a.checked_mul(b),
+            BinaryOperator::Divide | BinaryOperator::Modulo if b == 0 => {
+                return Err(RuntimeError::new(span, "Division by zero."))
+            }
+            BinaryOperator::Divide => a.checked_div(b),
+            BinaryOperator::Modulo => a.checked_rem(b),
+            // The result wouldn't be an integer, and `Int ** Int` is typed as one
+            BinaryOperator::Power if b < 0 => {
+                return Err(RuntimeError::new(
+                    span,
+                    "Can't raise an integer to a negative power, use a float base.",
+                ))
+            }
+            BinaryOperator::Power => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+            _ => return Err(RuntimeError::new(span, "Unknown arithmetic operator.")),
+        };
+
+        match result {
+            Some(value) => Ok(Value::Integer(value)),
+            None => Err(RuntimeError::new(span, "Integer overflow.")),
+        }
+    }
+
+    fn float_arithmetic(
+        &self,
+        operator: BinaryOperator,
+        span: Span,
+        a: f64,
+        b: f64,
+    ) -> Result<Value, RuntimeError> {
+        let result = match operator {
+            BinaryOperator::Add => a + b,
+            BinaryOperator::Subtract => a - b,
+            BinaryOperator::Multiply => a * b,
+            BinaryOperator::Divide => a / b,
+            BinaryOperator::Modulo => a % b,
+            BinaryOperator::Power => a.powf(b),
+            _ => return Err(RuntimeError::new(span, "Unknown arithmetic operator.")),
+        };
+
+        Ok(Value::Float(result))
+    }
+
+    // Bitwise and shift operators only make sense on integers, floats are rejected
+    fn bitwise(
+        &self,
+        operator: BinaryOperator,
+        span: Span,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, RuntimeError> {
+        let (a, b) = match (left, right) {
+            (Value::Integer(a), Value::Integer(b)) => (a, b),
+            _ => return Err(RuntimeError::type_error(span, "Operands must be integers.")),
+        };
+
+        let result = match operator {
+            BinaryOperator::BitAnd => Some(a & b),
+            BinaryOperator::BitOr => Some(a | b),
+            BinaryOperator::BitXor => Some(a ^ b),
+            BinaryOperator::ShiftLeft => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)),
+            BinaryOperator::ShiftRight => u32::try_from(b).ok().and_then(|b| a.checked_shr(b)),
+            _ => return Err(RuntimeError::new(span, "Unknown bitwise operator.")),
+        };
+
+        match result {
+            Some(value) => Ok(Value::Integer(value)),
+            None => Err(RuntimeError::new(
+                span,
+                "Shift amount must be between 0 and 63.",
+            )),
+        }
+    }
+
+    fn comparison(
+        &self,
+        operator: BinaryOperator,
+        span: Span,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, RuntimeError> {
+        let ordering = match (&left, &right) {
+            (Value::Integer(a), Value::Integer(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            _ => match (number(&left), number(&right)) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => return Err(RuntimeError::type_error(span, "Operands must be numbers.")),
+            },
+        };
+
+        // Comparisons involving NaN are always false
+        let result = match ordering {
+            Some(ordering) => match operator {
+                BinaryOperator::Greater => ordering.is_gt(),
+                BinaryOperator::GreaterEqual => ordering.is_ge(),
+                BinaryOperator::Less => ordering.is_lt(),
+                _ => ordering.is_le(),
+            },
+            None => false,
+        };
+
+        Ok(Value::Boolean(result))
+    }
+}
+
+impl ExprVisitor for Interpreter {
+    type Output = Result<Value, RuntimeError>;
+    fn visit_assign_expr(&mut self, expr: &Assign) -> Self::Output {
+        let value = self.evaluate(&expr.value)?;
+        self.assign(&expr.name, expr.span, value.clone())?;
+        Ok(value)
+    }
+
+    fn visit_binary_expr(&mut self, expr: &Binary) -> Self::Output {
+        let left = self.evaluate(&expr.left)?;
+        let right = self.evaluate(&expr.right)?;
+        let (operator, span) = (expr.operator, expr.span);
+
+        match operator {
+            BinaryOperator::Comma => Ok(right),
+            BinaryOperator::Equal => Ok(Value::Boolean(left == right)),
+            BinaryOperator::NotEqual => Ok(Value::Boolean(left != right)),
+            BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual
+            | BinaryOperator::Less
+            | BinaryOperator::LessEqual => self.comparison(operator, span, left, right),
+            BinaryOperator::In => contains(right, &left, span),
+            BinaryOperator::Range | BinaryOperator::RangeInclusive => {
+                range(operator, span, left, right)
+            }
+            BinaryOperator::BitAnd
+            | BinaryOperator::BitOr
+            | BinaryOperator::BitXor
+            | BinaryOperator::ShiftLeft
+            | BinaryOperator::ShiftRight => self.bitwise(operator, span, left, right),
+            _ => self.arithmetic(operator, span, left, right),
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> Self::Output {
+        let callee = self.evaluate(&expr.callee)?;
+        let arguments = expr
+            .arguments
+            .iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<Result<Vec<Value>, RuntimeError>>()?;
+        let named = expr
+            .named
+            .iter()
+            .map(|argument| Ok((argument.name.clone(), self.evaluate(&argument.value)?)))
+            .collect::<Result<Vec<(String, Value)>, RuntimeError>>()?;
+
+        // Only the parameters of functions written in the language have names to pass by
+        let built_in = matches!(
+            callee,
+            Value::NativeFunction(_) | Value::Constructor(..) | Value::Resume(_)
+        );
+        if built_in && !named.is_empty() {
+            return Err(RuntimeError::type_error(
+                expr.span,
+                &format!("Can't pass named arguments to {}.", callee),
+            ));
+        }
+
+        match callee {
+            Value::Function(function) => self.call(&function, arguments, named, expr.span),
+            Value::NativeFunction(native) => {
+                check_arity(native.arity, arguments.len(), expr.span)?;
+                (native.function)(&arguments, expr.span)
+            }
+            Value::Class(class) => self.instantiate(&class, arguments, named, expr.span),
+            Value::Constructor(enumeration, variant) => {
+                let arity = enumeration.variants[variant].fields.len();
+                check_arity(arity, arguments.len(), expr.span)?;
+                Ok(Value::Tagged(Rc::new(Tagged {
+                    enumeration,
+                    variant,
+                    values: arguments,
+                })))
+            }
+            // Once the generator is done every call gives nil
+            Value::Resume(generator) => {
+                check_arity(0, arguments.len(), expr.span)?;
+                Ok(self.resume(&generator, expr.span)?.unwrap_or(Value::Nil))
+            }
+            _ => Err(RuntimeError::type_error(
+                expr.span,
+                "Can only call functions and classes.",
+            )),
+        }
+    }
+
+    fn visit_conditional_expr(&mut self, expr: &Conditional) -> Self::Output {
+        if self.evaluate(&expr.condition)?.is_truthy() {
+            self.evaluate(&expr.then_branch)
+        } else {
+            self.evaluate(&expr.else_branch)
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> Self::Output {
+        match self.evaluate(&expr.object)? {
+            Value::Instance(instance) => property(&instance, &expr.name, expr.span),
+            Value::Module(module) => module.get(&expr.name).ok_or_else(|| {
+                let message = format!("Module '{}' doesn't export '{}'.", module.name, expr.name);
+                RuntimeError::name_error(expr.span, &message)
+            }),
+            // A variant with fields has to be called, one without is already a value
+            Value::Enum(enumeration) => match enumeration.variant(&expr.name) {
+                Some(variant) if enumeration.variants[variant].fields.is_empty() => {
+                    Ok(Value::Tagged(Rc::new(Tagged {
+                        enumeration,
+                        variant,
+                        values: Vec::new(),
+                    })))
+                }
+                Some(variant) => Ok(Value::Constructor(enumeration, variant)),
+                None => {
+                    let message = format!(
+                        "Enum '{}' has no variant '{}'.",
+                        enumeration.name, expr.name
+                    );
+                    Err(RuntimeError::name_error(expr.span, &message))
+                }
+            },
+            Value::Tagged(tagged) => tagged.get(&expr.name).ok_or_else(|| {
+                let message = format!("Variant '{}' has no field '{}'.", tagged.name(), expr.name);
+                RuntimeError::name_error(expr.span, &message)
+            }),
+            Value::Generator(generator) if expr.name == "next" => Ok(Value::Resume(generator)),
+            Value::Generator(_) => Err(RuntimeError::name_error(
+                expr.span,
+                &format!("Undefined property '{}'.", expr.name),
+            )),
+            _ => Err(RuntimeError::type_error(
+                expr.span,
+                "Only instances have properties.",
+            )),
+        }
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> Self::Output {
+        self.evaluate(&expr.expression)
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) -> Self::Output {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+        element(&object, &index, expr.span)
+    }
+
+    // Storing into a map adds the key when it is missing, a list index has to exist already
+    fn visit_index_set_expr(&mut self, expr: &IndexSet) -> Self::Output {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+        let old = match expr.store {
+            Store::Assign => None,
+            _ => Some(element(&object, &index, expr.span)?),
+        };
+        let value = self.evaluate(&expr.value)?;
+        let (value, result) = self.stored(expr.store, old, value, expr.span)?;
+
+        match object {
+            Value::List(list) => {
+                let mut list = list.borrow_mut();
+                let position = list_index(&index, list.len(), expr.span)?;
+                list[position] = value;
+            }
+            Value::Map(map) => map
+                .borrow_mut()
+                .insert(index, value)
+                .map_err(|message| RuntimeError::type_error(expr.span, &message))?,
+            _ => {
+                return Err(RuntimeError::type_error(
+                    expr.span,
+                    "Only lists and maps can be indexed.",
+                ))
+            }
+        }
+        Ok(result)
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &Lambda) -> Self::Output {
+        Ok(Value::Function(Rc::new(function::Function {
+            declaration: Declaration::new(None, &expr.params, &expr.body),
+            closure: Rc::clone(&self.environment),
+            is_initializer: false,
+        })))
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> Self::Output {
+        let elements = expr
+            .elements
+            .iter()
+            .map(|element| self.evaluate(element))
+            .collect::<Result<Vec<Value>, RuntimeError>>()?;
+        Ok(Value::List(Rc::new(RefCell::new(elements))))
+    }
+
+    fn visit_literal_expr(&mut self, expr: &Literal) -> Self::Output {
+        Ok(Value::from(&expr.value))
+    }
+
+    // Entries are evaluated left to right, a repeated key keeps the last value
+    fn visit_map_expr(&mut self, expr: &Map) -> Self::Output {
+        let mut map = map::Map::new();
+        for (key, value) in expr.keys.iter().zip(&expr.values) {
+            let key_value = self.evaluate(key)?;
+            let value = self.evaluate(value)?;
+            map.insert(key_value, value)
+                .map_err(|message| RuntimeError::type_error(key.span(), &message))?;
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    }
+
+    // The first arm whose pattern matches and whose guard holds gives the value
+    fn visit_match_expr(&mut self, expr: &Match) -> Self::Output {
+        let subject = self.evaluate(&expr.subject)?;
+
+        for arm in &expr.arms {
+            let mut bindings = Vec::new();
+            if !match_pattern(&arm.pattern, &subject, &mut bindings) {
+                continue;
+            }
+            if let Some(value) = self.evaluate_arm(arm, bindings)? {
+                return Ok(value);
+            }
+        }
+
+        Err(RuntimeError::new(
+            expr.span,
+            &format!("No match arm matches {}.", subject.repr()),
+        ))
+    }
+
+    fn visit_postfix_expr(&mut self, expr: &Postfix) -> Self::Output {
+        let old = self.lookup(&expr.name, expr.span)?;
+        let new = match (&old, expr.operator) {
+            (Value::Integer(n), PostfixOperator::Increment) => n.checked_add(1).map(Value::Integer),
+            (Value::Integer(n), PostfixOperator::Decrement) => n.checked_sub(1).map(Value::Integer),
+            (Value::Float(n), PostfixOperator::Increment) => Some(Value::Float(n + 1.0)),
+            (Value::Float(n), PostfixOperator::Decrement) => Some(Value::Float(n - 1.0)),
+            _ => {
+                return Err(RuntimeError::type_error(
+                    expr.span,
+                    "Operand must be a number.",
+                ))
+            }
+        };
+
+        match new {
+            Some(new) => {
+                self.assign(&expr.name, expr.span, new)?;
+                Ok(old)
+            }
+            None => Err(RuntimeError::new(expr.span, "Integer overflow.")),
+        }
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> Self::Output {
+        let instance = match self.evaluate(&expr.object)? {
+            Value::Instance(instance) => instance,
+            _ => {
+                return Err(RuntimeError::type_error(
+                    expr.span,
+                    "Only instances have fields.",
+                ))
+            }
+        };
+        let old = match expr.store {
+            Store::Assign => None,
+            _ => Some(property(&instance, &expr.name, expr.span)?),
+        };
+        let value = self.evaluate(&expr.value)?;
+        let (value, result) = self.stored(expr.store, old, value, expr.span)?;
+
+        instance
+            .borrow_mut()
+            .fields
+            .insert(expr.name.clone(), value);
+        Ok(result)
+    }
+
+    // Slicing copies the elements into a new list
+    fn visit_slice_expr(&mut self, expr: &Slice) -> Self::Output {
+        let list = as_list(self.evaluate(&expr.object)?, expr.span)?;
+        let start = match &expr.start {
+            Some(start) => Some(self.evaluate(start)?),
+            None => None,
+        };
+        let end = match &expr.end {
+            Some(end) => Some(self.evaluate(end)?),
+            None => None,
+        };
+
+        let list = list.borrow();
+        let start = slice_bound(start, 0, list.len(), expr.span)?;
+        let end = slice_bound(end, list.len(), list.len(), expr.span)?;
+        let elements = if start < end {
+            list[start..end].to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(Value::List(Rc::new(RefCell::new(elements))))
+    }
+
+    // The superclass's method, bound to the `this` of the method it's used in
+    fn visit_super_expr(&mut self, expr: &Super) -> Self::Output {
+        let superclass = match self.lookup("super", expr.span)? {
+            Value::Class(class) => class,
+            _ => unreachable!("`super` is only ever bound to a class"),
+        };
+        let this = self.lookup("this", expr.span)?;
+        let method = superclass.find_method(&expr.method).ok_or_else(|| {
+            RuntimeError::name_error(expr.span, &format!("Undefined property '{}'.", expr.method))
+        })?;
+        Ok(Value::Function(Rc::new(method.bind(this))))
+    }
+
+    fn visit_this_expr(&mut self, expr: &This) -> Self::Output {
+        self.lookup("this", expr.span)
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) -> Self::Output {
+        let right = self.evaluate(&expr.right)?;
+
+        match (expr.operator, right) {
+            (UnaryOperator::Not, right) => Ok(Value::Boolean(!right.is_truthy())),
+            (UnaryOperator::Negate, Value::Integer(n)) => match n.checked_neg() {
+                Some(n) => Ok(Value::Integer(n)),
+                None => Err(RuntimeError::new(expr.span, "Integer overflow.")),
+            },
+            (UnaryOperator::Negate, Value::Float(n)) => Ok(Value::Float(-n)),
+            (UnaryOperator::Negate, _) => Err(RuntimeError::type_error(
+                expr.span,
+                "Operand must be a number.",
+            )),
+            (UnaryOperator::BitNot, Value::Integer(n)) => Ok(Value::Integer(!n)),
+            (UnaryOperator::BitNot, _) => Err(RuntimeError::type_error(
+                expr.span,
+                "Operand must be an integer.",
+            )),
+        }
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) -> Self::Output {
+        self.lookup(&expr.name, expr.span)
+    }
+}
+
+impl StmtVisitor for Interpreter {
+    type Output = Result<(), Unwind>;
+    fn visit_block_stmt(&mut self, stmt: &Block) -> Self::Output {
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        self.execute_block(&stmt.statements, environment)
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &Break) -> Self::Output {
+        Err(Unwind::Break(stmt.label.clone(), stmt.span))
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) -> Self::Output {
+        let superclass = match &stmt.superclass {
+            Some(superclass) => match self.visit_variable_expr(superclass)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    let error =
+                        RuntimeError::type_error(superclass.span, "Superclass must be a class.");
+                    return Err(error.into());
+                }
+            },
+            None => None,
+        };
+
+        // The methods of a subclass find its superclass as `super`
+        let closure = match &superclass {
+            Some(superclass) => {
+                let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                environment.define("super", Value::Class(Rc::clone(superclass)));
+                Rc::new(RefCell::new(environment))
+            }
+            None => Rc::clone(&self.environment),
+        };
+
+        let methods = stmt
+            .methods
+            .iter()
+            .map(|method| {
+                let function = function::Function {
+                    declaration: Declaration::new(
+                        Some(method.name.clone()),
+                        &method.params,
+                        &method.body,
+                    ),
+                    closure: Rc::clone(&closure),
+                    is_initializer: method.name == "init",
+                };
+                (method.name.clone(), Rc::new(function))
+            })
+            .collect();
+
+        let class = class::Class {
+            name: stmt.name.clone(),
+            superclass,
+            methods,
+        };
+        self.environment
+            .borrow_mut()
+            .define(&stmt.name, Value::Class(Rc::new(class)));
+        Ok(())
+    }
+
+    fn visit_const_stmt(&mut self, stmt: &Const) -> Self::Output {
+        let value = self.evaluate(&stmt.initializer)?;
+        self.environment
+            .borrow_mut()
+            .define_constant(&stmt.name, value);
+        Ok(())
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &Continue) -> Self::Output {
+        Err(Unwind::Continue(stmt.label.clone(), stmt.span))
+    }
+
+    fn visit_enum_stmt(&mut self, stmt: &Enum) -> Self::Output {
+        let enumeration = enumeration::Enum {
+            name: stmt.name.clone(),
+            variants: stmt.variants.clone(),
+        };
+        self.environment
+            .borrow_mut()
+            .define(&stmt.name, Value::Enum(Rc::new(enumeration)));
+        Ok(())
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &Export) -> Self::Output {
+        self.execute(&stmt.declaration)
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> Self::Output {
+        self.evaluate(&stmt.expression)?;
+        Ok(())
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) -> Self::Output {
+        let mut iteration = self.for_in_iteration(stmt)?;
+        let span = stmt.iterable.span();
+        while let Some((item, value)) = self.next_item(&mut iteration, span)? {
+            let environment = self.pass_scope(&stmt.names, item, value);
+            let result = self.execute_block(std::slice::from_ref(&*stmt.body), environment);
+            if !keeps_looping(&stmt.label, result)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) -> Self::Output {
+        let function = function::Function {
+            declaration: Declaration::new(Some(stmt.name.clone()), &stmt.params, &stmt.body),
+            closure: Rc::clone(&self.environment),
+            is_initializer: false,
+        };
+        self.environment
+            .borrow_mut()
+            .define(&stmt.name, Value::Function(Rc::new(function)));
+        Ok(())
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) -> Self::Output {
+        if self.evaluate(&stmt.condition)?.is_truthy() {
+            self.execute(&stmt.then_branch)
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &Import) -> Self::Output {
+        let module = self.import(&stmt.path, stmt.span)?;
+        if let Some(alias) = &stmt.alias {
+            self.environment
+                .borrow_mut()
+                .define(alias, Value::Module(Rc::clone(&module)));
+        }
+        for name in &stmt.names {
+            let value = module.get(name).ok_or_else(|| {
+                let message = format!("Module '{}' doesn't export '{}'.", module.name, name);
+                RuntimeError::name_error(stmt.span, &message)
+            })?;
+            self.environment.borrow_mut().define(name, value);
+        }
+        Ok(())
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) -> Self::Output {
+        let value = self.evaluate(&stmt.expression)?;
+        println!("{}", value);
+        Ok(())
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) -> Self::Output {
+        let value = match &stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value, stmt.span))
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &Throw) -> Self::Output {
+        let value = self.evaluate(&stmt.value)?;
+        set_line(&value, stmt.span);
+        Err(RuntimeError::thrown(value, stmt.span).into())
+    }
+
+    // The finally body runs however the rest ended, and only changes the outcome when it throws
+    // or returns itself
+    fn visit_try_stmt(&mut self, stmt: &Try) -> Self::Output {
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        let mut result = self.execute_block(&stmt.body, environment);
+
+        if let Some(clause) = &stmt.catch_clause {
+            result = match result {
+                Err(Unwind::Error(error)) => {
+                    let error = self.caught(error)?;
+                    let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                    environment.define(&clause.name, error);
+                    self.execute_block(&clause.body, environment)
+                }
+                other => other,
+            };
+        }
+
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        self.execute_block(&stmt.finally_body, environment)?;
+        result
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) -> Self::Output {
+        let value = match &stmt.initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Nil,
+        };
+        self.environment.borrow_mut().define(&stmt.name, value);
+        Ok(())
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) -> Self::Output {
+        while self.evaluate(&stmt.condition)?.is_truthy() {
+            let result = self.execute(&stmt.body);
+            if !keeps_looping(&stmt.label, result)? {
+                break;
+            }
+            if let Some(increment) = &stmt.increment {
+                self.evaluate(increment)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Generators run their bodies themselves, so only a `yield` outside of a function gets here
+    fn visit_yield_stmt(&mut self, stmt: &Yield) -> Self::Output {
+        let message = "Can't use 'yield' outside of a function.";
+        Err(RuntimeError::new(stmt.span, message).into())
+    }
+}
+
+// The name an exported declaration introduces, the parser only lets these five through
+fn declared_name(declaration: &Stmt) -> &str {
+    match declaration {
+        Stmt::Class(class) => &class.name,
+        Stmt::Const(constant) => &constant.name,
+        Stmt::Enum(enumeration) => &enumeration.name,
+        Stmt::Function(function) => &function.name,
+        Stmt::Var(var) => &var.name,
+        _ => unreachable!("the resolver rejects exports of anything else"),
+    }
+}
+
+// An error instance remembers the line it was first thrown from
+fn set_line(value: &Value, span: Span) {
+    if let Value::Instance(instance) = value {
+        let mut instance = instance.borrow_mut();
+        if instance.fields.get("line") == Some(&Value::Nil) {
+            instance
+                .fields
+                .insert("line".to_string(), Value::Integer(i64::from(span.line)));
+        }
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(n) => Some(*n as f64),
+        Value::Float(n) => Some(*n),
+        _ => None,
+    }
+}
+
+// Where a for-in loop gets its items from
+pub enum Iteration {
+    List(Rc<RefCell<Vec<Value>>>, usize),
+    Range(i64, i64),
+    // A map is iterated over a copy of its entries
+    Entries(std::vec::IntoIter<(Value, Value)>),
+    // The `next` method of an iterator, bound to it
+    Iterator(Rc<function::Function>),
+    Generator(Rc<Generator>),
+}
+
+// What a generator's frame needs after moving on a bit
+enum Step {
+    Next,
+    Push(Frame),
+    Pop,
+    Yield(Value),
+}
+
+// Whether a loop goes on after its body ended this way. A `break` or `continue` without a label
+// belongs to the innermost loop, one with a label travels out to the loop that has it
+fn keeps_looping(own_label: &Option<String>, result: Result<(), Unwind>) -> Result<bool, Unwind> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(Unwind::Break(label, _)) if label.is_none() || label == *own_label => Ok(false),
+        Err(Unwind::Continue(label, _)) if label.is_none() || label == *own_label => Ok(true),
+        Err(unwind) => Err(unwind),
+    }
+}
+
+// Why a statement stopped early, a `return` travels up to the call that runs its function and
+// a `break` or `continue` up to its loop
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(Value, Span),
+    Break(Option<String>, Span),
+    Continue(Option<String>, Span),
+}
+
+impl Unwind {
+    // What's left when nothing caught it on the way up
+    fn into_error(self) -> RuntimeError {
+        let (keyword, label, span) = match self {
+            Unwind::Error(error) => return error,
+            Unwind::Return(_, span) => {
+                return RuntimeError::new(span, "Can't return from top-level code.")
+            }
+            Unwind::Break(label, span) => ("break", label, span),
+            Unwind::Continue(label, span) => ("continue", label, span),
+        };
+        let message = match label {
+            Some(label) => format!("No enclosing loop is labelled '{}'.", label),
+            None => format!("Can't use '{}' outside of a loop.", keyword),
+        };
+        RuntimeError::new(span, &message)
+    }
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+fn as_list(value: Value, span: Span) -> Result<Rc<RefCell<Vec<Value>>>, RuntimeError> {
+    match value {
+        Value::List(list) => Ok(list),
+        _ => Err(RuntimeError::type_error(span, "Only lists can be sliced.")),
+    }
+}
+
+// Whether the value fits the pattern, collecting what its names bind along the way
+fn match_pattern(pattern: &Pattern, value: &Value, bindings: &mut Vec<(String, Value)>) -> bool {
+    match pattern {
+        Pattern::Wildcard(_) => true,
+        Pattern::Literal(literal, _) => Value::from(literal) == *value,
+        Pattern::Binding(name, _) => {
+            bindings.push((name.clone(), value.clone()));
+            true
+        }
+        // The bindings of an alternative that failed halfway are dropped again
+        Pattern::Or(alternatives) => {
+            let bound = bindings.len();
+            alternatives.iter().any(|alternative| {
+                bindings.truncate(bound);
+                match_pattern(alternative, value, bindings)
+            })
+        }
+        Pattern::List { elements, rest, .. } => {
+            let list = match value {
+                Value::List(list) => list.borrow(),
+                _ => return false,
+            };
+            let fits = match rest {
+                Some(_) => list.len() >= elements.len(),
+                None => list.len() == elements.len(),
+            };
+            if !fits
+                || !elements
+                    .iter()
+                    .zip(list.iter())
+                    .all(|(element, value)| match_pattern(element, value, bindings))
+            {
+                return false;
+            }
+
+            match rest {
+                Some(rest) => {
+                    let remaining = list[elements.len()..].to_vec();
+                    match_pattern(
+                        rest,
+                        &Value::List(Rc::new(RefCell::new(remaining))),
+                        bindings,
+                    )
+                }
+                None => true,
+            }
+        }
+        Pattern::Instance { class, fields, .. } => {
+            let instance = match value {
+                Value::Instance(instance) => instance.borrow(),
+                _ => return false,
+            };
+            instance.class.is_a(class)
+                && fields
+                    .iter()
+                    .all(|(name, field)| match instance.fields.get(name) {
+                        Some(value) => match_pattern(field, value, bindings),
+                        None => false,
+                    })
+        }
+        Pattern::Variant {
+            enumeration,
+            variant,
+            fields,
+            ..
+        } => {
+            let tagged = match value {
+                Value::Tagged(tagged) => tagged,
+                _ => return false,
+            };
+            tagged.enumeration.name == *enumeration
+                && tagged.name() == variant
+                && tagged.values.len() == fields.len()
+                && fields
+                    .iter()
+                    .zip(&tagged.values)
+                    .all(|(field, value)| match_pattern(field, value, bindings))
+        }
+    }
+}
+
+fn check_arity(expected: usize, got: usize, span: Span) -> Result<(), RuntimeError> {
+    if expected != got {
+        return Err(RuntimeError::type_error(
+            span,
+            &format!("Expected {} arguments but got {}.", expected, got),
+        ));
+    }
+    Ok(())
+}
+
+// `needle in haystack` looks for a key of a map or an element of a list
+fn contains(haystack: Value, needle: &Value, span: Span) -> Result<Value, RuntimeError> {
+    match haystack {
+        Value::List(list) => Ok(Value::Boolean(list.borrow().contains(needle))),
+        Value::Map(map) => match map.borrow().contains_key(needle) {
+            Ok(found) => Ok(Value::Boolean(found)),
+            Err(message) => Err(RuntimeError::type_error(span, &message)),
+        },
+        Value::Range(start, end) => Ok(Value::Boolean(match needle {
+            Value::Integer(n) => start <= *n && *n < end,
+            _ => false,
+        })),
+        _ => Err(RuntimeError::type_error(
+            span,
+            "Right operand of 'in' must be a list, a map or a range.",
+        )),
+    }
+}
+
+// An inclusive range is kept as the exclusive one that holds the same integers
+fn range(
+    operator: BinaryOperator,
+    span: Span,
+    left: Value,
+    right: Value,
+) -> Result<Value, RuntimeError> {
+    let (start, end) = match (left, right) {
+        (Value::Integer(start), Value::Integer(end)) => (start, end),
+        _ => {
+            return Err(RuntimeError::type_error(
+                span,
+                "Range bounds must be integers.",
+            ))
+        }
+    };
+    match operator {
+        BinaryOperator::RangeInclusive => match end.checked_add(1) {
+            Some(end) => Ok(Value::Range(start, end)),
+            None => Err(RuntimeError::new(span, "Integer overflow.")),
+        },
+        _ => Ok(Value::Range(start, end)),
+    }
+}
+
+fn element(object: &Value, index: &Value, span: Span) -> Result<Value, RuntimeError> {
+    match object {
+        Value::List(list) => {
+            let list = list.borrow();
+            let position = list_index(index, list.len(), span)?;
+            Ok(list[position].clone())
+        }
+        Value::Map(map) => match map.borrow().get(index) {
+            Ok(Some(value)) => Ok(value.clone()),
+            Ok(None) => Err(RuntimeError::index_error(
+                span,
+                &format!("Key {} is not in the map.", index.repr()),
+            )),
+            Err(message) => Err(RuntimeError::type_error(span, &message)),
+        },
+        _ => Err(RuntimeError::type_error(
+            span,
+            "Only lists and maps can be indexed.",
+        )),
+    }
+}
+
+// A field, or a method bound to the instance
+fn property(
+    instance: &Rc<RefCell<class::Instance>>,
+    name: &str,
+    span: Span,
+) -> Result<Value, RuntimeError> {
+    class::Instance::get(instance, name)
+        .ok_or_else(|| RuntimeError::name_error(span, &format!("Undefined property '{}'.", name)))
+}
+
+// Negative indices count from the end, so -1 is the last element
+fn list_index(index: &Value, len: usize, span: Span) -> Result<usize, RuntimeError> {
+    let index = match index {
+        Value::Integer(index) => *index,
+        _ => {
+            return Err(RuntimeError::type_error(
+                span,
+                "List index must be an integer.",
+            ))
+        }
+    };
+
+    let position = if index < 0 { index + len as i64 } else { index };
+    if position < 0 || position >= len as i64 {
+        return Err(RuntimeError::index_error(
+            span,
+            &format!(
+                "Index {} is out of bounds for a list of length {}.",
+                index, len
+            ),
+        ));
+    }
+    Ok(position as usize)
+}
+
+// Slice bounds count from the end when negative like indices do, but are clamped to the list
+// instead of being out of bounds
+fn slice_bound(
+    bound: Option<Value>,
+    default: usize,
+    len: usize,
+    span: Span,
+) -> Result<usize, RuntimeError> {
+    let bound = match bound {
+        None => return Ok(default),
+        Some(Value::Integer(bound)) => bound,
+        Some(_) => {
+            return Err(RuntimeError::type_error(
+                span,
+                "Slice bounds must be integers.",
+            ))
+        }
+    };
+
+    let position = if bound < 0 {
+        bound.saturating_add(len as i64)
+    } else {
+        bound
+    };
+    Ok(position.clamp(0, len as i64) as usize)
+}
+
+// Which of the prelude's error classes a runtime error is caught as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    Error,
+    TypeError,
+    NameError,
+    IndexError,
+}
+
+impl ErrorKind {
+    pub fn class_name(self) -> &'static str {
+        match self {
+            ErrorKind::Error => "Error",
+            ErrorKind::TypeError => "TypeError",
+            ErrorKind::NameError => "NameError",
+            ErrorKind::IndexError => "IndexError",
+        }
+    }
+}
+
+// An error of the interpreter itself, or a value thrown by the program
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub span: Span,
+    pub message: String,
+    pub kind: ErrorKind,
+    pub thrown: Option<Value>,
+}
+
+impl RuntimeError {
+    pub fn new(span: Span, message: &str) -> Self {
+        RuntimeError {
+            span,
+            message: message.to_string(),
+            kind: ErrorKind::Error,
+            thrown: None,
+        }
+    }
+
+    pub fn type_error(span: Span, message: &str) -> Self {
+        RuntimeError {
+            kind: ErrorKind::TypeError,
+            ..RuntimeError::new(span, message)
+        }
+    }
+
+    pub fn name_error(span: Span, message: &str) -> Self {
+        RuntimeError {
+            kind: ErrorKind::NameError,
+            ..RuntimeError::new(span, message)
+        }
+    }
+
+    pub fn index_error(span: Span, message: &str) -> Self {
+        RuntimeError {
+            kind: ErrorKind::IndexError,
+            ..RuntimeError::new(span, message)
+        }
+    }
+
+    // Nothing caught it, an error instance is described by its class and message
+    pub fn thrown(value: Value, span: Span) -> Self {
+        let description = match &value {
+            Value::Instance(instance) => match instance.borrow().fields.get("message") {
+                Some(Value::String(message)) => {
+                    format!("{}: {}", instance.borrow().class.name, message)
+                }
+                _ => value.repr(),
+            },
+            _ => value.repr(),
+        };
+        RuntimeError {
+            thrown: Some(value),
+            ..RuntimeError::new(span, &format!("Uncaught {}", description))
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}, column {}] Error: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    fn evaluate(code: &str) -> Result<Value, String> {
+        let mut scanner = Scanner::new(code.to_string());
+        let expression = Parser::new(scanner.scan_tokens())
+            .parse()
+            .expect("expression should parse");
+        Interpreter::new()
+            .evaluate(&expression)
+            .map_err(|error| error.message)
+    }
+
+    fn run(code: &str) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&parse(code)).expect("program failed");
+        interpreter
+    }
+
+    fn run_error(code: &str) -> String {
+        let error = Interpreter::new()
+            .interpret(&parse(code))
+            .expect_err("program should fail");
+        error.message
+    }
+
+    fn parse(code: &str) -> Vec<Stmt> {
+        let mut scanner = Scanner::new(code.to_string());
+        Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .expect("program should parse")
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Value {
+        interpreter.lookup(name, Span::default()).unwrap()
+    }
+
+    #[test]
+    fn blocks_shadow_outer_variables_and_assign_through_to_them() {
+        let interpreter = run("
+            var a = 1;
+            var b = 2;
+            var c;
+            {
+                var a = 10;
+                b = a + b;
+                { c = a; a = 20; }
+            }
+        ");
+
+        assert_eq!(global(&interpreter, "a"), Value::Integer(1));
+        assert_eq!(global(&interpreter, "b"), Value::Integer(12));
+        assert_eq!(global(&interpreter, "c"), Value::Integer(10));
+
+        let error = Interpreter::new()
+            .interpret(&parse("{ var inner = 1; } inner = 2;"))
+            .expect_err("program should fail");
+        assert_eq!(error.message, "Undefined variable 'inner'.");
+    }
+
+    #[test]
+    fn integers_stay_integers_until_mixed_with_floats() {
+        assert_eq!(evaluate("7 / 2"), Ok(Value::Integer(3)));
+        assert_eq!(evaluate("-7 % 3"), Ok(Value::Integer(-1)));
+        assert_eq!(evaluate("2 ** 3 ** 2"), Ok(Value::Integer(512)));
+        assert_eq!(evaluate("-2 ** 2"), Ok(Value::Integer(-4)));
+        assert_eq!(
+            evaluate("2 ** -1"),
+            Err("Can't raise an integer to a negative power, use a float base.".to_string())
+        );
+        assert_eq!(evaluate("2.0 ** -1"), Ok(Value::Float(0.5)));
+        assert_eq!(evaluate("7.5 % 2"), Ok(Value::Float(1.5)));
+        assert_eq!(evaluate("1 + 2.5"), Ok(Value::Float(3.5)));
+        assert_eq!(
+            evaluate("\"a\" + \"b\""),
+            Ok(Value::String("ab".to_string()))
+        );
+        assert_eq!(evaluate("1 < 2 ? 2 == 2.0 : nil"), Ok(Value::Boolean(true)));
+
+        assert_eq!(evaluate("1 % 0"), Err("Division by zero.".to_string()));
+        assert_eq!(evaluate("2 ** 64"), Err("Integer overflow.".to_string()));
+        assert_eq!(
+            evaluate("\"a\" * 2"),
+            Err("Operands must be numbers.".to_string())
+        );
+        assert_eq!(
+            evaluate("true + 1"),
+            Err("Operands must be two numbers or two strings.".to_string())
+        );
+        assert_eq!(evaluate("x"), Err("Undefined variable 'x'.".to_string()));
+    }
+
+    #[test]
+    fn bitwise_operators_only_take_integers() {
+        assert_eq!(evaluate("12 & 10"), Ok(Value::Integer(8)));
+        assert_eq!(evaluate("12 | 3"), Ok(Value::Integer(15)));
+        assert_eq!(evaluate("12 ^ 10"), Ok(Value::Integer(6)));
+        assert_eq!(evaluate("~5"), Ok(Value::Integer(-6)));
+        assert_eq!(evaluate("3 << 4"), Ok(Value::Integer(48)));
+        assert_eq!(evaluate("-32 >> 2"), Ok(Value::Integer(-8)));
+        assert_eq!(evaluate("1 | 2 << 3 & 255"), Ok(Value::Integer(17)));
+
+        for code in &[
+            "1.0 & 1",
+            "1 | 2.0",
+            "1.5 ^ 1",
+            "1 << 1.0",
+            "8.0 >> 1",
+            "\"a\" & 1",
+        ] {
+            assert_eq!(
+                evaluate(code),
+                Err("Operands must be integers.".to_string())
+            );
+        }
+        assert_eq!(
+            evaluate("~1.5"),
+            Err("Operand must be an integer.".to_string())
+        );
+        let out_of_range = Err("Shift amount must be between 0 and 63.".to_string());
+        assert_eq!(evaluate("1 << 64"), out_of_range);
+        assert_eq!(evaluate("1 >> -1"), out_of_range);
+    }
+
+    #[test]
+    fn lambdas_capture_their_environment() {
+        let interpreter = run("
+            fun counter() {
+                var count = 0;
+                return fun () { count += 1; return count; };
+            }
+            fun twice(f, x) { return f(f(x)); }
+
+            var next = counter();
+            next();
+            var third = next() + next();
+            var other = counter()();
+            var squared = twice((n) => n * n, 3);
+            var nothing = (fun () {})();
+        ");
+
+        assert_eq!(global(&interpreter, "third"), Value::Integer(5));
+        assert_eq!(global(&interpreter, "other"), Value::Integer(1));
+        assert_eq!(global(&interpreter, "squared"), Value::Integer(81));
+        assert_eq!(global(&interpreter, "nothing"), Value::Nil);
+
+        // Binding a method to an instance doesn't copy the method
+        let interpreter = run("
+            class Counter { get() { return this; } }
+            var counter = Counter();
+            var first = counter.get;
+            var second = counter.get;
+        ");
+        match (
+            global(&interpreter, "first"),
+            global(&interpreter, "second"),
+        ) {
+            (Value::Function(first), Value::Function(second)) => {
+                assert!(!Rc::ptr_eq(&first, &second));
+                assert!(Rc::ptr_eq(&first.declaration, &second.declaration));
+            }
+            other => panic!("expected two bound methods, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lists_index_from_either_end_and_slice() {
+        let interpreter = run("
+            var xs = [1, 2, 3, 4];
+            var alias = xs;
+            alias[-1] = 40;
+            var last = xs[-1];
+            var middle = xs[1:-1];
+            var head = xs[:2];
+            var clamped = xs[-10:10];
+            var empty = xs[3:1];
+        ");
+
+        let list = |values: &[i64]| {
+            let values = values.iter().map(|n| Value::Integer(*n)).collect();
+            Value::List(Rc::new(RefCell::new(values)))
+        };
+        assert_eq!(global(&interpreter, "last"), Value::Integer(40));
+        assert_eq!(global(&interpreter, "middle"), list(&[2, 3]));
+        assert_eq!(global(&interpreter, "head"), list(&[1, 2]));
+        assert_eq!(global(&interpreter, "clamped"), list(&[1, 2, 3, 40]));
+        assert_eq!(global(&interpreter, "empty"), list(&[]));
+
+        assert_eq!(
+            run_error("[1, 2][-3];"),
+            "Index -3 is out of bounds for a list of length 2."
+        );
+        assert_eq!(run_error("[1][0.5] = 2;"), "List index must be an integer.");
+    }
+
+    #[test]
+    fn compound_assignment_and_increments_store_into_elements_and_fields() {
+        let interpreter = run("
+            var calls = 0;
+            fun at(i) { calls += 1; return i; }
+            var xs = [1, 2, 3];
+            xs[at(0)] += 10;
+            var doubled = xs[at(-1)] *= 2;
+            var m = {\"a\": 1, \"w\": \"x\"};
+            m[\"a\"] -= 3;
+            m[\"w\"] += \"y\";
+            class Counter { init() { this.count = 7; } }
+            var counter = Counter();
+            counter.count %= 4;
+            var left = counter.count -= 1;
+
+            var ys = [1, 2.5];
+            var before = ys[at(0)]++;
+            var after = ++ys[at(1)];
+            var old = counter.count--;
+            var count = --counter.count;
+        ");
+
+        let list = |values: &[i64]| {
+            let values = values.iter().map(|n| Value::Integer(*n)).collect();
+            Value::List(Rc::new(RefCell::new(values)))
+        };
+        assert_eq!(global(&interpreter, "xs"), list(&[11, 2, 6]));
+        assert_eq!(global(&interpreter, "doubled"), Value::Integer(6));
+        let m = run("var m = {\"a\": -2, \"w\": \"xy\"};");
+        assert_eq!(global(&interpreter, "m"), global(&m, "m"));
+        assert_eq!(global(&interpreter, "left"), Value::Integer(2));
+        assert_eq!(global(&interpreter, "before"), Value::Integer(1));
+        assert_eq!(global(&interpreter, "after"), Value::Float(3.5));
+        assert_eq!(
+            global(&interpreter, "ys"),
+            Value::List(Rc::new(RefCell::new(vec![
+                Value::Integer(2),
+                Value::Float(3.5)
+            ])))
+        );
+        assert_eq!(global(&interpreter, "calls"), Value::Integer(4));
+        assert_eq!(global(&interpreter, "old"), Value::Integer(2));
+        assert_eq!(global(&interpreter, "count"), Value::Integer(0));
+
+        assert_eq!(
+            run_error("var xs = [\"s\"]; xs[0] -= 1;"),
+            "Operands must be numbers."
+        );
+        assert_eq!(
+            run_error("class A {} A().n += 1;"),
+            "Undefined property 'n'."
+        );
+        assert_eq!(
+            run_error("var s = {\"k\": \"a\"}; s[\"k\"]++;"),
+            "Operand must be a number."
+        );
+    }
+
+    #[test]
+    fn lists_and_maps_that_hold_themselves_print_and_compare() {
+        let interpreter = run("
+            var xs = [1];
+            xs[0] = xs;
+            var ys = [1];
+            ys[0] = ys;
+            var m = {};
+            m[\"self\"] = m;
+            m[\"xs\"] = xs;
+            var same = xs == xs;
+            var twins = xs == ys;
+            var wrapped = [xs] == [xs];
+        ");
+
+        assert_eq!(global(&interpreter, "xs").to_string(), "[[...]]");
+        assert_eq!(
+            global(&interpreter, "m").to_string(),
+            "{\"self\": {...}, \"xs\": [[...]]}"
+        );
+        assert_eq!(global(&interpreter, "same"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "twins"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "wrapped"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn maps_hash_their_keys_by_value() {
+        let interpreter = run("
+            var m = {\"a\": 1, 2: [], nil: true,};
+            var alias = m;
+            alias[2.0] = \"two\";
+            m[-0.0] = 0;
+            var two = m[2];
+            var zero = 0.0 in m;
+            var missing = \"b\" in m;
+            var order = keys(m);
+            var size = len(m);
+            var same = {1: 2, 3: 4} == {3: 4, 1.0: 2};
+        ");
+
+        assert_eq!(global(&interpreter, "two"), Value::String("two".into()));
+        assert_eq!(global(&interpreter, "zero"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "missing"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "size"), Value::Integer(4));
+        assert_eq!(global(&interpreter, "same"), Value::Boolean(true));
+        assert_eq!(
+            global(&interpreter, "order").to_string(),
+            "[\"a\", 2, nil, -0]"
+        );
+
+        assert_eq!(run_error("{1: 2}[3];"), "Key 3 is not in the map.");
+        assert_eq!(
+            run_error("var m = {}; m[[1]] = 2;"),
+            "A list can't be used as a map key."
+        );
+    }
+
+    #[test]
+    fn classes_bind_this_and_run_init_on_construction() {
+        let interpreter = run("
+            class Point {
+                init(x, y) {
+                    this.x = x;
+                    this.y = y;
+                }
+                sum() { return this.x + this.y; }
+            }
+            var point = Point(1, 3);
+            var sum = point.sum;
+            point.x = 5;
+            var moved = sum();
+        ");
+
+        assert_eq!(global(&interpreter, "moved"), Value::Integer(8));
+        assert_eq!(
+            run_error("class A {} A().missing;"),
+            "Undefined property 'missing'."
+        );
+        assert_eq!(
+            run_error("class A {} A(1);"),
+            "Expected 0 arguments but got 1."
+        );
+    }
+
+    #[test]
+    fn match_tries_arms_in_order_and_binds_names() {
+        let interpreter = run("
+            class Point {
+                init(x, y) {
+                    this.x = x;
+                    this.y = y;
+                }
+                sum() { return this.x + this.y; }
+            }
+            fun describe(value) {
+                return match value {
+                    0 | -1 => \"small\",
+                    Point{x: 0, y} => y,
+                    Point{x, y} if x > y => \"wide\",
+                    Point{} => value.sum(),
+                    [first, ..rest] if first => rest,
+                    [_, second] => second,
+                    _ => nil,
+                };
+            }
+            var small = describe(-1.0);
+            var y = describe(Point(0, 7));
+            var wide = describe(Point(3, 1));
+            var sum = describe(Point(1, 3));
+            var rest = describe([true, 2, 3]);
+            var second = describe([false, 2]);
+        ");
+
+        assert_eq!(global(&interpreter, "small"), Value::String("small".into()));
+        assert_eq!(global(&interpreter, "y"), Value::Integer(7));
+        assert_eq!(global(&interpreter, "wide"), Value::String("wide".into()));
+        assert_eq!(global(&interpreter, "sum"), Value::Integer(4));
+        assert_eq!(global(&interpreter, "rest").to_string(), "[2, 3]");
+        assert_eq!(global(&interpreter, "second"), Value::Integer(2));
+
+        assert_eq!(
+            run_error("match [1] { [] => 0, \"a\" => 1 };"),
+            "No match arm matches [1]."
+        );
+    }
+
+    #[test]
+    fn modules_run_once_and_only_share_their_exports() {
+        let directory = std::env::temp_dir().join(format!("modules-{}", std::process::id()));
+        fs::create_dir_all(directory.join("lib")).unwrap();
+        let files = [
+            (
+                "lib/util.tk",
+                "var hidden = 1; export fun twice(n) { return n * 2; }",
+            ),
+            ("lib/cycle.tk", "import \"../loop.tk\" as other;"),
+            ("loop.tk", "from \"lib/cycle.tk\" import nothing;"),
+            ("lib/broken.tk", "const C = 1; C = 2;\nbreak;"),
+            ("lib/typed.tk", "var x: Int = \"one\";"),
+            ("lib/constant.tk", "export const X = 1; X = 2;"),
+        ];
+        for (name, code) in &files {
+            fs::write(directory.join(name), code).unwrap();
+        }
+
+        let run_script = |code: &str| {
+            let mut interpreter = Interpreter::for_script(&directory.join("main.tk"));
+            interpreter.interpret(&parse(code)).map(|()| interpreter)
+        };
+        let interpreter = run_script(
+            "import \"lib/util.tk\" as a; import \"lib/util.tk\" as b; \
+             from \"lib/util.tk\" import twice; var same = a == b; var four = twice(2);",
+        )
+        .expect("program failed");
+        assert_eq!(global(&interpreter, "same"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "four"), Value::Integer(4));
+
+        let error = |code: &str| run_script(code).err().expect("program should fail").message;
+        assert_eq!(
+            error("import \"lib/util.tk\" as u; u.hidden;"),
+            "Module 'lib/util.tk' doesn't export 'hidden'."
+        );
+        assert_eq!(
+            error("import \"loop.tk\" as l;"),
+            "Import cycle: loop.tk -> lib/cycle.tk -> ../loop.tk."
+        );
+        assert_eq!(
+            error("import \"lib/broken.tk\" as b;"),
+            "Module 'lib/broken.tk' has errors:\n\
+             [line 1, column 14] Error: Can't assign to constant 'C'.\n\
+             [line 1, column 7] Note: 'C' is declared here.\n\
+             [line 2, column 1] Error: Can't use 'break' outside of a loop."
+        );
+        assert_eq!(
+            error("import \"lib/typed.tk\" as t;"),
+            "Module 'lib/typed.tk' has errors:\n\
+             [line 1, column 14] Error: Can't assign String to 'x' of type Int."
+        );
+        assert_eq!(
+            error("import \"lib/constant.tk\" as c;"),
+            "Module 'lib/constant.tk' has errors:\n\
+             [line 1, column 21] Error: Can't assign to constant 'X'.\n\
+             [line 1, column 14] Note: 'X' is declared here."
+        );
+        assert_eq!(
+            run_error("const X = 1; X = 2;"),
+            "Can't assign to constant 'X'."
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn exceptions_unwind_to_the_nearest_catch_and_always_run_finally() {
+        let interpreter = run("
+            class Timeout < Error {
+                init(seconds) {
+                    super.init(\"timed out\");
+                    this.seconds = seconds;
+                }
+            }
+            var log = [];
+            fun attempt() {
+                try {
+                    throw Timeout(5);
+                } finally {
+                    log[0] = \"finally\";
+                }
+            }
+            fun early() {
+                try { return 1; } finally { log[1] = \"ran\"; }
+            }
+            log = [nil, nil];
+            var caught;
+            try { attempt(); } catch (e) { caught = e; }
+            var returned = early();
+            var builtin;
+            try { [1][3]; } catch (e) { builtin = e; }
+            var thrown;
+            try { throw 42; } catch (e) { thrown = e; }
+        ");
+
+        let caught = global(&interpreter, "caught");
+        let field = |value: &Value, name: &str| match value {
+            Value::Instance(instance) => instance.borrow().fields[name].clone(),
+            other => panic!("{} is not an instance", other),
+        };
+        assert_eq!(caught.to_string(), "<Timeout instance>");
+        assert_eq!(field(&caught, "message"), Value::String("timed out".into()));
+        assert_eq!(field(&caught, "seconds"), Value::Integer(5));
+        assert_eq!(field(&caught, "line"), Value::Integer(11));
+        assert_eq!(
+            global(&interpreter, "log").to_string(),
+            "[\"finally\", \"ran\"]"
+        );
+        assert_eq!(global(&interpreter, "returned"), Value::Integer(1));
+
+        let builtin = global(&interpreter, "builtin");
+        assert_eq!(builtin.to_string(), "<IndexError instance>");
+        assert_eq!(field(&builtin, "line"), Value::Integer(24));
+        assert_eq!(global(&interpreter, "thrown"), Value::Integer(42));
+
+        assert_eq!(
+            run_error("throw TypeError(\"no\");"),
+            "Uncaught TypeError: no"
+        );
+        assert_eq!(
+            run_error("try { x; } finally { }"),
+            "Undefined variable 'x'."
+        );
+    }
+
+    #[test]
+    fn if_and_loops_run_their_branches_and_bodies() {
+        let interpreter = run("
+            var steps = 0;
+            for (var i = 0; i < 10; i++) {
+                if (i % 2 == 0) steps += 1; else steps += 10;
+            }
+            var n = 0;
+            while (n < 3) n++;
+            fun first(xs) {
+                for (var k = 0; k < len(xs); k++) if (xs[k] > 1) return k;
+                return -1;
+            }
+            var found = first([0, 1, 5]);
+        ");
+
+        assert_eq!(global(&interpreter, "steps"), Value::Integer(55));
+        assert_eq!(global(&interpreter, "n"), Value::Integer(3));
+        assert_eq!(global(&interpreter, "found"), Value::Integer(2));
+    }
+
+    #[test]
+    fn loops_break_and_continue_by_label() {
+        let interpreter = run("
+            var steps = 0;
+            for (var i = 0; i < 10; i++) {
+                if (i % 2 == 0) continue;
+                steps += i;
+            }
+            var pairs = 0;
+            var n = 0;
+            outer: while (true) {
+                for (var j = 0; ; j++) {
+                    if (j == n) { n++; continue outer; }
+                    if (n == 3) break outer;
+                    pairs = pairs * 100 + n * 10 + j;
+                }
+            }
+            fun first(xs) {
+                for (var k = 0; k < len(xs); k++) if (xs[k] > 1) return k;
+                return -1;
+            }
+            var found = first([0, 1, 5]);
+        ");
+
+        assert_eq!(global(&interpreter, "steps"), Value::Integer(25));
+        assert_eq!(global(&interpreter, "pairs"), Value::Integer(102021));
+        assert_eq!(global(&interpreter, "n"), Value::Integer(3));
+        assert_eq!(global(&interpreter, "found"), Value::Integer(2));
+        assert_eq!(run_error("break;"), "Can't use 'break' outside of a loop.");
+    }
+
+    #[test]
+    fn enum_variants_construct_compare_and_destructure() {
+        let interpreter = run("
+            enum Shape { Circle(r), Rect(w, h), Empty }
+            fun area(shape) {
+                return match shape {
+                    Shape.Circle(r) => 3 * r * r,
+                    Shape.Rect(w, h) => w * h,
+                    Shape.Empty => 0,
+                };
+            }
+            var areas = [area(Shape.Circle(2)), area(Shape.Rect(2, 5)), area(Shape.Empty)];
+            var rect = Shape.Rect(1, \"a\");
+            var same = Shape.Rect(1, \"a\") == rect;
+            var different = Shape.Rect(\"a\", 1) == rect;
+            var width = rect.w;
+        ");
+
+        assert_eq!(global(&interpreter, "areas").to_string(), "[12, 10, 0]");
+        assert_eq!(
+            global(&interpreter, "rect").to_string(),
+            "Shape.Rect(1, \"a\")"
+        );
+        assert_eq!(global(&interpreter, "same"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "different"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "width"), Value::Integer(1));
+        assert_eq!(
+            run_error("enum E { A(x) } E.A(1, 2);"),
+            "Expected 1 arguments but got 2."
+        );
+        assert_eq!(
+            run_error("enum E { A } E.B;"),
+            "Enum 'E' has no variant 'B'."
+        );
+    }
+
+    #[test]
+    fn for_in_walks_ranges_lists_maps_and_iterators() {
+        let interpreter = run("
+            var sum = 0;
+            for (i in 0..5) sum += i;
+            for (i in 1..=3) sum += i * 100;
+            var squares = 0;
+            for (x in [1, 2, 3]) {
+                if (x == 2) continue;
+                squares += x * x;
+            }
+            var scores = {\"a\": 1, \"b\": 2};
+            var total = 0;
+            for (name, score in scores) total += score;
+            var names = 0;
+            for (name in scores) names++;
+            class Countdown {
+                init(n) { this.n = n; }
+                iter() { return this; }
+                next() {
+                    if (this.n == 0) return nil;
+                    this.n = this.n - 1;
+                    return this.n;
+                }
+            }
+            var seen = 0;
+            outer: for (c in Countdown(5)) {
+                for (j in 0..10) if (j == 1) continue outer;
+                seen = 1;
+            }
+            var last = nil;
+            for (c in Countdown(3)) last = c;
+        ");
+
+        assert_eq!(global(&interpreter, "sum"), Value::Integer(610));
+        assert_eq!(global(&interpreter, "squares"), Value::Integer(10));
+        assert_eq!(global(&interpreter, "total"), Value::Integer(3));
+        assert_eq!(global(&interpreter, "names"), Value::Integer(2));
+        assert_eq!(global(&interpreter, "seen"), Value::Integer(0));
+        assert_eq!(global(&interpreter, "last"), Value::Integer(0));
+        assert_eq!(
+            run_error("for (x in true) print x;"),
+            "Can't iterate over a boolean."
+        );
+        assert_eq!(
+            run_error("for (k, v in [1]) print k;"),
+            "Only maps can be iterated with a key and a value."
+        );
+    }
+
+    #[test]
+    fn generators_resume_where_they_yielded() {
+        let interpreter = run("
+            fun naturals() {
+                var n = 0;
+                while (true) { yield n; n++; }
+            }
+            fun take(items, count) {
+                for (item in items) {
+                    if (count == 0) return;
+                    count--;
+                    yield item;
+                }
+            }
+            fun squares(items) { for (x in items) yield x * x; }
+            var sum = 0;
+            for (s in squares(take(naturals(), 4))) sum += s;
+
+            var g = take(naturals(), 2);
+            var first = g.next();
+            var second = g.next();
+            var done = g.next();
+
+            fun evens(limit) {
+                for (var i = 0; i < limit; i++) {
+                    if (i % 2 == 1) continue;
+                    yield i;
+                }
+            }
+            var digits = 0;
+            for (e in evens(7)) digits = digits * 10 + e;
+
+            var cleaned = false;
+            fun guarded() {
+                try { yield \"a\"; throw \"b\"; } catch (e) { yield e; } finally { cleaned = true; }
+            }
+            var log = \"\";
+            for (x in guarded()) log = log + x;
+
+            fun failing() { yield 1; throw \"boom\"; }
+            var f = failing();
+            f.next();
+            var caught = nil;
+            try { f.next(); } catch (e) { caught = e; }
+            var after = f.next();
+
+            fun branches(flag) { if (flag) yield 1; else { { yield 2; } yield 3; } }
+            var picked = 0;
+            for (b in branches(false)) picked = picked * 10 + b;
+        ");
+
+        assert_eq!(global(&interpreter, "sum"), Value::Integer(14));
+        assert_eq!(global(&interpreter, "first"), Value::Integer(0));
+        assert_eq!(global(&interpreter, "second"), Value::Integer(1));
+        assert_eq!(global(&interpreter, "done"), Value::Nil);
+        assert_eq!(global(&interpreter, "digits"), Value::Integer(246));
+        assert_eq!(global(&interpreter, "log"), Value::String("ab".to_string()));
+        assert_eq!(global(&interpreter, "cleaned"), Value::Boolean(true));
+        assert_eq!(
+            global(&interpreter, "caught"),
+            Value::String("boom".to_string())
+        );
+        assert_eq!(global(&interpreter, "after"), Value::Nil);
+        assert_eq!(global(&interpreter, "picked"), Value::Integer(23));
+        assert_eq!(
+            run_error("var s; fun g() { yield 1; s.next(); } s = g(); s.next(); s.next();"),
+            "Generator is already running."
+        );
+    }
+
+    #[test]
+    fn arguments_fill_parameters_by_position_name_default_and_rest() {
+        let interpreter = run("
+            fun greet(name, greeting = \"Hello\", mark = \"!\") {
+                return greeting + \", \" + name + mark;
+            }
+            var plain = greet(\"Ann\");
+            var named = greet(\"Bob\", mark: \"?\");
+            var reordered = greet(greeting: \"Hi\", name: \"Cy\");
+
+            fun count(first, ...rest) { return len(rest); }
+            var none = count(1);
+            var some = count(1, 2, 3);
+
+            fun double(a, b = a * 2) { return b; }
+            var doubled = double(4);
+            var calls = 0;
+            fun counted(a = calls++) { return a; }
+            counted(); counted(7); counted();
+
+            class Point { init(x, y = 0) { this.x = x; this.y = y; } }
+            var y = Point(1, y: 5).y;
+
+            fun mix(a, b = 2, ...rest) { return a + b * 10 + len(rest) * 100; }
+            var short = mix(1);
+            var skipped = mix(1, b: 9);
+            var long = mix(1, 3, 4, 5);
+            fun only(a, ...rest) { return a + len(rest); }
+            var by_name = only(a: 1);
+        ");
+
+        assert_eq!(
+            global(&interpreter, "plain"),
+            Value::String("Hello, Ann!".to_string())
+        );
+        assert_eq!(
+            global(&interpreter, "named"),
+            Value::String("Hello, Bob?".to_string())
+        );
+        assert_eq!(
+            global(&interpreter, "reordered"),
+            Value::String("Hi, Cy!".to_string())
+        );
+        assert_eq!(global(&interpreter, "none"), Value::Integer(0));
+        assert_eq!(global(&interpreter, "some"), Value::Integer(2));
+        assert_eq!(global(&interpreter, "doubled"), Value::Integer(8));
+        assert_eq!(global(&interpreter, "calls"), Value::Integer(2));
+        assert_eq!(global(&interpreter, "y"), Value::Integer(5));
+        assert_eq!(global(&interpreter, "short"), Value::Integer(21));
+        assert_eq!(global(&interpreter, "skipped"), Value::Integer(91));
+        assert_eq!(global(&interpreter, "long"), Value::Integer(231));
+        assert_eq!(global(&interpreter, "by_name"), Value::Integer(1));
+
+        let greet = "fun greet(name, greeting = 1) {} ";
+        assert_eq!(
+            run_error(&format!("{}greet();", greet)),
+            "Expected 1 to 2 arguments but got 0."
+        );
+        assert_eq!(
+            run_error(&format!("{}greet(1, 2, 3);", greet)),
+            "Expected 1 to 2 arguments but got 3."
+        );
+        assert_eq!(
+            run_error(&format!("{}greet(1, nope: 2);", greet)),
+            "No parameter named 'nope'."
+        );
+        assert_eq!(
+            run_error(&format!("{}greet(1, name: 2);", greet)),
+            "Argument 'name' is passed more than once."
+        );
+        assert_eq!(
+            run_error(&format!("{}greet(greeting: 2);", greet)),
+            "Missing argument for 'name'."
+        );
+        assert_eq!(
+            run_error("fun f(a, ...rest) {} f();"),
+            "Expected at least 1 arguments but got 0."
+        );
+        assert_eq!(
+            run_error("fun f(...rest) {} f(rest: 1);"),
+            "Can't pass the rest parameter 'rest' by name."
+        );
+        assert_eq!(
+            run_error("len(list: [1]);"),
+            "Can't pass named arguments to <native fn len>."
+        );
+    }
+}
@@ -1,37 +1,55 @@
-extern crate lazy_static;
-extern crate regex;
-use self::lazy_static::lazy_static;
-use self::regex::Regex;
-use std::any::Any;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap; // This line is crucial!
                                //
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Serialize)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Print,
     Dot,
+    DotDot,
+    DotDotDot,
+    DotDotEqual,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
     Bang,
     BangAndEqual,
     Equal,
     Arrow,
+    ThinArrow,
     Greater,
     GreaterOrEqual,
     Question,
     Colon,
     Less,
     LessOrEqual,
+    LessLess,
+    GreaterGreater,
+    StarStar,
+    PlusPlus,
+    MinusMinus,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
 
     // Literals.
     Identifier,
@@ -40,48 +58,89 @@ pub enum TokenType {
 
     // Keywords.
     And,
+    As,
+    Break,
+    Catch,
     Class,
+    Const,
+    Continue,
     Else,
+    Enum,
+    Export,
     False,
+    Finally,
+    From,
     Fun,
     For,
     If,
+    Import,
+    In,
+    Match,
     Nil,
     Or,
     ECHO,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
+    Yield,
 
     EOF,
     EqualAndEqual,
 }
-#[derive(Debug)]
+// The value carried by literal tokens, numbers without a fractional part are kept as integers
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub enum TokenLiteral {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Nil,
+}
+
+// Where something is in the source code, columns start at 1 like lines do
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32,
+    pub length: u32,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
-    pub literal: Option<Box<dyn Any>>,
+    pub literal: Option<TokenLiteral>,
     pub line: u32,
+    pub column: u32,
 }
 
 impl Token {
-    pub fn get_literal<T: 'static>(&self) -> Option<&T> {
-        self.literal.as_ref()?.downcast_ref::<T>()
+    pub fn span(&self) -> Span {
+        Span {
+            line: self.line,
+            column: self.column,
+            length: self.lexeme.chars().count() as u32,
+        }
     }
+
     pub fn new(
         token_type: TokenType,
         lexeme: String,
-        literal: Option<Box<dyn Any>>,
+        literal: Option<TokenLiteral>,
         line: u32,
+        column: u32,
     ) -> Token {
         Token {
             token_type,
             lexeme,
             literal,
             line,
+            column,
         }
     }
 }
@@ -92,6 +151,8 @@ pub struct Scanner {
     start: u32,
     current: u32,
     line: u32,
+    // Offset where the current line begins, used to compute the column of each token
+    line_start: u32,
 }
 
 lazy_static! {
@@ -99,12 +160,21 @@ lazy_static! {
         let mut map = HashMap::new();
         map.insert("if", TokenType::If);
         map.insert("else", TokenType::Else);
+        map.insert("enum", TokenType::Enum);
         map.insert("class", TokenType::Class);
         map.insert("false", TokenType::False);
         map.insert("for", TokenType::For);
+        map.insert("in", TokenType::In);
+        map.insert("as", TokenType::As);
+        map.insert("export", TokenType::Export);
+        map.insert("from", TokenType::From);
+        map.insert("import", TokenType::Import);
+        map.insert("match", TokenType::Match);
         map.insert("fun", TokenType::Fun);
         map.insert("nil", TokenType::Nil);
         map.insert("while", TokenType::While);
+        map.insert("break", TokenType::Break);
+        map.insert("continue", TokenType::Continue);
         map.insert("true", TokenType::True);
         map.insert("or", TokenType::Or);
         map.insert("and", TokenType::And);
@@ -112,7 +182,13 @@ lazy_static! {
         map.insert("return", TokenType::Return);
         map.insert("super", TokenType::Super);
         map.insert("this", TokenType::This);
+        map.insert("throw", TokenType::Throw);
+        map.insert("try", TokenType::Try);
+        map.insert("catch", TokenType::Catch);
+        map.insert("finally", TokenType::Finally);
         map.insert("var", TokenType::Var);
+        map.insert("const", TokenType::Const);
+        map.insert("yield", TokenType::Yield);
         map
     };
 }
@@ -125,6 +201,7 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
         }
     }
 
@@ -133,17 +210,23 @@ impl Scanner {
 
         self.current += 1;
 
-        return c;
+        c
     }
 
     fn add_token_with_type(&mut self, token_type: TokenType) {
         self.add_token(token_type, None);
     }
 
-    fn add_token(&mut self, token_type: TokenType, literal: Option<Box<dyn Any>>) {
+    fn add_token(&mut self, token_type: TokenType, literal: Option<TokenLiteral>) {
         let text = &self.source_code[self.start as usize..self.current as usize];
-        self.tokens
-            .push(Token::new(token_type, text.to_string(), literal, self.line));
+        let column = self.start - self.line_start + 1;
+        self.tokens.push(Token::new(
+            token_type,
+            text.to_string(),
+            literal,
+            self.line,
+            column,
+        ));
     }
 
     pub fn scan_tokens(&mut self) -> &Vec<Token> {
@@ -152,13 +235,19 @@ impl Scanner {
             self.scan_token();
         }
 
-        self.tokens
-            .push(Token::new(TokenType::EOF, String::new(), None, self.line));
-        return &self.tokens;
+        let column = self.current - self.line_start + 1;
+        self.tokens.push(Token::new(
+            TokenType::EOF,
+            String::new(),
+            None,
+            self.line,
+            column,
+        ));
+        &self.tokens
     }
 
     fn is_at_end(&self) -> bool {
-        return self.current >= self.source_code.len() as u32;
+        self.current >= self.source_code.len() as u32
     }
 
     fn scan_token(&mut self) {
@@ -168,21 +257,72 @@ impl Scanner {
             Some(')') => self.add_token_with_type(TokenType::RightParen),
             Some('{') => self.add_token_with_type(TokenType::LeftBrace),
             Some('}') => self.add_token_with_type(TokenType::RightBrace),
+            Some('[') => self.add_token_with_type(TokenType::LeftBracket),
+            Some(']') => self.add_token_with_type(TokenType::RightBracket),
             Some(',') => self.add_token_with_type(TokenType::Comma),
-            Some('.') => self.add_token_with_type(TokenType::Dot),
-            Some('-') => self.add_token_with_type(TokenType::Minus),
-            Some('+') => self.add_token_with_type(TokenType::Plus),
+            Some('.') => {
+                let token_type = if self.validate_symbol('.') {
+                    if self.validate_symbol('=') {
+                        TokenType::DotDotEqual
+                    } else if self.validate_symbol('.') {
+                        TokenType::DotDotDot
+                    } else {
+                        TokenType::DotDot
+                    }
+                } else {
+                    TokenType::Dot
+                };
+                self.add_token_with_type(token_type)
+            }
             Some('?') => self.add_token_with_type(TokenType::Question),
             Some(':') => self.add_token_with_type(TokenType::Colon),
             Some(';') => self.add_token_with_type(TokenType::Semicolon),
-            Some('*') => self.add_token_with_type(TokenType::Star),
-            Some('o') => {
-                if self.validate_symbol('r') {
-                    self.add_token_with_type(TokenType::Or);
-                }
-            }
+            Some('&') => self.add_token_with_type(TokenType::Ampersand),
+            Some('|') => self.add_token_with_type(TokenType::Pipe),
+            Some('^') => self.add_token_with_type(TokenType::Caret),
+            Some('~') => self.add_token_with_type(TokenType::Tilde),
             // These next validations are comparing the next character after the current one and
             // validating if its a equal to return the symbol + equal combination
+            Some('-') => {
+                let token_type = if self.validate_symbol('-') {
+                    TokenType::MinusMinus
+                } else if self.validate_symbol('=') {
+                    TokenType::MinusEqual
+                } else if self.validate_symbol('>') {
+                    TokenType::ThinArrow
+                } else {
+                    TokenType::Minus
+                };
+                self.add_token_with_type(token_type)
+            }
+            Some('+') => {
+                let token_type = if self.validate_symbol('+') {
+                    TokenType::PlusPlus
+                } else if self.validate_symbol('=') {
+                    TokenType::PlusEqual
+                } else {
+                    TokenType::Plus
+                };
+                self.add_token_with_type(token_type)
+            }
+            Some('*') => {
+                let token_type = if self.validate_symbol('*') {
+                    TokenType::StarStar
+                } else if self.validate_symbol('=') {
+                    TokenType::StarEqual
+                } else {
+                    TokenType::Star
+                };
+                self.add_token_with_type(token_type)
+            }
+            Some('%') => {
+                let token_type = if self.validate_symbol('=') {
+                    TokenType::PercentEqual
+                } else {
+                    TokenType::Percent
+                };
+                self.add_token_with_type(token_type)
+            }
             Some('!') => {
                 let token_type = if self.validate_symbol('=') {
                     TokenType::BangAndEqual
@@ -194,13 +334,17 @@ impl Scanner {
             Some('=') => {
                 let token_type = if self.validate_symbol('=') {
                     TokenType::EqualAndEqual
+                } else if self.validate_symbol('>') {
+                    TokenType::Arrow
                 } else {
                     TokenType::Equal
                 };
                 self.add_token_with_type(token_type)
             }
             Some('<') => {
-                let token_type = if self.validate_symbol('=') {
+                let token_type = if self.validate_symbol('<') {
+                    TokenType::LessLess
+                } else if self.validate_symbol('=') {
                     TokenType::LessOrEqual
                 } else {
                     TokenType::Less
@@ -208,7 +352,9 @@ impl Scanner {
                 self.add_token_with_type(token_type)
             }
             Some('>') => {
-                let token_type = if self.validate_symbol('=') {
+                let token_type = if self.validate_symbol('>') {
+                    TokenType::GreaterGreater
+                } else if self.validate_symbol('=') {
                     TokenType::GreaterOrEqual
                 } else {
                     TokenType::Greater
@@ -236,11 +382,9 @@ impl Scanner {
                         }
 
                         // Keep track of newlines within comments
-                        if self.peek() == Some('\n') {
-                            self.line += 1;
+                        if self.advance() == Some('\n') {
+                            self.new_line();
                         }
-
-                        self.advance();
                     }
 
                     // Check if we ended due to EOF (unclosed comment)
@@ -248,13 +392,15 @@ impl Scanner {
                         // Handle error - unclosed comment
                         // self.error(self.line, "Unterminated multiline comment.");
                     }
+                } else if self.validate_symbol('=') {
+                    self.add_token_with_type(TokenType::SlashEqual);
                 } else {
                     // Just a regular division operator
                     self.add_token_with_type(TokenType::Slash);
                 }
             }
             Some('"') => self.string(),
-            Some('\n') => self.line = self.line + 1,
+            Some('\n') => self.new_line(),
             Some(' ') => {}
             Some('\r') => {}
             Some('\t') => {}
@@ -272,12 +418,16 @@ impl Scanner {
         }
     }
 
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
     fn string(&mut self) {
         while self.peek().unwrap() != '"' && !self.is_at_end() {
-            if self.peek().unwrap() == '\n' {
-                self.line = self.line + 1
+            if self.advance() == Some('\n') {
+                self.new_line();
             }
-            self.advance();
         }
 
         if self.is_at_end() {
@@ -291,11 +441,14 @@ impl Scanner {
         let end = self.current - 1;
         let start = self.start + 1;
         let value = &self.source_code[start as usize..end as usize];
-        self.add_token(TokenType::String, Some(Box::new(value.to_string())));
+        self.add_token(
+            TokenType::String,
+            Some(TokenLiteral::String(value.to_string())),
+        );
     }
 
     fn is_digit(&self, c: char) -> bool {
-        return c >= '0' && c <= '9';
+        c.is_ascii_digit()
     }
 
     fn number(&mut self) {
@@ -310,9 +463,20 @@ impl Scanner {
                 self.advance();
             }
         }
-        match self.source_code[self.start as usize..self.current as usize].parse::<f64>() {
-            Ok(value) => self.add_token(TokenType::Number, Some(Box::new(value))),
-            Err(e) => panic!("Failed to parse: {}", e),
+        let text = &self.source_code[self.start as usize..self.current as usize];
+        let literal = if text.contains('.') {
+            text.parse::<f64>().map(TokenLiteral::Float).ok()
+        } else {
+            // Integers too big for an i64 still make a valid number, just a less precise one
+            text.parse::<i64>()
+                .map(TokenLiteral::Integer)
+                .or_else(|_| text.parse::<f64>().map(TokenLiteral::Float))
+                .ok()
+        };
+
+        match literal {
+            Some(value) => self.add_token(TokenType::Number, Some(value)),
+            None => panic!("Failed to parse number \"{}\" at line {}", text, self.line),
         }
     }
 
@@ -321,15 +485,7 @@ impl Scanner {
             return Some('\0');
         }
 
-        return self.source_code.chars().nth((self.current + 1) as usize);
-    }
-
-    fn peek_next_after_next(&self) -> Option<char> {
-        if self.current + 2 >= self.source_code.len() as u32 {
-            return Some('\0');
-        }
-
-        return self.source_code.chars().nth((self.current + 2) as usize);
+        self.source_code.chars().nth((self.current + 1) as usize)
     }
 
     fn peek(&self) -> Option<char> {
@@ -337,26 +493,24 @@ impl Scanner {
             return Some('\0');
         }
 
-        return self.source_code.chars().nth(self.current as usize);
+        self.source_code.chars().nth(self.current as usize)
     }
 
     fn validate_symbol(&mut self, c: char) -> bool {
-        match self.is_at_end() || self.source_code.chars().nth(self.current as usize).unwrap() != c
-        {
-            true => return false,
-            false => {
-                self.current += 1;
-                return true;
-            }
+        if self.is_at_end() || self.source_code.chars().nth(self.current as usize).unwrap() != c {
+            return false;
         }
+
+        self.current += 1;
+        true
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        return (c >= 'a' && c <= 'z') || (c <= 'a' && c <= 'Z') || (c == '_');
+        c.is_ascii_lowercase() || c.is_ascii_uppercase() || c == '_'
     }
 
     fn is_alphanumeric(&self, c: char) -> bool {
-        return self.is_alpha(c) && self.is_digit(c);
+        self.is_alpha(c) || self.is_digit(c)
     }
 
     fn identifier(&mut self) {
@@ -366,34 +520,86 @@ impl Scanner {
 
         let text = &self.source_code[self.start as usize..self.current as usize];
         let token_type: TokenType = KEYWORDS.get(text).unwrap_or(&TokenType::Identifier).clone();
-        self.add_token_with_type(token_type);
+        let literal = match token_type {
+            TokenType::True => Some(TokenLiteral::Boolean(true)),
+            TokenType::False => Some(TokenLiteral::Boolean(false)),
+            TokenType::Nil => Some(TokenLiteral::Nil),
+            _ => None,
+        };
+        self.add_token(token_type, literal);
     }
 }
 
-pub fn generate_tokens(text: &str) {
-    let string_regex = Regex::new(r#""[\w\s]*""#).unwrap();
-    let number_regex = Regex::new(r"\d+\.?\d+").unwrap();
-    let parentheses_regex = Regex::new(r"\(|\)").unwrap();
-    let comment_regex = Regex::new(r"\/{2}.*").unwrap();
-
-    let strings_matched = string_regex.find_iter(text);
-    let parentheses_matched = parentheses_regex.find_iter(text);
-    let numbers_matched = number_regex.find_iter(text);
-    let comments_matched = comment_regex.find_iter(text);
-
-    for mat in comments_matched {
-        println!("Comments matched: {}", mat.as_str());
-    }
-
-    for mat in parentheses_matched {
-        println!("Parentheses matched: {}", mat.as_str());
-    }
-
-    for mat in strings_matched {
-        println!("Strings matched: {}", mat.as_str());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literals_keep_whether_they_are_integers_floats_or_strings() {
+        let mut scanner =
+            Scanner::new("42 4.5 7. \"4.5\" true nil x 99999999999999999999".to_string());
+        let literals: Vec<(TokenType, Option<TokenLiteral>)> = scanner
+            .scan_tokens()
+            .iter()
+            .map(|token| (token.token_type.clone(), token.literal.clone()))
+            .collect();
+
+        assert_eq!(
+            literals,
+            vec![
+                (TokenType::Number, Some(TokenLiteral::Integer(42))),
+                (TokenType::Number, Some(TokenLiteral::Float(4.5))),
+                // A dot without digits after it isn't part of the number
+                (TokenType::Number, Some(TokenLiteral::Integer(7))),
+                (TokenType::Dot, None),
+                (
+                    TokenType::String,
+                    Some(TokenLiteral::String("4.5".to_string()))
+                ),
+                (TokenType::True, Some(TokenLiteral::Boolean(true))),
+                (TokenType::Nil, Some(TokenLiteral::Nil)),
+                (TokenType::Identifier, None),
+                (TokenType::Number, Some(TokenLiteral::Float(1e20))),
+                (TokenType::EOF, None),
+            ]
+        );
     }
 
-    for mat in numbers_matched {
-        println!("Numbers matched: {}", mat.as_str());
+    #[test]
+    fn operators_take_the_longest_match() {
+        let mut scanner = Scanner::new("a%=b**c++ - --d % e*f+=1<<2<=3>>4".to_string());
+        let types: Vec<TokenType> = scanner
+            .scan_tokens()
+            .iter()
+            .map(|token| token.token_type.clone())
+            .collect();
+
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Identifier,
+                TokenType::PercentEqual,
+                TokenType::Identifier,
+                TokenType::StarStar,
+                TokenType::Identifier,
+                TokenType::PlusPlus,
+                TokenType::Minus,
+                TokenType::MinusMinus,
+                TokenType::Identifier,
+                TokenType::Percent,
+                TokenType::Identifier,
+                TokenType::Star,
+                TokenType::Identifier,
+                TokenType::PlusEqual,
+                TokenType::Number,
+                TokenType::LessLess,
+                TokenType::Number,
+                TokenType::LessOrEqual,
+                TokenType::Number,
+                TokenType::GreaterGreater,
+                TokenType::Number,
+                TokenType::EOF,
+            ]
+        );
     }
 }
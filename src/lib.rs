@@ -1,3 +1,33 @@
+extern crate lazy_static;
+extern crate serde;
+extern crate serde_json;
+
+pub mod ast;
+pub mod ast_printer;
+pub mod class;
+pub mod const_fold;
+pub mod enumeration;
+pub mod environment;
+pub mod fold;
+pub mod function;
+pub mod generator;
+pub mod interpreter;
+pub mod lexer;
+pub mod map;
+pub mod match_check;
+pub mod module;
+pub mod natives;
+pub mod parser;
+pub mod pipeline;
+pub mod prelude;
+pub mod resolver;
+pub mod rpn_ast_printer;
+pub mod source_printer;
+pub mod tools;
+pub mod type_check;
+pub mod value;
+pub mod visit_mut;
+
 #[cfg(test)]
 mod tests {
     #[test]
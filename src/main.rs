@@ -1,55 +1,261 @@
-mod ast;
-mod ast_printer;
-mod lexer;
-mod parser;
-mod rpn_ast_printer;
-
-use ast::{Binary, Expr, Grouping, Literal, LiteralValue, Unary};
-use ast_printer::AstPrinter;
-use lexer::{Scanner, Token, TokenType};
-use parser::Parser;
-use rpn_ast_printer::RPNAstPrinter;
+extern crate programming_language;
+extern crate serde_json;
+
+use programming_language::ast::Stmt;
+use programming_language::ast_printer::AstPrinter;
+use programming_language::interpreter::Interpreter;
+use programming_language::lexer::{Scanner, Token, TokenLiteral};
+use programming_language::parser::{operator_table, Parser};
+use programming_language::pipeline::check_program;
+use programming_language::rpn_ast_printer::RPNAstPrinter;
+use programming_language::source_printer::SourcePrinter;
+use programming_language::tools::check_grammar::check_grammar;
+use programming_language::tools::generate_programs::{random_seed, Grammar, ProgramGenerator};
+use std::env;
 use std::fs;
-use std::fs::File;
-use std::io::BufWriter;
+use std::path::Path;
+use std::process;
 
 fn main() {
-    read_ast();
-}
+    let args: Vec<String> = env::args().skip(1).collect();
 
-fn read_file() {
-    let text = fs::read_to_string("example.tk");
-
-    match text {
-        // Ok(data) => generate_tokens(&data),
-        Ok(data) => println!("{:?}", Scanner::new(data).scan_tokens()),
-        Err(e) => println!("Error: {}", e),
+    match args.first().map(String::as_str) {
+        Some("tokens") => dump_tokens(&args[1..]),
+        Some("run") => run_file(args.get(1).map(String::as_str).unwrap_or("example.tk")),
+        Some("generate") => generate_program(&args[1..]),
+        Some("operators") => print!("{}", operator_table()),
+        Some("check-grammar") => check_grammar_file(),
+        _ => read_ast(&args),
     }
 }
 
-fn read_ast() {
-    let result = fs::read_to_string("example.tk");
-    let code: String = match result {
-        // Ok(string_value) => "1 + 2 / 4 - 2 * 45 - 2 + (55 * 12)".into(),
-        Ok(string_value) => string_value,
-        Err(err) => {
-            panic!("Failed: {}", err)
+// Usage: tokens [--json] [file], prints every token the scanner produces for the file
+fn dump_tokens(args: &[String]) {
+    let json = args.iter().any(|arg| arg == "--json");
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .map(String::as_str)
+        .unwrap_or("example.tk");
+
+    let code = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(65);
         }
     };
+
     let mut scanner = Scanner::new(code);
     let tokens = scanner.scan_tokens();
-    let mut parser = Parser::new(tokens);
-    // let expression = parser.parse();
-    let expression = match parser.parse() {
-        Some(expr) => expr,
-        none => {
-            panic!("Failed: {}", 1)
+
+    if json {
+        for token in tokens {
+            match serde_json::to_string(token) {
+                Ok(line) => println!("{}", line),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(65);
+                }
+            }
+        }
+    } else {
+        print!("{}", token_table(tokens));
+    }
+}
+
+// A header, then a row for each token with its columns lined up
+fn token_table(tokens: &[Token]) -> String {
+    let mut table = format!(
+        "{:<16} {:<20} {:<20} {:>5} {:>6}\n",
+        "TYPE", "LEXEME", "LITERAL", "LINE", "COLUMN"
+    );
+    for token in tokens {
+        table.push_str(&token_row(token));
+        table.push('\n');
+    }
+    table
+}
+
+fn token_row(token: &Token) -> String {
+    let literal = match &token.literal {
+        Some(TokenLiteral::Integer(int)) => int.to_string(),
+        Some(TokenLiteral::Float(float)) => format!("{:?}", float),
+        Some(TokenLiteral::String(s)) => format!("{:?}", s),
+        Some(TokenLiteral::Boolean(b)) => b.to_string(),
+        Some(TokenLiteral::Nil) => String::from("nil"),
+        None => String::new(),
+    };
+    format!(
+        "{:<16} {:<20} {:<20} {:>5} {:>6}",
+        format!("{:?}", token.token_type),
+        token.lexeme,
+        literal,
+        token.line,
+        token.column
+    )
+}
+
+// Usage: generate [--seed N] [--depth N], prints a random program that follows grammar.txt
+fn generate_program(args: &[String]) {
+    let option = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+    let seed = option("--seed").unwrap_or_else(random_seed);
+    let depth = option("--depth").unwrap_or(40) as usize;
+
+    let grammar = match fs::read_to_string("grammar.txt")
+        .map_err(|e| e.to_string())
+        .and_then(|text| Grammar::parse(&text))
+    {
+        Ok(grammar) => grammar,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(65);
+        }
+    };
+
+    println!(
+        "{}",
+        ProgramGenerator::new(&grammar, seed, depth).generate()
+    );
+}
+
+// Reports the grammar.txt rules the parser doesn't implement, and operators missing from it
+fn check_grammar_file() {
+    let grammar = match fs::read_to_string("grammar.txt")
+        .map_err(|e| e.to_string())
+        .and_then(|text| Grammar::parse(&text))
+    {
+        Ok(grammar) => grammar,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(65);
         }
     };
-    // if had_error {
-    //     return;
-    // }
 
-    let mut printer = RPNAstPrinter {};
-    println!("{}", printer.print(&expression));
+    let problems = check_grammar(&grammar, include_str!("parser.rs"));
+    if problems.is_empty() {
+        println!("grammar.txt matches the parser.");
+        return;
+    }
+    for problem in &problems {
+        eprintln!("{}", problem);
+    }
+    process::exit(1);
+}
+
+fn run_file(path: &str) {
+    let statements = match load_program(path) {
+        Some(statements) => statements,
+        None => process::exit(65),
+    };
+
+    let checked = check_program(statements);
+    for warning in &checked.warnings {
+        eprintln!("{}", warning);
+    }
+    let statements = match checked.statements {
+        Ok(statements) => statements,
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{}", error);
+            }
+            process::exit(65);
+        }
+    };
+
+    let mut interpreter = Interpreter::for_script(Path::new(path));
+    if let Err(e) = interpreter.interpret(&statements) {
+        eprintln!("{}", e);
+        process::exit(70);
+    }
+}
+
+// Usage: [--emit=rpn|ast|ast-json|source] [file], prints the syntax tree of the file
+fn read_ast(args: &[String]) {
+    let emit = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--emit="))
+        .unwrap_or("rpn");
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .map(String::as_str)
+        .unwrap_or("example.tk");
+
+    let statements = match load_program(path) {
+        Some(statements) => statements,
+        None => process::exit(65),
+    };
+
+    match emit {
+        "rpn" => println!("{}", RPNAstPrinter {}.print_program(&statements)),
+        "ast" => println!("{}", AstPrinter {}.print_program(&statements)),
+        "source" => println!("{}", SourcePrinter::new().print_program(&statements)),
+        "ast-json" => match serde_json::to_string_pretty(&statements) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(65);
+            }
+        },
+        _ => {
+            eprintln!(
+                "Error: unknown --emit value '{}', expected rpn, ast, ast-json or source.",
+                emit
+            );
+            process::exit(64);
+        }
+    }
+}
+
+// Source files are scanned and parsed, `.json` files hold a tree written by --emit=ast-json
+fn load_program(path: &str) -> Option<Vec<Stmt>> {
+    let code = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return None;
+        }
+    };
+
+    if path.ends_with(".json") {
+        return match serde_json::from_str(&code) {
+            Ok(statements) => Some(statements),
+            Err(e) => {
+                eprintln!("Error: invalid AST in {}: {}", path, e);
+                None
+            }
+        };
+    }
+
+    let mut scanner = Scanner::new(code);
+    let tokens = scanner.scan_tokens();
+    Parser::new(tokens).parse_program()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_print_as_a_table_with_their_literals() {
+        let mut scanner = Scanner::new("n = \"hi\" 2.5\n  7".to_string());
+        let table = token_table(scanner.scan_tokens());
+
+        let expected = [
+            "TYPE             LEXEME               LITERAL               LINE COLUMN",
+            "Identifier       n                                             1      1",
+            "Equal            =                                             1      3",
+            "String           \"hi\"                 \"hi\"                     1      5",
+            "Number           2.5                  2.5                      1     10",
+            "Number           7                    7                        2      3",
+            "EOF                                                            2      4",
+        ];
+        assert_eq!(table, expected.join("\n") + "\n");
+    }
 }
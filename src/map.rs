@@ -0,0 +1,158 @@
+use crate::value::{integral, Value};
+use std::collections::HashMap;
+
+// What a map hashes its keys by. Numbers follow `==`, so a float with an integral value is the
+// same key as that integer and -0.0 the same key as 0. NaN isn't equal to itself, so it can't
+// be found again and is rejected
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Integer(i64),
+    // The bits of a float that isn't integral, NaN never gets here
+    Float(u64),
+    String(String),
+    Boolean(bool),
+    Nil,
+}
+
+impl MapKey {
+    pub fn new(value: &Value) -> Result<MapKey, String> {
+        match value {
+            Value::Integer(n) => Ok(MapKey::Integer(*n)),
+            Value::Float(n) if n.is_nan() => Err(String::from("NaN can't be used as a map key.")),
+            // Keys are the same exactly when the values are `==`
+            Value::Float(n) => Ok(match integral(*n) {
+                Some(n) => MapKey::Integer(n),
+                None => MapKey::Float(n.to_bits()),
+            }),
+            Value::String(s) => Ok(MapKey::String(s.clone())),
+            Value::Boolean(b) => Ok(MapKey::Boolean(*b)),
+            Value::Nil => Ok(MapKey::Nil),
+            other => Err(format!(
+                "A {} can't be used as a map key.",
+                other.type_name()
+            )),
+        }
+    }
+}
+
+// A map from values to values that remembers the order its keys were first inserted in, which
+// is the order it prints and iterates in. A key keeps the value it was first inserted with, so
+// after `m[1.0] = a; m[1] = b;` the one entry is still keyed by the float
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+    positions: HashMap<MapKey, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Map {
+            entries: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, key: Value, value: Value) -> Result<(), String> {
+        let hashed = MapKey::new(&key)?;
+        match self.positions.get(&hashed) {
+            Some(&position) => self.entries[position].1 = value,
+            None => {
+                self.positions.insert(hashed, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &Value) -> Result<Option<&Value>, String> {
+        let hashed = MapKey::new(key)?;
+        Ok(self
+            .positions
+            .get(&hashed)
+            .map(|&position| &self.entries[position].1))
+    }
+
+    pub fn contains_key(&self, key: &Value) -> Result<bool, String> {
+        Ok(self.positions.contains_key(&MapKey::new(key)?))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, (Value, Value)> {
+        self.entries.iter()
+    }
+}
+
+// Maps are equal when they hold the same keys with equal values, in any order
+impl PartialEq for Map {
+    fn eq(&self, other: &Map) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Ok(Some(value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn keys_follow_numeric_equality() {
+        let mut map = Map::new();
+        map.insert(Value::Float(1.0), Value::String("one".into()))
+            .unwrap();
+        map.insert(Value::Integer(1), Value::String("uno".into()))
+            .unwrap();
+        map.insert(Value::Float(-0.0), Value::Nil).unwrap();
+        map.insert(Value::Float(0.5), Value::Boolean(true)).unwrap();
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(
+            map.get(&Value::Float(1.0)),
+            Ok(Some(&Value::String("uno".into())))
+        );
+        assert_eq!(map.contains_key(&Value::Integer(0)), Ok(true));
+        assert_eq!(map.contains_key(&Value::Float(0.25)), Ok(false));
+        assert!(matches!(map.iter().next(), Some((Value::Float(_), _))));
+
+        assert_eq!(
+            map.insert(Value::Float(f64::NAN), Value::Nil),
+            Err(String::from("NaN can't be used as a map key."))
+        );
+        assert_eq!(
+            map.get(&Value::List(Rc::new(RefCell::new(Vec::new())))),
+            Err(String::from("A list can't be used as a map key."))
+        );
+    }
+
+    #[test]
+    fn large_numbers_are_the_same_key_exactly_when_they_are_equal() {
+        let float = Value::Float(9007199254740992.0);
+        let pairs = [
+            (Value::Integer(9007199254740992), true),
+            (Value::Integer(9007199254740993), false),
+            (Value::Integer(i64::MAX), false),
+            (Value::Integer(i64::MIN), false),
+        ];
+        for (integer, equal) in pairs.iter() {
+            assert_eq!(integer == &float, *equal, "{}", integer);
+            assert_eq!(
+                MapKey::new(integer) == MapKey::new(&float),
+                *equal,
+                "{}",
+                integer
+            );
+        }
+        assert_eq!(Value::Integer(i64::MIN), Value::Float(i64::MIN as f64));
+        assert_ne!(Value::Integer(i64::MAX), Value::Float(i64::MAX as f64));
+    }
+}
@@ -0,0 +1,503 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Conditional, Const, Continue, Enum, EnumVariant,
+    Export, ExprVisitor, Expression, ForIn, Function, Get, Grouping, If, Import, Index, IndexSet,
+    Lambda, List, Literal, LiteralValue, Map, Match, MatchArm, Parameter, Pattern, Postfix, Print,
+    Return, Set, Slice, Stmt, StmtVisitor, Super, This, Throw, Try, Unary, Var, Variable, While,
+    Yield,
+};
+use crate::lexer::Span;
+use crate::value::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+// Something suspicious about a program that still lets it run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Warning {
+    fn new(span: Span, message: &str) -> Self {
+        Warning {
+            span,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}, column {}] Warning: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+// Looks at every match in the program before it runs. An arm is unreachable when an earlier
+// arm without a guard matches everything, or already matches all of its literals or enum
+// variants. A match whose arms only test booleans has to cover both of them, and one whose arms
+// only test the variants of an enum has to cover all of those
+pub fn check_matches(statements: &[Stmt]) -> Vec<Warning> {
+    let mut checker = MatchChecker {
+        enums: HashMap::new(),
+        warnings: Vec::new(),
+    };
+    // Top-level enums are known everywhere, even in functions declared before them
+    for statement in statements {
+        match statement {
+            Stmt::Enum(enumeration) => checker.declare(enumeration),
+            Stmt::Export(export) => {
+                if let Stmt::Enum(enumeration) = &*export.declaration {
+                    checker.declare(enumeration);
+                }
+            }
+            _ => {}
+        }
+    }
+    checker.check_program(statements);
+    checker.warnings
+}
+
+struct MatchChecker {
+    // The variants of every enum declared so far
+    enums: HashMap<String, Vec<EnumVariant>>,
+    warnings: Vec<Warning>,
+}
+
+impl MatchChecker {
+    fn check_program(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    // The defaults of the parameters, then the body
+    fn check_function(&mut self, params: &[Parameter], body: &[Stmt]) {
+        for default in params.iter().filter_map(|param| param.default.as_ref()) {
+            default.accept(self);
+        }
+        self.check_program(body)
+    }
+
+    fn declare(&mut self, stmt: &Enum) {
+        self.enums.insert(stmt.name.clone(), stmt.variants.clone());
+    }
+
+    fn check_arms(&mut self, expr: &Match) {
+        let mut catch_all = false;
+        let mut covered: Vec<Value> = Vec::new();
+        let mut covered_variants: Vec<(&str, &str)> = Vec::new();
+
+        for arm in &expr.arms {
+            self.check_pattern(&arm.pattern);
+
+            let literals = literals(&arm.pattern);
+            let variants = variants(&arm.pattern);
+            let already_covered = literals
+                .as_ref()
+                .is_some_and(|literals| literals.iter().all(|l| covered.contains(l)))
+                || variants
+                    .as_ref()
+                    .is_some_and(|variants| variants.iter().all(|v| covered_variants.contains(v)));
+            if catch_all || already_covered {
+                self.warnings
+                    .push(Warning::new(arm.pattern.span(), "Unreachable match arm."));
+            }
+
+            // A guard can fail, so its arm doesn't cover anything
+            if arm.guard.is_some() {
+                continue;
+            }
+            catch_all |= arm.pattern.is_irrefutable();
+            covered.extend(literals.unwrap_or_default());
+            complete_variants(&arm.pattern, &mut covered_variants);
+        }
+
+        if catch_all {
+            return;
+        }
+        let missing = if expr.arms.iter().all(is_boolean_arm) {
+            [true, false]
+                .iter()
+                .filter(|missing| !covered.contains(&Value::Boolean(**missing)))
+                .map(bool::to_string)
+                .collect()
+        } else {
+            self.missing_variants(&expr.arms, &covered_variants)
+        };
+        for missing in missing {
+            let message = format!("Non-exhaustive match, `{}` is not covered.", missing);
+            self.warnings.push(Warning::new(expr.span, &message));
+        }
+    }
+
+    // The variants of a known enum that none of the arms covers, when every arm tests variants
+    // of that one enum
+    fn missing_variants(&self, arms: &[MatchArm], covered: &[(&str, &str)]) -> Vec<String> {
+        let mut enumeration = None;
+        for arm in arms {
+            let tested = match variants(&arm.pattern) {
+                Some(tested) => tested,
+                None => return Vec::new(),
+            };
+            for (name, _) in tested {
+                if enumeration.is_some_and(|other| other != name) {
+                    return Vec::new();
+                }
+                enumeration = Some(name);
+            }
+        }
+
+        let (name, variants) = match enumeration.and_then(|name| self.enums.get_key_value(name)) {
+            Some(known) => known,
+            None => return Vec::new(),
+        };
+        variants
+            .iter()
+            .filter(|variant| !covered.contains(&(name.as_str(), variant.name.as_str())))
+            .map(|variant| format!("{}.{}", name, variant.name))
+            .collect()
+    }
+
+    // A variant pattern that can never match, because its enum has no such variant or the
+    // variant has a different number of fields
+    fn check_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Or(alternatives) => alternatives.iter().for_each(|p| self.check_pattern(p)),
+            Pattern::List { elements, .. } => elements.iter().for_each(|p| self.check_pattern(p)),
+            Pattern::Instance { fields, .. } => {
+                fields.iter().for_each(|(_, p)| self.check_pattern(p))
+            }
+            Pattern::Variant {
+                enumeration,
+                variant,
+                fields,
+                span,
+            } => {
+                let message = match self.enums.get(enumeration) {
+                    Some(variants) => match variants.iter().find(|v| v.name == *variant) {
+                        Some(found) if found.fields.len() != fields.len() => Some(format!(
+                            "Expected {} fields for '{}.{}' but the pattern has {}.",
+                            found.fields.len(),
+                            enumeration,
+                            variant,
+                            fields.len()
+                        )),
+                        Some(_) => None,
+                        None => Some(format!(
+                            "Enum '{}' has no variant '{}'.",
+                            enumeration, variant
+                        )),
+                    },
+                    None => None,
+                };
+                if let Some(message) = message {
+                    self.warnings.push(Warning::new(*span, &message));
+                }
+                fields.iter().for_each(|p| self.check_pattern(p));
+            }
+            Pattern::Wildcard(_) | Pattern::Literal(..) | Pattern::Binding(..) => {}
+        }
+    }
+}
+
+// The values a pattern made of literals alone matches, None when it matches anything else
+fn literals(pattern: &Pattern) -> Option<Vec<Value>> {
+    match pattern {
+        Pattern::Literal(literal, _) => Some(vec![Value::from(literal)]),
+        Pattern::Or(alternatives) => {
+            let mut values = Vec::new();
+            for alternative in alternatives {
+                values.extend(literals(alternative)?);
+            }
+            Some(values)
+        }
+        _ => None,
+    }
+}
+
+// The enum variants a pattern made of variants alone tests, None when it matches anything else
+fn variants(pattern: &Pattern) -> Option<Vec<(&str, &str)>> {
+    match pattern {
+        Pattern::Variant {
+            enumeration,
+            variant,
+            ..
+        } => Some(vec![(enumeration.as_str(), variant.as_str())]),
+        Pattern::Or(alternatives) => {
+            let mut names = Vec::new();
+            for alternative in alternatives {
+                names.extend(variants(alternative)?);
+            }
+            Some(names)
+        }
+        _ => None,
+    }
+}
+
+// The variants a pattern matches whatever their fields hold
+fn complete_variants<'p>(pattern: &'p Pattern, covered: &mut Vec<(&'p str, &'p str)>) {
+    match pattern {
+        Pattern::Variant {
+            enumeration,
+            variant,
+            fields,
+            ..
+        } if fields.iter().all(Pattern::is_irrefutable) => {
+            covered.push((enumeration.as_str(), variant.as_str()))
+        }
+        Pattern::Or(alternatives) => {
+            for alternative in alternatives {
+                complete_variants(alternative, covered);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_boolean_arm(arm: &MatchArm) -> bool {
+    fn is_boolean(pattern: &Pattern) -> bool {
+        match pattern {
+            Pattern::Literal(LiteralValue::Boolean(_), _) => true,
+            Pattern::Or(alternatives) => alternatives.iter().all(is_boolean),
+            _ => false,
+        }
+    }
+    is_boolean(&arm.pattern)
+}
+
+impl ExprVisitor for MatchChecker {
+    type Output = ();
+
+    fn visit_assign_expr(&mut self, expr: &Assign) {
+        expr.value.accept(self)
+    }
+
+    fn visit_binary_expr(&mut self, expr: &Binary) {
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_conditional_expr(&mut self, expr: &Conditional) {
+        expr.condition.accept(self);
+        expr.then_branch.accept(self);
+        expr.else_branch.accept(self);
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) {
+        expr.callee.accept(self);
+        for argument in &expr.arguments {
+            argument.accept(self);
+        }
+        for argument in &expr.named {
+            argument.value.accept(self);
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) {
+        expr.object.accept(self)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) {
+        expr.expression.accept(self)
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+    }
+
+    fn visit_index_set_expr(&mut self, expr: &IndexSet) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+        expr.value.accept(self);
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &Lambda) {
+        self.check_function(&expr.params, &expr.body)
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) {
+        for element in &expr.elements {
+            element.accept(self);
+        }
+    }
+
+    fn visit_literal_expr(&mut self, _expr: &Literal) {}
+
+    fn visit_map_expr(&mut self, expr: &Map) {
+        for (key, value) in expr.keys.iter().zip(&expr.values) {
+            key.accept(self);
+            value.accept(self);
+        }
+    }
+
+    fn visit_match_expr(&mut self, expr: &Match) {
+        self.check_arms(expr);
+        expr.subject.accept(self);
+        for arm in &expr.arms {
+            if let Some(guard) = &arm.guard {
+                guard.accept(self);
+            }
+            arm.value.accept(self);
+        }
+    }
+
+    fn visit_postfix_expr(&mut self, _expr: &Postfix) {}
+
+    fn visit_set_expr(&mut self, expr: &Set) {
+        expr.object.accept(self);
+        expr.value.accept(self);
+    }
+
+    fn visit_slice_expr(&mut self, expr: &Slice) {
+        expr.object.accept(self);
+        for bound in expr.start.iter().chain(&expr.end) {
+            bound.accept(self);
+        }
+    }
+
+    fn visit_super_expr(&mut self, _expr: &Super) {}
+
+    fn visit_this_expr(&mut self, _expr: &This) {}
+
+    fn visit_unary_expr(&mut self, expr: &Unary) {
+        expr.right.accept(self)
+    }
+
+    fn visit_variable_expr(&mut self, _expr: &Variable) {}
+}
+
+impl StmtVisitor for MatchChecker {
+    type Output = ();
+
+    fn visit_block_stmt(&mut self, stmt: &Block) {
+        self.check_program(&stmt.statements)
+    }
+
+    fn visit_break_stmt(&mut self, _stmt: &Break) {}
+
+    fn visit_class_stmt(&mut self, stmt: &Class) {
+        for method in &stmt.methods {
+            self.visit_function_stmt(method);
+        }
+    }
+
+    fn visit_const_stmt(&mut self, stmt: &Const) {
+        stmt.initializer.accept(self)
+    }
+
+    fn visit_continue_stmt(&mut self, _stmt: &Continue) {}
+
+    fn visit_enum_stmt(&mut self, stmt: &Enum) {
+        self.declare(stmt)
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &Export) {
+        stmt.declaration.accept(self)
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression) {
+        stmt.expression.accept(self)
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) {
+        stmt.iterable.accept(self);
+        stmt.body.accept(self);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) {
+        self.check_function(&stmt.params, &stmt.body)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) {
+        stmt.condition.accept(self);
+        stmt.then_branch.accept(self);
+        if let Some(else_branch) = &stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_import_stmt(&mut self, _stmt: &Import) {}
+
+    fn visit_print_stmt(&mut self, stmt: &Print) {
+        stmt.expression.accept(self)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) {
+        if let Some(value) = &stmt.value {
+            value.accept(self);
+        }
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &Throw) {
+        stmt.value.accept(self)
+    }
+
+    fn visit_try_stmt(&mut self, stmt: &Try) {
+        self.check_program(&stmt.body);
+        if let Some(clause) = &stmt.catch_clause {
+            self.check_program(&clause.body);
+        }
+        self.check_program(&stmt.finally_body);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) {
+        if let Some(initializer) = &stmt.initializer {
+            initializer.accept(self);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) {
+        stmt.condition.accept(self);
+        stmt.body.accept(self);
+        if let Some(increment) = &stmt.increment {
+            increment.accept(self);
+        }
+    }
+
+    fn visit_yield_stmt(&mut self, stmt: &Yield) {
+        stmt.value.accept(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    #[test]
+    fn reports_unreachable_arms_and_missing_cases() {
+        let code = "var a = match x { n => 1, 2 => 2 };\n\
+                    var b = match x { 1 | 2 => 1, 2.0 => 2, 3 if x => 3, 3 => 4, _ => 5 };\n\
+                    fun f() { return match x > 1 { true => 1 }; }\n\
+                    var c = match s { S.A(_) => 1, S.A(1) => 2, S.C => 3, S.A(a, b) => 4 };\n\
+                    enum S { A(x), B }";
+        let mut scanner = Scanner::new(code.to_string());
+        let statements = Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .expect("program should parse");
+
+        let warnings: Vec<String> = check_matches(&statements)
+            .iter()
+            .map(Warning::to_string)
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "[line 1, column 27] Warning: Unreachable match arm.",
+                "[line 2, column 31] Warning: Unreachable match arm.",
+                "[line 3, column 18] Warning: Non-exhaustive match, `false` is not covered.",
+                "[line 4, column 32] Warning: Unreachable match arm.",
+                "[line 4, column 45] Warning: Enum 'S' has no variant 'C'.",
+                "[line 4, column 55] Warning: Expected 1 fields for 'S.A' but the pattern has 2.",
+                "[line 4, column 55] Warning: Unreachable match arm.",
+                "[line 4, column 9] Warning: Non-exhaustive match, `S.B` is not covered.",
+            ]
+        );
+    }
+}
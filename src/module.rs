@@ -0,0 +1,84 @@
+use crate::value::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// An imported file, only the names it exports can be reached through it
+#[derive(Debug)]
+pub struct Module {
+    pub name: String,
+    pub exports: HashMap<String, Value>,
+}
+
+impl Module {
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.exports.get(name).cloned()
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+// Remembers every module that finished loading, so a file imported twice runs once, and the
+// chain of files that are loading right now, which is how an import cycle shows up
+#[derive(Debug, Default)]
+pub struct ModuleLoader {
+    loaded: HashMap<PathBuf, Rc<Module>>,
+    loading: Vec<(PathBuf, String)>,
+}
+
+impl ModuleLoader {
+    // The script that was run counts as loading, imports start from its directory and a
+    // module importing it back is a cycle
+    pub fn for_script(path: &Path) -> Self {
+        let file = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        ModuleLoader {
+            loaded: HashMap::new(),
+            loading: vec![(file, name)],
+        }
+    }
+
+    // Paths are relative to the file doing the import, or to the working directory when no
+    // file is
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        match self.loading.last().and_then(|(file, _)| file.parent()) {
+            Some(directory) => directory.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    pub fn get(&self, file: &Path) -> Option<Rc<Module>> {
+        self.loaded.get(file).cloned()
+    }
+
+    // Fails with the cycle, like `a.tk -> b.tk -> a.tk`, when the file is already loading
+    pub fn enter(&mut self, file: &Path, name: &str) -> Result<(), String> {
+        if let Some(start) = self.loading.iter().position(|(other, _)| other == file) {
+            let mut cycle: Vec<&str> = self.loading[start..]
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect();
+            cycle.push(name);
+            return Err(cycle.join(" -> "));
+        }
+        self.loading.push((file.to_path_buf(), name.to_string()));
+        Ok(())
+    }
+
+    // A module that failed to load isn't kept, the next import tries again
+    pub fn leave(&mut self, module: Option<&Rc<Module>>) {
+        if let Some((file, _)) = self.loading.pop() {
+            if let Some(module) = module {
+                self.loaded.insert(file, Rc::clone(module));
+            }
+        }
+    }
+}
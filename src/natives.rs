@@ -0,0 +1,79 @@
+use crate::environment::Environment;
+use crate::function::NativeFunction;
+use crate::interpreter::RuntimeError;
+use crate::lexer::Span;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// The functions every program starts out with, defined in the global scope
+pub fn define_natives(environment: &mut Environment) {
+    let natives = [
+        NativeFunction {
+            name: "len",
+            arity: 1,
+            function: len,
+        },
+        NativeFunction {
+            name: "keys",
+            arity: 1,
+            function: keys,
+        },
+        NativeFunction {
+            name: "values",
+            arity: 1,
+            function: values,
+        },
+    ];
+
+    for native in natives {
+        environment.define(native.name, Value::NativeFunction(Rc::new(native)));
+    }
+}
+
+fn len(arguments: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    let len = match &arguments[0] {
+        Value::String(s) => s.chars().count(),
+        Value::List(list) => list.borrow().len(),
+        Value::Map(map) => map.borrow().len(),
+        Value::Range(start, end) => end.saturating_sub(*start).max(0) as usize,
+        other => {
+            return Err(RuntimeError::type_error(
+                span,
+                &format!("Can't take the length of a {}.", other.type_name()),
+            ))
+        }
+    };
+    Ok(Value::Integer(len as i64))
+}
+
+// The keys of a map in insertion order, as a new list
+fn keys(arguments: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::Map(map) => {
+            let keys = map.borrow().iter().map(|(key, _)| key.clone()).collect();
+            Ok(Value::List(Rc::new(RefCell::new(keys))))
+        }
+        _ => Err(RuntimeError::type_error(
+            span,
+            "Argument to 'keys' must be a map.",
+        )),
+    }
+}
+
+fn values(arguments: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::Map(map) => {
+            let values = map
+                .borrow()
+                .iter()
+                .map(|(_, value)| value.clone())
+                .collect();
+            Ok(Value::List(Rc::new(RefCell::new(values))))
+        }
+        _ => Err(RuntimeError::type_error(
+            span,
+            "Argument to 'values' must be a map.",
+        )),
+    }
+}
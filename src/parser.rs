@@ -1,124 +1,1690 @@
-use ast::{Binary, Expr, Unary};
-use lexer::{Token, TokenType};
-use std::any::Any;
+use ast::{
+    Assign, Binary, BinaryOperator, Call, Class, Conditional, Expr, Get, Index, IndexSet, Lambda,
+    List, Map, Match, MatchArm, NamedArgument, NodeId, Parameter, Pattern, Postfix,
+    PostfixOperator, Set, Slice, Store, Super, This, TypeAnnotation, Unary, UnaryOperator,
+    Variable,
+};
+use lexer::{Span, Token, TokenLiteral, TokenType};
 use std::fmt;
 
-use crate::ast::{Grouping, Literal, LiteralValue};
+use crate::ast::{
+    Block, Break, CatchClause, Const, Continue, Enum, EnumVariant, Export, Expression, ForIn,
+    Function, Grouping, If, Import, Literal, LiteralValue, Print, Return, Stmt, Throw, Try, Var,
+    While, Yield,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fixity {
+    Prefix,
+    Infix,
+    Postfix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
+    // A chain of the same level, like `1..2..3`, is a parse error
+    None,
+}
+
+pub struct Operator {
+    pub token_type: TokenType,
+    pub symbol: &'static str,
+    pub fixity: Fixity,
+    // Higher binds tighter
+    pub precedence: u8,
+    pub associativity: Associativity,
+}
+
+const fn prefix(token_type: TokenType, symbol: &'static str, precedence: u8) -> Operator {
+    Operator {
+        token_type,
+        symbol,
+        fixity: Fixity::Prefix,
+        precedence,
+        associativity: Associativity::Right,
+    }
+}
+
+const fn infix(
+    token_type: TokenType,
+    symbol: &'static str,
+    precedence: u8,
+    associativity: Associativity,
+) -> Operator {
+    Operator {
+        token_type,
+        symbol,
+        fixity: Fixity::Infix,
+        precedence,
+        associativity,
+    }
+}
+
+const fn postfix(token_type: TokenType, symbol: &'static str, precedence: u8) -> Operator {
+    Operator {
+        token_type,
+        symbol,
+        fixity: Fixity::Postfix,
+        precedence,
+        associativity: Associativity::Left,
+    }
+}
+
+use self::Associativity::{Left, Right};
+
+// Every operator the parser knows, from the loosest to the tightest binding. A new operator is
+// one more line here, plus a case in `prefix`/`infix`/`postfix` if it needs its own node
+pub static OPERATORS: &[Operator] = &[
+    infix(TokenType::Comma, ",", 1, Left),
+    infix(TokenType::Equal, "=", 2, Right),
+    infix(TokenType::PlusEqual, "+=", 2, Right),
+    infix(TokenType::MinusEqual, "-=", 2, Right),
+    infix(TokenType::StarEqual, "*=", 2, Right),
+    infix(TokenType::SlashEqual, "/=", 2, Right),
+    infix(TokenType::PercentEqual, "%=", 2, Right),
+    // Both branches of `a ? b : c` are parsed like a parenthesized expression
+    infix(TokenType::Question, "? :", 3, Right),
+    infix(TokenType::Pipe, "|", 4, Left),
+    infix(TokenType::Caret, "^", 5, Left),
+    infix(TokenType::Ampersand, "&", 6, Left),
+    infix(TokenType::EqualAndEqual, "==", 7, Left),
+    infix(TokenType::BangAndEqual, "!=", 7, Left),
+    infix(TokenType::Greater, ">", 8, Left),
+    infix(TokenType::GreaterOrEqual, ">=", 8, Left),
+    infix(TokenType::Less, "<", 8, Left),
+    infix(TokenType::LessOrEqual, "<=", 8, Left),
+    infix(TokenType::In, "in", 8, Left),
+    infix(TokenType::DotDot, "..", 9, Associativity::None),
+    infix(TokenType::DotDotEqual, "..=", 9, Associativity::None),
+    infix(TokenType::LessLess, "<<", 10, Left),
+    infix(TokenType::GreaterGreater, ">>", 10, Left),
+    infix(TokenType::Plus, "+", 11, Left),
+    infix(TokenType::Minus, "-", 11, Left),
+    infix(TokenType::Star, "*", 12, Left),
+    infix(TokenType::Slash, "/", 12, Left),
+    infix(TokenType::Percent, "%", 12, Left),
+    prefix(TokenType::Bang, "!", 13),
+    prefix(TokenType::Minus, "-", 13),
+    prefix(TokenType::Tilde, "~", 13),
+    prefix(TokenType::PlusPlus, "++", 13),
+    prefix(TokenType::MinusMinus, "--", 13),
+    // Tighter than the prefix operators, so `-2 ** 2` is `-(2 ** 2)`
+    infix(TokenType::StarStar, "**", 14, Right),
+    postfix(TokenType::PlusPlus, "++", 15),
+    postfix(TokenType::MinusMinus, "--", 15),
+    // A call is a postfix operator whose operand list ends at the closing parenthesis
+    postfix(TokenType::LeftParen, "( )", 16),
+    // So is indexing, `xs[i]` and `xs[a:b]` both end at the closing bracket
+    postfix(TokenType::LeftBracket, "[ ]", 16),
+    // Property access
+    postfix(TokenType::Dot, ".", 16),
+];
+
+impl Operator {
+    fn find(token_type: &TokenType, fixity: Fixity) -> Option<&'static Operator> {
+        OPERATORS
+            .iter()
+            .find(|operator| operator.fixity == fixity && &operator.token_type == token_type)
+    }
+
+    // Each level gets a pair of binding powers, the side that binds a bit tighter decides
+    // which way a chain of the same operator groups
+    fn binding_power(&self) -> (u8, u8) {
+        let base = self.precedence * 2;
+        match self.associativity {
+            Associativity::Left | Associativity::None => (base, base + 1),
+            Associativity::Right => (base + 1, base),
+        }
+    }
+}
+
+// The operator table as text, one line per precedence level, loosest first
+pub fn operator_table() -> String {
+    let mut rows: Vec<(u8, Fixity, Associativity, Vec<&str>)> = Vec::new();
+
+    for operator in OPERATORS {
+        match rows.last_mut() {
+            Some(row) if row.0 == operator.precedence && row.1 == operator.fixity => {
+                row.3.push(operator.symbol)
+            }
+            _ => rows.push((
+                operator.precedence,
+                operator.fixity,
+                operator.associativity,
+                vec![operator.symbol],
+            )),
+        }
+    }
+
+    let mut table = format!(
+        "{:<11} {:<8} {:<14} {}\n",
+        "PRECEDENCE", "FIXITY", "ASSOCIATIVITY", "OPERATORS"
+    );
+    for (precedence, fixity, associativity, symbols) in rows {
+        table.push_str(&format!(
+            "{:<11} {:<8} {:<14} {}\n",
+            precedence,
+            format!("{:?}", fixity).to_lowercase(),
+            format!("{:?}", associativity).to_lowercase(),
+            symbols.join(" ")
+        ));
+    }
+
+    table
+}
 
 pub struct Parser<'a> {
     tokens: &'a [Token],
     current: usize,
+    next_id: u32,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(tokens: &'a [Token]) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            next_id: 0,
+        }
     }
 
-    pub fn parse(&mut self) -> Option<Box<Expr<'a>>> {
-        match self.comma() {
-            Ok(expr) => Some(expr),
-            Err(_) => None,
+    // Parses a whole program, after an error it skips to the next statement so every error
+    // in the file gets reported
+    pub fn parse_program(&mut self) -> Option<Vec<Stmt>> {
+        let mut statements = Vec::new();
+        let mut had_error = false;
+
+        while !self.is_at_end() {
+            match self.top_level_declaration() {
+                Ok(statement) => statements.push(statement),
+                Err(_) => {
+                    had_error = true;
+                    self.synchronize();
+                }
+            }
+        }
+
+        if had_error {
+            None
+        } else {
+            Some(statements)
         }
     }
 
-    fn comparison(&mut self) -> Result<Box<Expr<'a>>, ParserError> {
-        let mut expression = self.term()?;
-        while self.match_tokens(&[
-            TokenType::Greater,
-            TokenType::GreaterOrEqual,
-            TokenType::Less,
-            TokenType::LessOrEqual,
-        ]) {
-            let operator: &'a Token = self.previous();
-            let right: Box<Expr<'a>> = self.term()?;
-            expression = Box::new(Expr::Binary(Binary {
-                left: expression,
-                operator,
-                right,
-            }));
+    // Imports and exports only make sense at the top of a module
+    fn top_level_declaration(&mut self) -> Result<Stmt, ParserError> {
+        if self.match_tokens(&[TokenType::Import, TokenType::From]) {
+            return self.import_declaration();
+        }
+        if self.match_tokens(&[TokenType::Export]) {
+            return self.export_declaration();
         }
 
-        Ok(expression)
+        self.declaration()
     }
 
-    fn expression(&mut self) -> Result<Box<Expr<'a>>, ParserError> {
-        self.ternary()
+    fn declaration(&mut self) -> Result<Stmt, ParserError> {
+        if self.check(&TokenType::Import)
+            || self.check(&TokenType::From)
+            || self.check(&TokenType::Export)
+        {
+            let token = self.peek();
+            return Err(self.create_error(
+                token,
+                "Imports and exports are only allowed at the top level.",
+            ));
+        }
+        if self.match_tokens(&[TokenType::Class]) {
+            return self.class_declaration();
+        }
+        if self.match_tokens(&[TokenType::Enum]) {
+            return self.enum_declaration();
+        }
+        // `fun` without a name starts a lambda, which is an expression statement
+        if self.check(&TokenType::Fun) && self.check_next(&TokenType::Identifier) {
+            self.advance();
+            return self.fun_declaration();
+        }
+        if self.match_tokens(&[TokenType::Var]) {
+            return self.var_declaration();
+        }
+        if self.match_tokens(&[TokenType::Const]) {
+            return self.const_declaration();
+        }
+
+        self.statement()
     }
 
-    fn equality(&mut self) -> Result<Box<Expr<'a>>, ParserError> {
-        let mut expression = self.comparison()?;
+    // `import "path" as name;` or `from "path" import a, b;`, the first keyword has already
+    // been consumed
+    fn import_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let keyword = self.previous().token_type.clone();
+        let path = self.consume(TokenType::String, "Expect module path.")?;
 
-        while self.match_tokens(&[TokenType::BangAndEqual, TokenType::EqualAndEqual]) {
-            let operator: &'a Token = self.previous();
-            let right: Box<Expr<'a>> = self.comparison()?;
-            expression = Box::new(Expr::Binary(Binary {
-                left: expression,
-                operator,
-                right,
-            }));
+        let (alias, names) = if keyword == TokenType::Import {
+            self.consume(TokenType::As, "Expect 'as' after module path.")?;
+            let alias = self.consume(TokenType::Identifier, "Expect module name after 'as'.")?;
+            (Some(alias.lexeme.clone()), Vec::new())
+        } else {
+            self.consume(TokenType::Import, "Expect 'import' after module path.")?;
+            let mut names = Vec::new();
+            loop {
+                let name = self.consume(TokenType::Identifier, "Expect name to import.")?;
+                names.push(name.lexeme.clone());
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            (None, names)
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after import.")?;
+
+        let path_name = match &path.literal {
+            Some(TokenLiteral::String(s)) => s.clone(),
+            _ => unreachable!("string tokens carry their value"),
+        };
+        Ok(Stmt::Import(Import {
+            id: self.node_id(),
+            path: path_name,
+            alias,
+            names,
+            span: path.span(),
+        }))
+    }
+
+    // Only declarations have a name to export
+    fn export_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        let declaration = if self.match_tokens(&[TokenType::Class]) {
+            self.class_declaration()?
+        } else if self.match_tokens(&[TokenType::Enum]) {
+            self.enum_declaration()?
+        } else if self.match_tokens(&[TokenType::Fun]) {
+            self.fun_declaration()?
+        } else if self.match_tokens(&[TokenType::Var]) {
+            self.var_declaration()?
+        } else if self.match_tokens(&[TokenType::Const]) {
+            self.const_declaration()?
+        } else {
+            let token = self.peek();
+            return Err(self.create_error(token, "Expect declaration after 'export'."));
+        };
+
+        Ok(Stmt::Export(Export {
+            id: self.node_id(),
+            declaration: Box::new(declaration),
+            span,
+        }))
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+        let annotation = self.optional_annotation(TokenType::Colon)?;
+
+        let initializer = if self.match_tokens(&[TokenType::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+
+        Ok(Stmt::Var(Var {
+            id: self.node_id(),
+            name: name.lexeme.clone(),
+            annotation,
+            initializer,
+            span: name.span(),
+        }))
+    }
+
+    fn const_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let name = self.consume(TokenType::Identifier, "Expect constant name.")?;
+        let annotation = self.optional_annotation(TokenType::Colon)?;
+        self.consume(TokenType::Equal, "Expect '=' after constant name.")?;
+        let initializer = self.expression()?;
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after constant declaration.",
+        )?;
+
+        Ok(Stmt::Const(Const {
+            id: self.node_id(),
+            name: name.lexeme.clone(),
+            annotation,
+            initializer,
+            span: name.span(),
+        }))
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+        let superclass = if self.match_tokens(&[TokenType::Less]) {
+            let superclass = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            Some(Variable {
+                id: self.node_id(),
+                name: superclass.lexeme.clone(),
+                span: superclass.span(),
+            })
+        } else {
+            None
+        };
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.method()?);
         }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
 
-        Ok(expression)
+        Ok(Stmt::Class(Class {
+            id: self.node_id(),
+            name: name.lexeme.clone(),
+            superclass,
+            methods,
+            span: name.span(),
+        }))
+    }
+
+    // The variants are separated by commas, a trailing one is allowed
+    fn enum_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let name = self.consume(TokenType::Identifier, "Expect enum name.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before enum body.")?;
+
+        let mut variants = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            variants.push(self.variant()?);
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after enum body.")?;
+
+        Ok(Stmt::Enum(Enum {
+            id: self.node_id(),
+            name: name.lexeme.clone(),
+            variants,
+            span: name.span(),
+        }))
+    }
+
+    fn variant(&mut self) -> Result<EnumVariant, ParserError> {
+        let name = self.consume(TokenType::Identifier, "Expect variant name.")?;
+        let mut fields = Vec::new();
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            while !self.check(&TokenType::RightParen) {
+                let field = self.consume(TokenType::Identifier, "Expect field name.")?;
+                fields.push(field.lexeme.clone());
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expect ')' after variant fields.")?;
+        }
+
+        Ok(EnumVariant {
+            name: name.lexeme.clone(),
+            fields,
+            span: name.span(),
+        })
+    }
+
+    fn fun_declaration(&mut self) -> Result<Stmt, ParserError> {
+        Ok(Stmt::Function(self.function("function")?))
+    }
+
+    // A method is written like a function declaration without the `fun`
+    fn method(&mut self) -> Result<Function, ParserError> {
+        self.function("method")
     }
 
-    fn term(&mut self) -> Result<Box<Expr<'a>>, ParserError> {
-        let mut expression = self.factor()?;
+    fn function(&mut self, kind: &str) -> Result<Function, ParserError> {
+        let name = self.consume(TokenType::Identifier, &format!("Expect {} name.", kind))?;
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+        let params = self.parameters()?;
+        let return_type = self.optional_annotation(TokenType::ThinArrow)?;
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.function_body(|parser| parser.block())?;
 
-        while self.match_tokens(&[TokenType::Minus, TokenType::Plus]) {
-            let operator: &'a Token = self.previous();
-            let right: Box<Expr<'a>> = self.factor()?;
-            expression = Box::new(Expr::Binary(Binary {
-                left: expression,
-                operator,
-                right,
+        Ok(Function {
+            id: self.node_id(),
+            name: name.lexeme.clone(),
+            params,
+            return_type,
+            body: body.into(),
+            span: name.span(),
+        })
+    }
+
+    fn statement(&mut self) -> Result<Stmt, ParserError> {
+        if self.match_tokens(&[TokenType::If]) {
+            return self.if_statement();
+        }
+        if self.check(&TokenType::While) || self.check(&TokenType::For) || self.starts_label() {
+            return self.loop_statement();
+        }
+        if self.match_tokens(&[TokenType::Break]) {
+            return self.break_statement();
+        }
+        if self.match_tokens(&[TokenType::Continue]) {
+            return self.continue_statement();
+        }
+        if self.match_tokens(&[TokenType::Print]) {
+            return self.print_statement();
+        }
+        if self.match_tokens(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+        if self.match_tokens(&[TokenType::Throw]) {
+            return self.throw_statement();
+        }
+        if self.match_tokens(&[TokenType::Yield]) {
+            return self.yield_statement();
+        }
+        if self.match_tokens(&[TokenType::Try]) {
+            return self.try_statement();
+        }
+        if self.check(&TokenType::LeftBrace) && !self.starts_map() {
+            let span = self.advance().span();
+            let statements = self.block()?;
+            return Ok(Stmt::Block(Block {
+                id: self.node_id(),
+                statements,
+                span,
             }));
         }
 
-        Ok(expression)
+        self.expression_statement()
     }
 
-    fn primary(&mut self) -> Result<Box<Expr<'a>>, ParserError> {
-        if self.match_tokens(&[TokenType::Number]) {
-            if let Some(int) = self.previous().get_literal::<i64>() {
-                return Ok(Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Integer(*int),
-                })));
-            } else if let Some(float) = self.previous().get_literal::<f64>() {
-                return Ok(Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Float(*float),
-                })));
-            } else {
-                return Err(self.create_error(self.previous(), "Expected number literal"));
+    fn if_statement(&mut self) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression_bp(0)?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.match_tokens(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If(If {
+            id: self.node_id(),
+            condition,
+            then_branch,
+            else_branch,
+            span,
+        }))
+    }
+
+    // A `while` or a `for`, either one can have a label in front
+    fn loop_statement(&mut self) -> Result<Stmt, ParserError> {
+        let label = if self.starts_label() {
+            let label = self.advance().lexeme.clone();
+            self.advance();
+            Some(label)
+        } else {
+            None
+        };
+
+        if self.match_tokens(&[TokenType::While]) {
+            self.while_statement(label)
+        } else if self.match_tokens(&[TokenType::For]) {
+            self.for_statement(label)
+        } else {
+            Err(self.create_error(self.peek(), "Expect loop after label."))
+        }
+    }
+
+    fn while_statement(&mut self, label: Option<String>) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression_bp(0)?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = Box::new(self.statement()?);
+
+        Ok(Stmt::While(While {
+            id: self.node_id(),
+            label,
+            condition,
+            body,
+            increment: None,
+            span,
+        }))
+    }
+
+    // `for (initializer; condition; increment) body` is a while loop in a block that holds the
+    // initializer, a missing condition is always true
+    fn for_statement(&mut self, label: Option<String>) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        if let Some(for_in) = self.for_in(&label, span)? {
+            return Ok(for_in);
+        }
+
+        let initializer = if self.match_tokens(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_tokens(&[TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(&TokenType::Semicolon) {
+            let id = self.node_id();
+            Box::new(Expr::Literal(Literal {
+                id,
+                value: LiteralValue::Boolean(true),
+                span,
+            }))
+        } else {
+            self.expression_bp(0)?
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(&TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression_bp(0)?)
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+        let body = Box::new(self.statement()?);
+
+        let while_loop = Stmt::While(While {
+            id: self.node_id(),
+            label,
+            condition,
+            body,
+            increment,
+            span,
+        });
+        Ok(match initializer {
+            Some(initializer) => Stmt::Block(Block {
+                id: self.node_id(),
+                statements: vec![initializer, while_loop],
+                span,
+            }),
+            None => while_loop,
+        })
+    }
+
+    // `for (a, b in items) body` once the parenthesis is open. `for (x in xs; ...)` starts
+    // the same way but is a classic loop whose initializer is `x in xs`, so when no
+    // parenthesis follows the iterable nothing is consumed
+    fn for_in(&mut self, label: &Option<String>, span: Span) -> Result<Option<Stmt>, ParserError> {
+        let start = self.current;
+        let mut names = Vec::new();
+        while self.match_tokens(&[TokenType::Identifier]) {
+            names.push(self.previous().lexeme.clone());
+            if names.len() == 2 || !self.match_tokens(&[TokenType::Comma]) {
+                break;
             }
-        } else if self.match_tokens(&[TokenType::String]) {
-            if let Some(s) = self.previous().get_literal::<String>() {
-                return Ok(Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::String(s.clone()),
+        }
+
+        if !names.is_empty() && self.match_tokens(&[TokenType::In]) {
+            let iterable = self.expression_bp(0)?;
+            if self.match_tokens(&[TokenType::RightParen]) {
+                let body = Box::new(self.statement()?);
+                return Ok(Some(Stmt::ForIn(ForIn {
+                    id: self.node_id(),
+                    label: label.clone(),
+                    names,
+                    iterable,
+                    body,
+                    span,
                 })));
+            }
+        }
+        self.current = start;
+        Ok(None)
+    }
+
+    fn break_statement(&mut self) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        let label = self.optional_label("break")?;
+        Ok(Stmt::Break(Break {
+            id: self.node_id(),
+            label,
+            span,
+        }))
+    }
+
+    fn continue_statement(&mut self) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        let label = self.optional_label("continue")?;
+        Ok(Stmt::Continue(Continue {
+            id: self.node_id(),
+            label,
+            span,
+        }))
+    }
+
+    // The label after `break` or `continue`, and the semicolon that ends the statement
+    fn optional_label(&mut self, keyword: &str) -> Result<Option<String>, ParserError> {
+        let label = if self.match_tokens(&[TokenType::Identifier]) {
+            Some(self.previous().lexeme.clone())
+        } else {
+            None
+        };
+        self.consume(
+            TokenType::Semicolon,
+            &format!("Expect ';' after '{}'.", keyword),
+        )?;
+        Ok(label)
+    }
+
+    // `name:` in front of a loop
+    fn starts_label(&self) -> bool {
+        self.check(&TokenType::Identifier) && self.check_next(&TokenType::Colon)
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        let expression = self.expression_bp(0)?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+
+        Ok(Stmt::Print(Print {
+            id: self.node_id(),
+            expression,
+            span,
+        }))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression_bp(0)?)
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+
+        Ok(Stmt::Return(Return {
+            id: self.node_id(),
+            value,
+            span,
+        }))
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        let value = self.expression_bp(0)?;
+        self.consume(TokenType::Semicolon, "Expect ';' after thrown value.")?;
+
+        Ok(Stmt::Throw(Throw {
+            id: self.node_id(),
+            value,
+            span,
+        }))
+    }
+
+    fn yield_statement(&mut self) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        let value = self.expression_bp(0)?;
+        self.consume(TokenType::Semicolon, "Expect ';' after yielded value.")?;
+
+        Ok(Stmt::Yield(Yield {
+            id: self.node_id(),
+            value,
+            span,
+        }))
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, ParserError> {
+        let span = self.previous().span();
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+
+        let catch_clause = if self.match_tokens(&[TokenType::Catch]) {
+            self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+            let name = self.consume(TokenType::Identifier, "Expect error name.")?;
+            self.consume(TokenType::RightParen, "Expect ')' after error name.")?;
+            self.consume(TokenType::LeftBrace, "Expect '{' before catch body.")?;
+            Some(CatchClause {
+                name: name.lexeme.clone(),
+                body: self.block()?,
+                span: name.span(),
+            })
+        } else {
+            None
+        };
+
+        let finally_body = if self.match_tokens(&[TokenType::Finally]) {
+            self.consume(TokenType::LeftBrace, "Expect '{' after 'finally'.")?;
+            self.block()?
+        } else if catch_clause.is_none() {
+            return Err(
+                self.create_error(self.peek(), "Expect 'catch' or 'finally' after try block.")
+            );
+        } else {
+            Vec::new()
+        };
+
+        Ok(Stmt::Try(Try {
+            id: self.node_id(),
+            body,
+            catch_clause,
+            finally_body,
+            span,
+        }))
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt, ParserError> {
+        let expression = self.expression_bp(0)?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+
+        Ok(Stmt::Expression(Expression {
+            id: self.node_id(),
+            span: expression.span(),
+            expression,
+        }))
+    }
+
+    // The statements up to the closing brace, the opening one has already been consumed
+    fn block(&mut self) -> Result<Vec<Stmt>, ParserError> {
+        let mut statements = Vec::new();
+
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+
+        Ok(statements)
+    }
+
+    fn synchronize(&mut self) {
+        self.advance();
+
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+
+            match self.peek().token_type {
+                TokenType::Class
+                | TokenType::Enum
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::Const
+                | TokenType::Import
+                | TokenType::From
+                | TokenType::Export
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Throw
+                | TokenType::Yield
+                | TokenType::Try => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    pub fn parse(&mut self) -> Option<Box<Expr>> {
+        let expression = self.expression_bp(0).ok()?;
+
+        if !self.is_at_end() {
+            self.create_error(self.peek(), "Expect end of expression.");
+            return None;
+        }
+
+        Some(expression)
+    }
+
+    // Everything but the comma operator
+    fn expression(&mut self) -> Result<Box<Expr>, ParserError> {
+        let min_bp = match Operator::find(&TokenType::Comma, Fixity::Infix) {
+            Some(comma) => comma.binding_power().1,
+            None => 0,
+        };
+        self.expression_bp(min_bp)
+    }
+
+    // Parses an operand and then every operator that binds at least as tight as `min_bp`
+    fn expression_bp(&mut self, min_bp: u8) -> Result<Box<Expr>, ParserError> {
+        let mut expression = self.prefix()?;
+        // The last operator applied here, if it doesn't chain
+        let mut unchained: Option<&Operator> = None;
+
+        loop {
+            let token_type = &self.peek().token_type;
+
+            if let Some(operator) = Operator::find(token_type, Fixity::Postfix) {
+                if operator.binding_power().0 < min_bp {
+                    break;
+                }
+                let operator: &'a Token = self.advance();
+                expression = self.postfix(expression, operator)?;
+            } else if let Some(operator) = Operator::find(token_type, Fixity::Infix) {
+                let (left_bp, right_bp) = operator.binding_power();
+                if left_bp < min_bp {
+                    break;
+                }
+                if let Some(previous) = unchained {
+                    if previous.precedence == operator.precedence {
+                        let message =
+                            format!("'{}' can't be chained, add parentheses.", operator.symbol);
+                        return Err(self.create_error(self.peek(), &message));
+                    }
+                }
+                if operator.associativity == Associativity::None {
+                    unchained = Some(operator);
+                }
+                let token: &'a Token = self.advance();
+                expression = self.infix(expression, token, right_bp)?;
             } else {
-                return Err(self.create_error(self.previous(), "Expected string literal"));
+                break;
+            }
+        }
+
+        Ok(expression)
+    }
+
+    fn prefix(&mut self) -> Result<Box<Expr>, ParserError> {
+        let right_bp = match Operator::find(&self.peek().token_type, Fixity::Prefix) {
+            Some(operator) => operator.binding_power().1,
+            None => return self.primary(),
+        };
+        let operator: &'a Token = self.advance();
+        let right: Box<Expr> = self.expression_bp(right_bp)?;
+
+        // `++x` is the same as `x += 1`, and `++xs[i]` as `xs[i] += 1`
+        if let TokenType::PlusPlus | TokenType::MinusMinus = operator.token_type {
+            let kind = increment_operator(operator);
+            let one = self.one(operator);
+            if let Some((name, name_span)) = as_variable(&right) {
+                let target = (name, name_span, right);
+                return Ok(self.compound_assignment(target, kind, operator.span(), one));
             }
-        } else if self.match_tokens(&[TokenType::True, TokenType::False]) {
-            if let Some(b) = self.previous().get_literal::<bool>() {
-                return Ok(Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Boolean(*b),
+            let store = Store::Compound(kind);
+            return self.store(*right, store, one, operator, "Invalid increment target.");
+        }
+
+        let kind = match UnaryOperator::from_token_type(&operator.token_type) {
+            Some(kind) => kind,
+            None => return Err(self.create_error(operator, "Expect unary operator.")),
+        };
+        let id = self.node_id();
+        Ok(Box::new(Expr::Unary(Unary {
+            id,
+            operator: kind,
+            span: operator.span(),
+            right,
+        })))
+    }
+
+    fn infix(
+        &mut self,
+        left: Box<Expr>,
+        operator: &'a Token,
+        right_bp: u8,
+    ) -> Result<Box<Expr>, ParserError> {
+        match operator.token_type {
+            TokenType::Equal
+            | TokenType::PlusEqual
+            | TokenType::MinusEqual
+            | TokenType::StarEqual
+            | TokenType::SlashEqual
+            | TokenType::PercentEqual => {
+                let value: Box<Expr> = self.expression_bp(right_bp)?;
+                if let Some((name, span)) = as_variable(&left) {
+                    return Ok(self.desugar_assignment((name, span, left), operator, value));
+                }
+
+                let store = Store::from_token_type(&operator.token_type)
+                    .expect("assignment operators store a value");
+                self.store(*left, store, value, operator, "Invalid assignment target.")
+            }
+            TokenType::Question => {
+                let then_branch = self.expression()?;
+                self.consume(TokenType::Colon, "Expect ':' after then branch.")?;
+                let else_branch = self.expression()?;
+                let id = self.node_id();
+                Ok(Box::new(Expr::Conditional(Conditional {
+                    id,
+                    condition: left,
+                    span: operator.span(),
+                    then_branch,
+                    else_branch,
+                })))
+            }
+            _ => {
+                let right: Box<Expr> = self.expression_bp(right_bp)?;
+                self.binary(left, operator, right)
+            }
+        }
+    }
+
+    fn postfix(&mut self, left: Box<Expr>, operator: &'a Token) -> Result<Box<Expr>, ParserError> {
+        match operator.token_type {
+            TokenType::LeftParen => return self.call(left, operator),
+            TokenType::LeftBracket => return self.index(left, operator),
+            TokenType::Dot => {
+                let name =
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                let id = self.node_id();
+                return Ok(Box::new(Expr::Get(Get {
+                    id,
+                    object: left,
+                    name: name.lexeme.clone(),
+                    span: name.span(),
                 })));
-            } else {
-                return Err(self.create_error(self.previous(), "Expected boolean literal"));
             }
-        } else if self.match_tokens(&[TokenType::Nil]) {
-            if self.previous().get_literal::<()>().is_some() {
-                return Ok(Box::new(Expr::Literal(Literal {
-                    value: LiteralValue::Nil,
+            _ => {}
+        }
+
+        let kind = match PostfixOperator::from_token_type(&operator.token_type) {
+            Some(kind) => kind,
+            None => return Err(self.create_error(operator, "Expect postfix operator.")),
+        };
+
+        if let Some((name, _)) = as_variable(&left) {
+            let id = self.node_id();
+            return Ok(Box::new(Expr::Postfix(Postfix {
+                id,
+                name,
+                operator: kind,
+                span: operator.span(),
+            })));
+        }
+        let store = Store::Postfix(increment_operator(operator));
+        let one = self.one(operator);
+        self.store(*left, store, one, operator, "Invalid increment target.")
+    }
+
+    // A list element or a field keeps the operator, so the list and the index are evaluated
+    // once. Anything else but a name can't be assigned to
+    fn store(
+        &mut self,
+        target: Expr,
+        store: Store,
+        value: Box<Expr>,
+        operator: &'a Token,
+        message: &str,
+    ) -> Result<Box<Expr>, ParserError> {
+        let id = self.node_id();
+        match target {
+            Expr::Index(index) => Ok(Box::new(Expr::IndexSet(IndexSet {
+                id,
+                object: index.object,
+                span: index.span,
+                index: index.index,
+                store,
+                value,
+            }))),
+            Expr::Get(get) => Ok(Box::new(Expr::Set(Set {
+                id,
+                object: get.object,
+                name: get.name,
+                span: get.span,
+                store,
+                value,
+            }))),
+            _ => Err(self.create_error(operator, message)),
+        }
+    }
+
+    // What `++` and `--` add to or take from their target
+    fn one(&mut self, operator: &'a Token) -> Box<Expr> {
+        let id = self.node_id();
+        Box::new(Expr::Literal(Literal {
+            id,
+            value: LiteralValue::Integer(1),
+            span: operator.span(),
+        }))
+    }
+
+    // The arguments up to the closing parenthesis, the opening one has already been consumed.
+    // A name followed by a colon starts a named argument, and only named ones may follow it.
+    // Passing one twice is left to the resolver
+    fn call(&mut self, callee: Box<Expr>, paren: &'a Token) -> Result<Box<Expr>, ParserError> {
+        let mut arguments = Vec::new();
+        let mut named: Vec<NamedArgument> = Vec::new();
+
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if self.check(&TokenType::Identifier) && self.check_next(&TokenType::Colon) {
+                    let name = self.advance();
+                    self.advance();
+                    named.push(NamedArgument {
+                        name: name.lexeme.clone(),
+                        value: *self.expression()?,
+                        span: name.span(),
+                    });
+                } else if !named.is_empty() {
+                    return Err(self.create_error(
+                        self.peek(),
+                        "Expect named argument after a named argument.",
+                    ));
+                } else {
+                    arguments.push(*self.expression()?);
+                }
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+
+        let id = self.node_id();
+        Ok(Box::new(Expr::Call(Call {
+            id,
+            callee,
+            span: paren.span(),
+            arguments,
+            named,
+        })))
+    }
+
+    // `xs[i]` or the slice `xs[a:b]`, the opening bracket has already been consumed
+    fn index(&mut self, object: Box<Expr>, bracket: &'a Token) -> Result<Box<Expr>, ParserError> {
+        let mut start = None;
+        if !self.match_tokens(&[TokenType::Colon]) {
+            let index = self.expression()?;
+            if !self.match_tokens(&[TokenType::Colon]) {
+                self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                let id = self.node_id();
+                return Ok(Box::new(Expr::Index(Index {
+                    id,
+                    object,
+                    span: bracket.span(),
+                    index,
                 })));
+            }
+            start = Some(index);
+        }
+
+        let end = if self.check(&TokenType::RightBracket) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightBracket, "Expect ']' after slice.")?;
+
+        let id = self.node_id();
+        Ok(Box::new(Expr::Slice(Slice {
+            id,
+            object,
+            span: bracket.span(),
+            start,
+            end,
+        })))
+    }
+
+    // `[a, b, c]`, a trailing comma is allowed. The opening bracket has already been consumed
+    fn list(&mut self) -> Result<Box<Expr>, ParserError> {
+        let bracket = self.previous();
+        let mut elements = Vec::new();
+
+        while !self.check(&TokenType::RightBracket) {
+            elements.push(*self.expression()?);
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+
+        let id = self.node_id();
+        Ok(Box::new(Expr::List(List {
+            id,
+            elements,
+            span: bracket.span(),
+        })))
+    }
+
+    // `{k: v, ...}`, a trailing comma is allowed. The opening brace has already been consumed
+    fn map(&mut self) -> Result<Box<Expr>, ParserError> {
+        let brace = self.previous();
+        let mut keys = Vec::new();
+        let mut values = Vec::new();
+
+        while !self.check(&TokenType::RightBrace) {
+            keys.push(*self.expression()?);
+            self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+            values.push(*self.expression()?);
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+
+        let id = self.node_id();
+        Ok(Box::new(Expr::Map(Map {
+            id,
+            keys,
+            values,
+            span: brace.span(),
+        })))
+    }
+
+    // `match subject { pattern if guard => value, ... }`, the `match` has already been consumed.
+    // The arms are separated by commas, a trailing one is allowed
+    fn match_expression(&mut self) -> Result<Box<Expr>, ParserError> {
+        let keyword = self.previous();
+        let subject = self.expression()?;
+        self.consume(TokenType::LeftBrace, "Expect '{' after match subject.")?;
+
+        let mut arms = vec![self.match_arm()?];
+        while self.match_tokens(&[TokenType::Comma]) && !self.check(&TokenType::RightBrace) {
+            arms.push(self.match_arm()?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after match arms.")?;
+
+        let id = self.node_id();
+        Ok(Box::new(Expr::Match(Match {
+            id,
+            subject,
+            arms,
+            span: keyword.span(),
+        })))
+    }
+
+    fn match_arm(&mut self) -> Result<MatchArm, ParserError> {
+        let pattern = self.pattern()?;
+        let guard = if self.match_tokens(&[TokenType::If]) {
+            Some(*self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::Arrow, "Expect '=>' after match pattern.")?;
+        let value = *self.expression()?;
+
+        Ok(MatchArm {
+            pattern,
+            guard,
+            value,
+        })
+    }
+
+    // Alternatives separated by `|`
+    fn pattern(&mut self) -> Result<Pattern, ParserError> {
+        let mut alternatives = vec![self.single_pattern()?];
+        while self.match_tokens(&[TokenType::Pipe]) {
+            alternatives.push(self.single_pattern()?);
+        }
+
+        if alternatives.len() == 1 {
+            Ok(alternatives.remove(0))
+        } else {
+            Ok(Pattern::Or(alternatives))
+        }
+    }
+
+    fn single_pattern(&mut self) -> Result<Pattern, ParserError> {
+        if self.match_tokens(&[TokenType::LeftBracket]) {
+            return self.list_pattern();
+        }
+        if self.check(&TokenType::Identifier) && self.check_next(&TokenType::LeftBrace) {
+            self.advance();
+            return self.instance_pattern();
+        }
+        if self.check(&TokenType::Identifier) && self.check_next(&TokenType::Dot) {
+            self.advance();
+            return self.variant_pattern();
+        }
+        if self.match_tokens(&[TokenType::Identifier]) {
+            return Ok(self.binding());
+        }
+
+        // A literal, numbers can be negative
+        let negative = self.match_tokens(&[TokenType::Minus]);
+        let token = self.advance();
+        let value = match (&token.literal, negative) {
+            (Some(TokenLiteral::Integer(n)), true) => LiteralValue::Integer(-n),
+            (Some(TokenLiteral::Float(n)), true) => LiteralValue::Float(-n),
+            (Some(TokenLiteral::Integer(n)), false) => LiteralValue::Integer(*n),
+            (Some(TokenLiteral::Float(n)), false) => LiteralValue::Float(*n),
+            (Some(TokenLiteral::String(s)), false) => LiteralValue::String(s.clone()),
+            (Some(TokenLiteral::Boolean(b)), false) => LiteralValue::Boolean(*b),
+            (Some(TokenLiteral::Nil), false) => LiteralValue::Nil,
+            _ => return Err(self.create_error(token, "Expect pattern.")),
+        };
+        Ok(Pattern::Literal(value, token.span()))
+    }
+
+    // `[a, b, ..rest]`, the opening bracket has already been consumed
+    fn list_pattern(&mut self) -> Result<Pattern, ParserError> {
+        let span = self.previous().span();
+        let mut elements = Vec::new();
+        let mut rest = None;
+
+        while !self.check(&TokenType::RightBracket) {
+            if self.match_tokens(&[TokenType::DotDot]) {
+                rest = Some(Box::new(if self.match_tokens(&[TokenType::Identifier]) {
+                    self.binding()
+                } else {
+                    Pattern::Wildcard(self.previous().span())
+                }));
+                break;
+            }
+            elements.push(self.pattern()?);
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after list pattern.")?;
+
+        Ok(Pattern::List {
+            elements,
+            rest,
+            span,
+        })
+    }
+
+    // `Point{x, y: 0}`, the class name has already been consumed
+    fn instance_pattern(&mut self) -> Result<Pattern, ParserError> {
+        let class = self.previous();
+        self.consume(TokenType::LeftBrace, "Expect '{' after class name.")?;
+        let mut fields = Vec::new();
+
+        while !self.check(&TokenType::RightBrace) {
+            let name = self.consume(TokenType::Identifier, "Expect field name.")?;
+            let pattern = if self.match_tokens(&[TokenType::Colon]) {
+                self.pattern()?
             } else {
-                return Err(self.create_error(self.previous(), "Expected nil"));
+                self.binding()
+            };
+            fields.push((name.lexeme.clone(), pattern));
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after field patterns.")?;
+
+        Ok(Pattern::Instance {
+            class: class.lexeme.clone(),
+            fields,
+            span: class.span(),
+        })
+    }
+
+    // `Shape.Circle(r)`, the enum name has already been consumed
+    fn variant_pattern(&mut self) -> Result<Pattern, ParserError> {
+        let enumeration = self.previous();
+        self.consume(TokenType::Dot, "Expect '.' after enum name.")?;
+        let variant = self.consume(TokenType::Identifier, "Expect variant name.")?;
+
+        let mut fields = Vec::new();
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            while !self.check(&TokenType::RightParen) {
+                fields.push(self.pattern()?);
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expect ')' after field patterns.")?;
+        }
+
+        Ok(Pattern::Variant {
+            enumeration: enumeration.lexeme.clone(),
+            variant: variant.lexeme.clone(),
+            fields,
+            span: enumeration.span(),
+        })
+    }
+
+    // The name that was just consumed, `_` binds nothing
+    fn binding(&self) -> Pattern {
+        let name = self.previous();
+        if name.lexeme == "_" {
+            Pattern::Wildcard(name.span())
+        } else {
+            Pattern::Binding(name.lexeme.clone(), name.span())
+        }
+    }
+
+    // `fun (a, b) { ... }`, the `fun` has already been consumed
+    fn lambda(&mut self) -> Result<Box<Expr>, ParserError> {
+        let keyword = self.previous();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'fun'.")?;
+        let params = self.parameters()?;
+        let return_type = self.optional_annotation(TokenType::ThinArrow)?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before function body.")?;
+        let body = self.function_body(|parser| parser.block())?;
+
+        let id = self.node_id();
+        Ok(Box::new(Expr::Lambda(Lambda {
+            id,
+            params,
+            return_type,
+            body: body.into(),
+            span: keyword.span(),
+        })))
+    }
+
+    // `(a, b) => a + b` or `(a, b) => { ... }`, the opening parenthesis has already been
+    // consumed. An expression body takes everything up to the next comma, so the lambda
+    // binds looser than any operator after it
+    fn arrow_lambda(&mut self) -> Result<Box<Expr>, ParserError> {
+        let paren = self.previous();
+        let params = self.parameters()?;
+        let return_type = self.optional_annotation(TokenType::ThinArrow)?;
+        self.consume(TokenType::Arrow, "Expect '=>' after lambda parameters.")?;
+
+        let body = if self.check(&TokenType::LeftBrace) && !self.starts_map() {
+            self.advance();
+            self.function_body(|parser| parser.block())?
+        } else {
+            // `=> value` is short for `{ return value; }`
+            self.function_body(|parser| {
+                let value = parser.expression()?;
+                Ok(vec![Stmt::Return(Return {
+                    id: parser.node_id(),
+                    span: value.span(),
+                    value: Some(value),
+                })])
+            })?
+        };
+
+        let id = self.node_id();
+        Ok(Box::new(Expr::Lambda(Lambda {
+            id,
+            params,
+            return_type,
+            body: body.into(),
+            span: paren.span(),
+        })))
+    }
+
+    // The parameters up to the closing parenthesis, the opening one has already been consumed.
+    // Where defaults and a rest parameter may go is up to the resolver
+    fn parameters(&mut self) -> Result<Vec<Parameter>, ParserError> {
+        let mut params = Vec::new();
+
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                params.push(self.parameter()?);
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        Ok(params)
+    }
+
+    // `...rest` or `name: Type = default`, the default is an expression of the boxed tree like
+    // the body it goes with
+    fn parameter(&mut self) -> Result<Parameter, ParserError> {
+        let rest = self.match_tokens(&[TokenType::DotDotDot]);
+        let name = self.consume(TokenType::Identifier, "Expect parameter name.")?;
+        let annotation = self.optional_annotation(TokenType::Colon)?;
+
+        let default = if self.match_tokens(&[TokenType::Equal]) {
+            Some(*self.function_body(|parser| parser.expression())?)
+        } else {
+            None
+        };
+
+        Ok(Parameter {
+            name: name.lexeme.clone(),
+            annotation,
+            default,
+            rest,
+            span: name.span(),
+        })
+    }
+
+    // `: Int` after a name or `-> Int` after the parameters, depending on what introduces it
+    fn optional_annotation(
+        &mut self,
+        introducer: TokenType,
+    ) -> Result<Option<TypeAnnotation>, ParserError> {
+        if !self.match_tokens(&[introducer]) {
+            return Ok(None);
+        }
+        self.type_annotation().map(Some)
+    }
+
+    fn type_annotation(&mut self) -> Result<TypeAnnotation, ParserError> {
+        let name = self.consume(TokenType::Identifier, "Expect type name.")?;
+        Ok(TypeAnnotation {
+            name: name.lexeme.clone(),
+            span: name.span(),
+        })
+    }
+
+    // Function bodies are statements, which only a boxed parser builds, so one takes over at
+    // the current token and hands the position back when it is done
+    fn function_body<T, F>(&mut self, parse: F) -> Result<T, ParserError>
+    where
+        F: FnOnce(&mut Parser<'a>) -> Result<T, ParserError>,
+    {
+        let mut parser = Parser::new(self.tokens);
+        parser.current = self.current;
+        parser.next_id = self.next_id;
+
+        let body = parse(&mut parser);
+        self.current = parser.current;
+        self.next_id = parser.next_id;
+        body
+    }
+
+    // Whether the parenthesis at the current token opens the parameters of an arrow lambda,
+    // which takes looking past the names, their types and their defaults for the `=>` or the
+    // `->` of a return type
+    fn is_arrow_lambda(&self) -> bool {
+        let mut index = self.current + 1;
+
+        while let Some(token) = self.tokens.get(index) {
+            match token.token_type {
+                TokenType::Identifier
+                | TokenType::Comma
+                | TokenType::Colon
+                | TokenType::DotDotDot => index += 1,
+                // A default runs up to the comma or parenthesis outside of any brackets it has
+                TokenType::Equal => {
+                    let mut depth = 0;
+                    index += 1;
+                    while let Some(token) = self.tokens.get(index) {
+                        match token.token_type {
+                            TokenType::LeftParen
+                            | TokenType::LeftBracket
+                            | TokenType::LeftBrace => depth += 1,
+                            TokenType::RightParen
+                            | TokenType::RightBracket
+                            | TokenType::RightBrace
+                                if depth > 0 =>
+                            {
+                                depth -= 1
+                            }
+                            TokenType::Comma | TokenType::RightParen if depth == 0 => break,
+                            TokenType::EOF => return false,
+                            _ => {}
+                        }
+                        index += 1;
+                    }
+                }
+                TokenType::RightParen => {
+                    return matches!(
+                        self.tokens.get(index + 1),
+                        Some(token) if token.token_type == TokenType::Arrow
+                            || token.token_type == TokenType::ThinArrow
+                    )
+                }
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    // Where a statement or an arrow body may start, `{` opens a block unless the token after it
+    // is followed by a colon, as in `{"a": 1}`. So a map there needs a single token as its first
+    // key and an empty `{}` is a block, anywhere else a `{` is always a map. A colon followed by
+    // a loop is a label, `{ outer: while ...` is a block
+    fn starts_map(&self) -> bool {
+        let token_type = |offset: usize| {
+            self.tokens
+                .get(self.current + offset)
+                .map(|t| &t.token_type)
+        };
+        token_type(2) == Some(&TokenType::Colon)
+            && !matches!(token_type(3), Some(TokenType::While) | Some(TokenType::For))
+    }
+
+    // Turns `x op= value` into `x = x op value`, plain `=` is kept as it is
+    fn desugar_assignment(
+        &mut self,
+        target: (String, Span, Box<Expr>),
+        equals: &'a Token,
+        value: Box<Expr>,
+    ) -> Box<Expr> {
+        let operator = match Store::from_token_type(&equals.token_type) {
+            Some(Store::Compound(operator)) => operator,
+            _ => {
+                let (name, name_span, _) = target;
+                let id = self.node_id();
+                return Box::new(Expr::Assign(Assign {
+                    id,
+                    name,
+                    span: name_span,
+                    value,
+                }));
             }
+        };
+
+        self.compound_assignment(target, operator, equals.span(), value)
+    }
+
+    // The target variable node is reused as the left operand of the new binary
+    fn compound_assignment(
+        &mut self,
+        target: (String, Span, Box<Expr>),
+        operator: BinaryOperator,
+        operator_span: Span,
+        value: Box<Expr>,
+    ) -> Box<Expr> {
+        let (name, name_span, variable) = target;
+        let id = self.node_id();
+        let value = Box::new(Expr::Binary(Binary {
+            id,
+            left: variable,
+            operator,
+            span: operator_span,
+            right: value,
+        }));
+
+        let id = self.node_id();
+        Box::new(Expr::Assign(Assign {
+            id,
+            name,
+            span: name_span,
+            value,
+        }))
+    }
+
+    fn binary(
+        &mut self,
+        left: Box<Expr>,
+        operator: &'a Token,
+        right: Box<Expr>,
+    ) -> Result<Box<Expr>, ParserError> {
+        let kind = match BinaryOperator::from_token_type(&operator.token_type) {
+            Some(kind) => kind,
+            None => return Err(self.create_error(operator, "Expect binary operator.")),
+        };
+
+        let id = self.node_id();
+        Ok(Box::new(Expr::Binary(Binary {
+            id,
+            left,
+            operator: kind,
+            span: operator.span(),
+            right,
+        })))
+    }
+
+    fn node_id(&mut self) -> NodeId {
+        self.next_id += 1;
+        NodeId(self.next_id - 1)
+    }
+
+    fn primary(&mut self) -> Result<Box<Expr>, ParserError> {
+        if self.match_tokens(&[
+            TokenType::Number,
+            TokenType::String,
+            TokenType::True,
+            TokenType::False,
+            TokenType::Nil,
+        ]) {
+            let token = self.previous();
+            let value = match &token.literal {
+                Some(TokenLiteral::Integer(int)) => LiteralValue::Integer(*int),
+                Some(TokenLiteral::Float(float)) => LiteralValue::Float(*float),
+                Some(TokenLiteral::String(s)) => LiteralValue::String(s.clone()),
+                Some(TokenLiteral::Boolean(b)) => LiteralValue::Boolean(*b),
+                Some(TokenLiteral::Nil) => LiteralValue::Nil,
+                None => return Err(self.create_error(token, "Expected literal value")),
+            };
+            let id = self.node_id();
+            Ok(Box::new(Expr::Literal(Literal {
+                id,
+                value,
+                span: token.span(),
+            })))
+        } else if self.match_tokens(&[TokenType::Identifier]) {
+            let name = self.previous();
+            let id = self.node_id();
+            Ok(Box::new(Expr::Variable(Variable {
+                id,
+                name: name.lexeme.clone(),
+                span: name.span(),
+            })))
+        } else if self.match_tokens(&[TokenType::Super]) {
+            let span = self.previous().span();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+            let id = self.node_id();
+            Ok(Box::new(Expr::Super(Super {
+                id,
+                method: method.lexeme.clone(),
+                span,
+            })))
+        } else if self.match_tokens(&[TokenType::This]) {
+            let id = self.node_id();
+            Ok(Box::new(Expr::This(This {
+                id,
+                span: self.previous().span(),
+            })))
+        } else if self.match_tokens(&[TokenType::Match]) {
+            self.match_expression()
+        } else if self.check(&TokenType::LeftParen) && self.is_arrow_lambda() {
+            self.advance();
+            self.arrow_lambda()
+        } else if self.match_tokens(&[TokenType::Fun]) {
+            self.lambda()
+        } else if self.match_tokens(&[TokenType::LeftBracket]) {
+            self.list()
+        } else if self.match_tokens(&[TokenType::LeftBrace]) {
+            self.map()
         } else if self.match_tokens(&[TokenType::LeftParen]) {
+            let span = self.previous().span();
             let expression = self.expression()?;
             self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
-            return Ok(Box::new(Expr::Grouping(Grouping { expression })));
+            let id = self.node_id();
+            Ok(Box::new(Expr::Grouping(Grouping {
+                id,
+                expression,
+                span,
+            })))
         } else {
             Err(self.create_error(self.peek(), "Expect expression"))
         }
@@ -148,7 +1714,7 @@ impl<'a> Parser<'a> {
         &mut self,
         token_type: TokenType,
         error_message: &str,
-    ) -> Result<&Token, ParserError> {
+    ) -> Result<&'a Token, ParserError> {
         if self.check(&token_type) {
             return Ok(self.advance());
         }
@@ -160,64 +1726,6 @@ impl<'a> Parser<'a> {
         eprintln!("[line {}] Error{}: {}", line, location, message);
     }
 
-    fn comma(&mut self) -> Result<Box<Expr<'a>>, ParserError> {
-        let mut expression = self.expression()?;
-
-        while self.match_tokens(&[TokenType::Comma]) {
-            let operator: &'a Token = self.previous();
-            let right: Box<Expr<'a>> = self.expression()?;
-            expression = Box::new(Expr::Binary(Binary {
-                left: expression,
-                operator,
-                right,
-            }));
-        }
-
-        Ok(expression)
-    }
-
-    fn ternary(&mut self) -> Result<Box<Expr<'a>>, ParserError> {
-        let mut expression = self.equality()?;
-
-        while self.match_tokens(&[TokenType::Question, TokenType::Colon]) {
-            let operator: &'a Token = self.previous();
-            let right: Box<Expr<'a>> = self.expression()?;
-            expression = Box::new(Expr::Binary(Binary {
-                left: expression,
-                operator,
-                right,
-            }));
-        }
-
-        Ok(expression)
-    }
-
-    fn unary(&mut self) -> Result<Box<Expr<'a>>, ParserError> {
-        if self.match_tokens(&[TokenType::Bang, TokenType::Minus]) {
-            let operator: &'a Token = self.previous();
-            let right: Box<Expr<'a>> = self.unary()?;
-            return Ok(Box::new(Expr::Unary(Unary { operator, right })));
-        }
-
-        self.primary()
-    }
-
-    fn factor(&mut self) -> Result<Box<Expr<'a>>, ParserError> {
-        let mut expression = self.unary()?;
-
-        while self.match_tokens(&[TokenType::Slash, TokenType::Star]) {
-            let operator: &'a Token = self.previous();
-            let right: Box<Expr<'a>> = self.unary()?;
-            expression = Box::new(Expr::Binary(Binary {
-                left: expression,
-                operator,
-                right,
-            }));
-        }
-
-        Ok(expression)
-    }
-
     fn match_tokens(&mut self, types: &[TokenType]) -> bool {
         for token_type in types {
             if self.check(token_type) {
@@ -242,8 +1750,15 @@ impl<'a> Parser<'a> {
         self.previous()
     }
 
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => &token.token_type == token_type,
+            None => false,
+        }
+    }
+
     fn is_at_end(&self) -> bool {
-        &self.peek().token_type == &TokenType::EOF
+        self.peek().token_type == TokenType::EOF
     }
 
     fn peek(&self) -> &'a Token {
@@ -255,13 +1770,83 @@ impl<'a> Parser<'a> {
     }
 }
 
+// `++` adds one and `--` takes one away
+fn increment_operator(operator: &Token) -> BinaryOperator {
+    match operator.token_type {
+        TokenType::PlusPlus => BinaryOperator::Add,
+        _ => BinaryOperator::Subtract,
+    }
+}
+
+// The name and span of a variable node, for the operators that need an assignment target
+fn as_variable(expr: &Expr) -> Option<(String, Span)> {
+    match expr {
+        Expr::Variable(variable) => Some((variable.name.clone(), variable.span)),
+        _ => None,
+    }
+}
+
 #[derive(Debug)]
 struct ParserError();
 
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "ParserError: {}", "Error happened")
+        write!(f, "ParserError: Error happened")
     }
 }
 
 impl std::error::Error for ParserError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast_printer::AstPrinter;
+    use lexer::Scanner;
+
+    fn printed(code: &str) -> String {
+        let mut scanner = Scanner::new(code.to_string());
+        let expression = Parser::new(scanner.scan_tokens())
+            .parse()
+            .expect("expression should parse");
+        AstPrinter.print(&expression)
+    }
+
+    #[test]
+    fn operators_group_by_precedence_and_associativity() {
+        let cases = [
+            ("1 - 2 - 3 * 4 % 5", "(- (- 1 2) (% (* 3 4) 5))"),
+            // `**` binds tighter than a leading minus and groups to the right
+            ("-2 ** 3 ** 2", "(- (** 2 (** 3 2)))"),
+            ("a = b += 1", "(= a (= b (+ b 1)))"),
+            ("x %= 2 ** n", "(= x (% x (** 2 n)))"),
+            ("-i++ ** 2", "(- (** (post++ i) 2))"),
+            ("--i", "(= i (- i 1))"),
+            ("1 | 2 ^ 3 & 4 == 5", "(| 1 (^ 2 (& 3 (== 4 5))))"),
+            ("a << 1 + 2 < b >> 3", "(< (<< a (+ 1 2)) (>> b 3))"),
+            ("~x & ~-y", "(& (~ x) (~ (- y)))"),
+            ("c ? 1 | 2 : d ? 3 : 4", "(? c (| 1 2) (? d 3 4))"),
+            (
+                "xs[0].f-- + g(1)[2]",
+                "(+ (post-- (. f (index xs 0))) (index (call g 1) 2))",
+            ),
+        ];
+        for (code, expected) in cases.iter() {
+            assert_eq!(printed(code), *expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn ranges_dont_chain() {
+        assert_eq!(printed("a < 1..2 + 3"), "(< a (.. 1 (+ 2 3)))");
+        assert_eq!(printed("(1..2)..=3"), "(..= (group (.. 1 2)) 3)");
+
+        for code in ["1..2..3", "1..=2..3", "a..b..=c"].iter() {
+            let mut scanner = Scanner::new(code.to_string());
+            assert!(
+                Parser::new(scanner.scan_tokens()).parse().is_none(),
+                "{}",
+                code
+            );
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::ast::Stmt;
+use crate::const_fold::fold_constants;
+use crate::match_check::check_matches;
+use crate::resolver::resolve;
+use crate::type_check::check_types;
+
+// What the static passes make of a parsed program, the entry script and every module it
+// imports go through them the same way before running
+pub struct Checked {
+    // Matches that can't be reached or miss a case, the program still runs
+    pub warnings: Vec<String>,
+    // The folded program, or the errors that keep it from running
+    pub statements: Result<Vec<Stmt>, Vec<String>>,
+}
+
+// A `break` with no loop to leave or a constant assigned to stops the program before the
+// types are checked, and annotated code that can't work stops it before it is folded
+pub fn check_program(statements: Vec<Stmt>) -> Checked {
+    let warnings = check_matches(&statements)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    let mut errors: Vec<String> = resolve(&statements)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    if errors.is_empty() {
+        errors = check_types(&statements)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+    }
+
+    let statements = if errors.is_empty() {
+        Ok(fold_constants(statements))
+    } else {
+        Err(errors)
+    };
+    Checked {
+        warnings,
+        statements,
+    }
+}
@@ -0,0 +1,13 @@
+use crate::ast::Stmt;
+use crate::lexer::Scanner;
+use crate::parser::Parser;
+
+// The classes every program starts out with, written in the language itself
+const SOURCE: &str = include_str!("prelude.tk");
+
+pub fn statements() -> Vec<Stmt> {
+    let mut scanner = Scanner::new(SOURCE.to_string());
+    Parser::new(scanner.scan_tokens())
+        .parse_program()
+        .expect("the prelude should parse")
+}
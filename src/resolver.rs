@@ -0,0 +1,526 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Conditional, Const, Continue, Enum, Export,
+    ExprVisitor, Expression, ForIn, Function, Get, Grouping, If, Import, Index, IndexSet, Lambda,
+    List, Literal, Map, Match, Parameter, Postfix, Print, Return, Set, Slice, Stmt, StmtVisitor,
+    Super, This, Throw, Try, Unary, Var, Variable, While, Yield,
+};
+use crate::lexer::Span;
+use std::collections::HashMap;
+use std::fmt;
+
+// A statement that can't mean anything where it is, reported before the program runs. The
+// note points at a second place the error is about, like the declaration of a constant
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolveError {
+    pub span: Span,
+    pub message: String,
+    pub note: Option<(Span, String)>,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}, column {}] Error: {}",
+            self.span.line, self.span.column, self.message
+        )?;
+        if let Some((span, note)) = &self.note {
+            write!(
+                f,
+                "\n[line {}, column {}] Note: {}",
+                span.line, span.column, note
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Checks that every `break` and `continue` is inside a loop of the same function, that a
+// label they name belongs to one of the loops around them, that `yield` is only used in
+// functions that can become generators, and that constants are never assigned to
+pub fn resolve(statements: &[Stmt]) -> Vec<ResolveError> {
+    let mut resolver = Resolver {
+        loops: Vec::new(),
+        function: FunctionKind::None,
+        scopes: vec![HashMap::new()],
+        errors: Vec::new(),
+    };
+    resolver.resolve_program(statements);
+    resolver.errors
+}
+
+struct Resolver {
+    // The labels of the loops around the current statement, innermost last
+    loops: Vec<Option<String>>,
+    function: FunctionKind,
+    // The names declared in each scope around the current statement, innermost last. A
+    // constant keeps the span of its declaration
+    scopes: Vec<HashMap<String, Option<Span>>>,
+    errors: Vec<ResolveError>,
+}
+
+// What kind of function the current statement is in, an initializer has to hand back its
+// instance so it can't be a generator
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    None,
+    Function,
+    Initializer,
+}
+
+impl Resolver {
+    fn resolve_program(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(ResolveError {
+            span,
+            message,
+            note: None,
+        });
+    }
+
+    // An error about a constant, with a note pointing at its declaration
+    fn constant_error(&mut self, span: Span, message: String, name: &str, declared: Span) {
+        self.errors.push(ResolveError {
+            span,
+            message,
+            note: Some((declared, format!("'{}' is declared here.", name))),
+        });
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    // A constant can be shadowed in an inner scope but not declared again in its own
+    fn declare(&mut self, name: &str, span: Span, constant: bool) {
+        let scope = self
+            .scopes
+            .last_mut()
+            .expect("there is always a global scope");
+        let previous = scope.insert(name.to_string(), constant.then_some(span));
+        if let Some(Some(declared)) = previous {
+            let message = format!("'{}' is already declared as a constant.", name);
+            self.constant_error(span, message, name, declared);
+        }
+    }
+
+    fn check_assignment(&mut self, name: &str, span: Span) {
+        let declared = self.scopes.iter().rev().find_map(|scope| scope.get(name));
+        if let Some(Some(declared)) = declared.copied() {
+            let message = format!("Can't assign to constant '{}'.", name);
+            self.constant_error(span, message, name, declared);
+        }
+    }
+
+    // A function body can't break out of the loops its declaration is in. A default sees the
+    // parameters before its own
+    fn resolve_function(&mut self, params: &[Parameter], body: &[Stmt], kind: FunctionKind) {
+        self.check_parameters(params);
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        let enclosing = std::mem::replace(&mut self.function, kind);
+        self.begin_scope();
+        for param in params {
+            if let Some(default) = &param.default {
+                default.accept(self);
+            }
+            self.declare(&param.name, param.span, false);
+        }
+        self.resolve_program(body);
+        self.end_scope();
+        self.loops = enclosing_loops;
+        self.function = enclosing;
+    }
+
+    // Calls fill the parameters in order, so the ones that can be left out come last
+    fn check_parameters(&mut self, params: &[Parameter]) {
+        let mut defaulted = false;
+        for (i, param) in params.iter().enumerate() {
+            if params[..i].iter().any(|other| other.name == param.name) {
+                let message = format!("Duplicate parameter '{}'.", param.name);
+                self.error(param.span, message);
+            }
+            let message = if param.rest && i + 1 < params.len() {
+                "A rest parameter must be the last one."
+            } else if param.rest && param.default.is_some() {
+                "A rest parameter can't have a default value."
+            } else if !param.rest && defaulted && param.default.is_none() {
+                "A parameter without a default can't follow one with a default."
+            } else {
+                defaulted |= param.default.is_some();
+                continue;
+            };
+            self.error(param.span, message.to_string());
+        }
+    }
+
+    fn resolve_block(&mut self, statements: &[Stmt]) {
+        self.begin_scope();
+        self.resolve_program(statements);
+        self.end_scope();
+    }
+
+    // A label only reaches the loops inside the one it names, so reusing it there is an error
+    fn resolve_loop(&mut self, label: &Option<String>, span: Span, body: &Stmt) {
+        if let Some(name) = label {
+            if self.loops.contains(label) {
+                let message = format!("Label '{}' is already used by an enclosing loop.", name);
+                self.error(span, message);
+            }
+        }
+        self.loops.push(label.clone());
+        body.accept(self);
+        self.loops.pop();
+    }
+
+    fn resolve_jump(&mut self, keyword: &str, label: &Option<String>, span: Span) {
+        let message = match label {
+            None if self.loops.is_empty() => {
+                format!("Can't use '{}' outside of a loop.", keyword)
+            }
+            Some(label) if !self.loops.contains(&Some(label.clone())) => {
+                format!("No enclosing loop is labelled '{}'.", label)
+            }
+            _ => return,
+        };
+        self.error(span, message);
+    }
+}
+
+impl ExprVisitor for Resolver {
+    type Output = ();
+
+    fn visit_assign_expr(&mut self, expr: &Assign) {
+        expr.value.accept(self);
+        self.check_assignment(&expr.name, expr.span);
+    }
+
+    fn visit_binary_expr(&mut self, expr: &Binary) {
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_conditional_expr(&mut self, expr: &Conditional) {
+        expr.condition.accept(self);
+        expr.then_branch.accept(self);
+        expr.else_branch.accept(self);
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) {
+        expr.callee.accept(self);
+        for argument in &expr.arguments {
+            argument.accept(self);
+        }
+        for (i, argument) in expr.named.iter().enumerate() {
+            argument.value.accept(self);
+            if expr.named[..i]
+                .iter()
+                .any(|other| other.name == argument.name)
+            {
+                let message = format!("Argument '{}' is passed more than once.", argument.name);
+                self.error(argument.span, message);
+            }
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) {
+        expr.object.accept(self)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) {
+        expr.expression.accept(self)
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+    }
+
+    fn visit_index_set_expr(&mut self, expr: &IndexSet) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+        expr.value.accept(self);
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &Lambda) {
+        self.resolve_function(&expr.params, &expr.body, FunctionKind::Function)
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) {
+        for element in &expr.elements {
+            element.accept(self);
+        }
+    }
+
+    fn visit_literal_expr(&mut self, _expr: &Literal) {}
+
+    fn visit_map_expr(&mut self, expr: &Map) {
+        for (key, value) in expr.keys.iter().zip(&expr.values) {
+            key.accept(self);
+            value.accept(self);
+        }
+    }
+
+    fn visit_match_expr(&mut self, expr: &Match) {
+        expr.subject.accept(self);
+        for arm in &expr.arms {
+            let mut names = Vec::new();
+            arm.pattern.bindings(&mut names);
+            self.begin_scope();
+            for name in names {
+                self.declare(&name, arm.pattern.span(), false);
+            }
+            if let Some(guard) = &arm.guard {
+                guard.accept(self);
+            }
+            arm.value.accept(self);
+            self.end_scope();
+        }
+    }
+
+    fn visit_postfix_expr(&mut self, expr: &Postfix) {
+        self.check_assignment(&expr.name, expr.span)
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) {
+        expr.object.accept(self);
+        expr.value.accept(self);
+    }
+
+    fn visit_slice_expr(&mut self, expr: &Slice) {
+        expr.object.accept(self);
+        for bound in expr.start.iter().chain(&expr.end) {
+            bound.accept(self);
+        }
+    }
+
+    fn visit_super_expr(&mut self, _expr: &Super) {}
+
+    fn visit_this_expr(&mut self, _expr: &This) {}
+
+    fn visit_unary_expr(&mut self, expr: &Unary) {
+        expr.right.accept(self)
+    }
+
+    fn visit_variable_expr(&mut self, _expr: &Variable) {}
+}
+
+impl StmtVisitor for Resolver {
+    type Output = ();
+
+    fn visit_block_stmt(&mut self, stmt: &Block) {
+        self.resolve_block(&stmt.statements)
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &Break) {
+        self.resolve_jump("break", &stmt.label, stmt.span)
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) {
+        self.declare(&stmt.name, stmt.span, false);
+        for method in &stmt.methods {
+            let kind = if method.name == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Function
+            };
+            self.resolve_function(&method.params, &method.body, kind);
+        }
+    }
+
+    fn visit_const_stmt(&mut self, stmt: &Const) {
+        stmt.initializer.accept(self);
+        self.declare(&stmt.name, stmt.span, true);
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &Continue) {
+        self.resolve_jump("continue", &stmt.label, stmt.span)
+    }
+
+    fn visit_enum_stmt(&mut self, stmt: &Enum) {
+        self.declare(&stmt.name, stmt.span, false);
+        for (i, variant) in stmt.variants.iter().enumerate() {
+            if stmt.variants[..i]
+                .iter()
+                .any(|other| other.name == variant.name)
+            {
+                let message = format!(
+                    "Enum '{}' already has a variant '{}'.",
+                    stmt.name, variant.name
+                );
+                self.error(variant.span, message);
+            }
+        }
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &Export) {
+        // The parser only builds declarations here, a tree read from JSON may hold anything
+        match *stmt.declaration {
+            Stmt::Class(_) | Stmt::Const(_) | Stmt::Enum(_) | Stmt::Function(_) | Stmt::Var(_) => {}
+            _ => self.error(stmt.span, "Only declarations can be exported.".to_string()),
+        }
+        stmt.declaration.accept(self)
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression) {
+        stmt.expression.accept(self)
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) {
+        stmt.iterable.accept(self);
+        self.begin_scope();
+        for name in &stmt.names {
+            self.declare(name, stmt.span, false);
+        }
+        self.resolve_loop(&stmt.label, stmt.span, &stmt.body);
+        self.end_scope();
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) {
+        self.declare(&stmt.name, stmt.span, false);
+        self.resolve_function(&stmt.params, &stmt.body, FunctionKind::Function)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) {
+        stmt.condition.accept(self);
+        stmt.then_branch.accept(self);
+        if let Some(else_branch) = &stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &Import) {
+        for name in stmt.alias.iter().chain(&stmt.names) {
+            self.declare(name, stmt.span, false);
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) {
+        stmt.expression.accept(self)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) {
+        if let Some(value) = &stmt.value {
+            value.accept(self);
+        }
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &Throw) {
+        stmt.value.accept(self)
+    }
+
+    fn visit_try_stmt(&mut self, stmt: &Try) {
+        self.resolve_block(&stmt.body);
+        if let Some(clause) = &stmt.catch_clause {
+            self.begin_scope();
+            self.declare(&clause.name, clause.span, false);
+            self.resolve_program(&clause.body);
+            self.end_scope();
+        }
+        self.resolve_block(&stmt.finally_body);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) {
+        if let Some(initializer) = &stmt.initializer {
+            initializer.accept(self);
+        }
+        self.declare(&stmt.name, stmt.span, false);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) {
+        stmt.condition.accept(self);
+        self.resolve_loop(&stmt.label, stmt.span, &stmt.body);
+        if let Some(increment) = &stmt.increment {
+            increment.accept(self);
+        }
+    }
+
+    fn visit_yield_stmt(&mut self, stmt: &Yield) {
+        let message = match self.function {
+            FunctionKind::None => Some("Can't use 'yield' outside of a function."),
+            FunctionKind::Initializer => Some("Can't yield from an initializer."),
+            FunctionKind::Function => None,
+        };
+        if let Some(message) = message {
+            self.error(stmt.span, message.to_string());
+        }
+        stmt.value.accept(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    #[test]
+    fn jumps_need_a_loop_in_the_same_function() {
+        let code = "break;\n\
+                    outer: while (true) { for (;;) { continue outer; break inner; } }\n\
+                    while (true) { fun f() { continue; } }\n\
+                    a: while (true) { a: while (true) { break a; } }\n\
+                    yield 1; class C { init() { yield 2; } }\n\
+                    const A = 1; A = 2; fun f(A) { A = 3; } { var A = 4; A++; } const A = 5;\n\
+                    fun g(a = 1, b, ...c, d) {} (...e = []) => e; g(b: 1, b: 2); fun h(a, a) {}";
+        let mut scanner = Scanner::new(code.to_string());
+        let statements = Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .expect("program should parse");
+
+        let errors: Vec<String> = resolve(&statements)
+            .iter()
+            .map(ResolveError::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "[line 1, column 1] Error: Can't use 'break' outside of a loop.",
+                "[line 2, column 50] Error: No enclosing loop is labelled 'inner'.",
+                "[line 3, column 26] Error: Can't use 'continue' outside of a loop.",
+                "[line 4, column 22] Error: Label 'a' is already used by an enclosing loop.",
+                "[line 5, column 1] Error: Can't use 'yield' outside of a function.",
+                "[line 5, column 29] Error: Can't yield from an initializer.",
+                "[line 6, column 14] Error: Can't assign to constant 'A'.\n\
+                 [line 6, column 7] Note: 'A' is declared here.",
+                "[line 6, column 67] Error: 'A' is already declared as a constant.\n\
+                 [line 6, column 7] Note: 'A' is declared here.",
+                "[line 7, column 14] Error: A parameter without a default can't follow one \
+                 with a default.",
+                "[line 7, column 20] Error: A rest parameter must be the last one.",
+                "[line 7, column 23] Error: A parameter without a default can't follow one \
+                 with a default.",
+                "[line 7, column 33] Error: A rest parameter can't have a default value.",
+                "[line 7, column 55] Error: Argument 'b' is passed more than once.",
+                "[line 7, column 71] Error: Duplicate parameter 'a'.",
+            ]
+        );
+    }
+
+    #[test]
+    fn only_declarations_can_be_exported() {
+        let json = r#"[{"Export": {"id": 1, "declaration": {"Print": {"id": 0,
+            "expression": {"Literal": {"id": 2, "value": "Nil",
+            "span": {"line": 1, "column": 14, "length": 3}}},
+            "span": {"line": 1, "column": 8, "length": 5}}},
+            "span": {"line": 1, "column": 1, "length": 6}}}]"#;
+        let statements: Vec<Stmt> = serde_json::from_str(json).expect("tree should load");
+
+        let errors: Vec<String> = resolve(&statements)
+            .iter()
+            .map(ResolveError::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec!["[line 1, column 1] Error: Only declarations can be exported."]
+        );
+    }
+}
@@ -1,17 +1,73 @@
-use crate::ast::{Binary, Expr, ExprVisitor, Grouping, Literal, LiteralValue, Unary};
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Conditional, Const, Continue, Enum, Export, Expr,
+    ExprVisitor, Expression, ForIn, Function, Get, Grouping, If, Import, Index, IndexSet, Lambda,
+    List, Literal, LiteralValue, Map, Match, Parameter, Postfix, Print, Return, Set, Slice, Stmt,
+    StmtVisitor, Super, This, Throw, Try, TypeAnnotation, Unary, Var, Variable, While, Yield,
+};
 
 pub struct RPNAstPrinter;
 
 impl ExprVisitor for RPNAstPrinter {
     type Output = String;
+    fn visit_assign_expr(&mut self, expr: &Assign) -> Self::Output {
+        format!("{} {} =", expr.name, expr.value.accept(self))
+    }
+
     fn visit_binary_expr(&mut self, expr: &Binary) -> Self::Output {
-        self.parenthesize(&expr.operator.lexeme, &[&expr.left, &expr.right])
+        self.parenthesize(expr.operator.symbol(), &[&expr.left, &expr.right])
+    }
+
+    // A named argument is its value followed by its name, `f 1 2 b: call`
+    fn visit_call_expr(&mut self, expr: &Call) -> Self::Output {
+        let mut builder = String::new();
+        for argument in std::iter::once(&*expr.callee).chain(&expr.arguments) {
+            builder.push_str(&argument.accept(self));
+            builder.push(' ');
+        }
+        for argument in &expr.named {
+            builder.push_str(&format!(
+                "{} {}: ",
+                argument.value.accept(self),
+                argument.name
+            ));
+        }
+        builder.push_str("call");
+        builder
+    }
+
+    fn visit_conditional_expr(&mut self, expr: &Conditional) -> Self::Output {
+        self.parenthesize(
+            "?:",
+            &[&expr.condition, &expr.then_branch, &expr.else_branch],
+        )
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> Self::Output {
+        format!("{} {} .", expr.object.accept(self), expr.name)
     }
 
     fn visit_grouping_expr(&mut self, expr: &Grouping) -> Self::Output {
         self.parenthesize("group", &[&expr.expression])
     }
 
+    fn visit_index_expr(&mut self, expr: &Index) -> Self::Output {
+        self.parenthesize("[]", &[&expr.object, &expr.index])
+    }
+
+    fn visit_index_set_expr(&mut self, expr: &IndexSet) -> Self::Output {
+        let operator = format!("[]{}", expr.store.symbol());
+        self.parenthesize(&operator, &[&expr.object, &expr.index, &expr.value])
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &Lambda) -> Self::Output {
+        self.function("fun", &expr.params, &expr.return_type, &expr.body)
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> Self::Output {
+        let elements: Vec<&Expr> = expr.elements.iter().collect();
+        self.parenthesize("list", &elements)
+    }
+
     fn visit_literal_expr(&mut self, expr: &Literal) -> Self::Output {
         match &expr.value {
             LiteralValue::Nil => String::from("nil"),
@@ -22,8 +78,241 @@ impl ExprVisitor for RPNAstPrinter {
         }
     }
 
+    // The keys and values alternate, `k1 v1 k2 v2 map`
+    fn visit_map_expr(&mut self, expr: &Map) -> Self::Output {
+        let entries: Vec<&Expr> = expr
+            .keys
+            .iter()
+            .zip(&expr.values)
+            .flat_map(|(key, value)| vec![key, value])
+            .collect();
+        self.parenthesize("map", &entries)
+    }
+
+    // Patterns aren't expressions and print as they are written, `x [h, ..t] h if t => match`
+    fn visit_match_expr(&mut self, expr: &Match) -> Self::Output {
+        let mut builder = expr.subject.accept(self);
+
+        for arm in &expr.arms {
+            builder.push_str(&format!(" {}", arm.pattern));
+            if let Some(guard) = &arm.guard {
+                builder.push_str(&format!(" {} if", guard.accept(self)));
+            }
+            builder.push_str(&format!(" {} =>", arm.value.accept(self)));
+        }
+
+        builder.push_str(" match");
+        builder
+    }
+
+    fn visit_postfix_expr(&mut self, expr: &Postfix) -> Self::Output {
+        format!("{} {}", expr.name, expr.operator.symbol())
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> Self::Output {
+        format!(
+            "{} {} {} .{}",
+            expr.object.accept(self),
+            expr.value.accept(self),
+            expr.name,
+            expr.store.symbol()
+        )
+    }
+
+    // A bound that was left out prints as nothing on its side of the colon
+    fn visit_slice_expr(&mut self, expr: &Slice) -> Self::Output {
+        let mut builder = expr.object.accept(self);
+        if let Some(start) = &expr.start {
+            builder.push(' ');
+            builder.push_str(&start.accept(self));
+        }
+        builder.push_str(" :");
+        if let Some(end) = &expr.end {
+            builder.push(' ');
+            builder.push_str(&end.accept(self));
+        }
+        builder.push_str(" [:]");
+        builder
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) -> Self::Output {
+        format!("{} super", expr.method)
+    }
+
+    fn visit_this_expr(&mut self, _expr: &This) -> Self::Output {
+        String::from("this")
+    }
+
     fn visit_unary_expr(&mut self, expr: &Unary) -> Self::Output {
-        self.parenthesize(&expr.operator.lexeme, &[&expr.right])
+        self.parenthesize(expr.operator.symbol(), &[&expr.right])
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) -> Self::Output {
+        expr.name.clone()
+    }
+}
+
+// Statements print their operands first and their keyword last, like the operators do
+impl StmtVisitor for RPNAstPrinter {
+    type Output = String;
+    fn visit_block_stmt(&mut self, stmt: &Block) -> Self::Output {
+        let mut builder = String::new();
+
+        for statement in &stmt.statements {
+            builder.push_str(&statement.accept(self));
+            builder.push(' ');
+        }
+
+        builder.push_str("block");
+        builder
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &Break) -> Self::Output {
+        labelled("break", &stmt.label)
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) -> Self::Output {
+        let mut builder = String::new();
+
+        for method in &stmt.methods {
+            builder.push_str(&self.visit_function_stmt(method));
+            builder.push(' ');
+        }
+
+        builder.push_str(&stmt.name);
+        if let Some(superclass) = &stmt.superclass {
+            builder.push_str(&format!(" {} <", superclass.name));
+        }
+        builder.push_str(" class");
+        builder
+    }
+
+    fn visit_const_stmt(&mut self, stmt: &Const) -> Self::Output {
+        let name = annotated(&stmt.name, &stmt.annotation);
+        format!("{} {} const", stmt.initializer.accept(self), name)
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &Continue) -> Self::Output {
+        labelled("continue", &stmt.label)
+    }
+
+    // `Circle(r) Rect(w,h) Empty Shape enum`, fields stick to their variant like types do
+    fn visit_enum_stmt(&mut self, stmt: &Enum) -> Self::Output {
+        let mut builder = String::new();
+        for variant in &stmt.variants {
+            builder.push_str(&variant.name);
+            if !variant.fields.is_empty() {
+                builder.push_str(&format!("({})", variant.fields.join(",")));
+            }
+            builder.push(' ');
+        }
+        builder.push_str(&format!("{} enum", stmt.name));
+        builder
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &Export) -> Self::Output {
+        format!("{} export", stmt.declaration.accept(self))
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> Self::Output {
+        self.parenthesize(";", &[&stmt.expression])
+    }
+
+    // `map body key value in outer: for`
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) -> Self::Output {
+        let mut builder = format!(
+            "{} {} {} in",
+            stmt.iterable.accept(self),
+            stmt.body.accept(self),
+            stmt.names.join(" ")
+        );
+        if let Some(label) = &stmt.label {
+            builder.push_str(&format!(" {}:", label));
+        }
+        builder.push_str(" for");
+        builder
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) -> Self::Output {
+        let name = format!("{} fun", stmt.name);
+        self.function(&name, &stmt.params, &stmt.return_type, &stmt.body)
+    }
+
+    // `condition then else if`, without an else when there is none
+    fn visit_if_stmt(&mut self, stmt: &If) -> Self::Output {
+        let mut builder = format!(
+            "{} {}",
+            stmt.condition.accept(self),
+            stmt.then_branch.accept(self)
+        );
+        if let Some(else_branch) = &stmt.else_branch {
+            builder.push_str(&format!(" {}", else_branch.accept(self)));
+        }
+        builder.push_str(" if");
+        builder
+    }
+
+    // `util.tk util import` or `math.tk sqrt pi from`
+    fn visit_import_stmt(&mut self, stmt: &Import) -> Self::Output {
+        match &stmt.alias {
+            Some(alias) => format!("{} {} import", stmt.path, alias),
+            None => format!("{} {} from", stmt.path, stmt.names.join(" ")),
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) -> Self::Output {
+        self.parenthesize("print", &[&stmt.expression])
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) -> Self::Output {
+        match &stmt.value {
+            Some(value) => self.parenthesize("return", &[value]),
+            None => String::from("return"),
+        }
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &Throw) -> Self::Output {
+        self.parenthesize("throw", &[&stmt.value])
+    }
+
+    // `body try catch-body e catch finally-body finally`, a missing clause is left out
+    fn visit_try_stmt(&mut self, stmt: &Try) -> Self::Output {
+        let mut builder = self.sequence(&stmt.body, "try");
+        if let Some(clause) = &stmt.catch_clause {
+            let keyword = format!("{} catch", clause.name);
+            builder.push(' ');
+            builder.push_str(&self.sequence(&clause.body, &keyword));
+        }
+        if !stmt.finally_body.is_empty() {
+            builder.push(' ');
+            builder.push_str(&self.sequence(&stmt.finally_body, "finally"));
+        }
+        builder
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) -> Self::Output {
+        let name = annotated(&stmt.name, &stmt.annotation);
+        match &stmt.initializer {
+            Some(initializer) => format!("{} {} var", initializer.accept(self), name),
+            None => format!("{} var", name),
+        }
+    }
+
+    // `condition body increment step outer: while`
+    fn visit_while_stmt(&mut self, stmt: &While) -> Self::Output {
+        let mut builder = format!("{} {}", stmt.condition.accept(self), stmt.body.accept(self));
+        if let Some(increment) = &stmt.increment {
+            builder.push_str(&format!(" {} step", increment.accept(self)));
+        }
+        if let Some(label) = &stmt.label {
+            builder.push_str(&format!(" {}:", label));
+        }
+        builder.push_str(" while");
+        builder
+    }
+
+    fn visit_yield_stmt(&mut self, stmt: &Yield) -> Self::Output {
+        self.parenthesize("yield", &[&stmt.value])
     }
 }
 
@@ -32,7 +321,63 @@ impl RPNAstPrinter {
         expr.accept(self)
     }
 
-    fn parenthesize(&mut self, name: &str, exprs: &[&Box<Expr>]) -> String {
+    pub fn print_program(&mut self, statements: &[Stmt]) -> String {
+        statements
+            .iter()
+            .map(|statement| statement.accept(self))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // The parameters, then the body, then the keyword and the return type. Types stick to
+    // their names, `a:Int`, a default comes before its parameter, `1 b=`, and a rest parameter
+    // reads `...rest`
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[Parameter],
+        return_type: &Option<TypeAnnotation>,
+        body: &[Stmt],
+    ) -> String {
+        let mut builder = String::new();
+
+        for param in params {
+            if let Some(default) = &param.default {
+                builder.push_str(&default.accept(self));
+                builder.push(' ');
+            }
+            if param.rest {
+                builder.push_str("...");
+            }
+            builder.push_str(&annotated(&param.name, &param.annotation));
+            if param.default.is_some() {
+                builder.push('=');
+            }
+            builder.push(' ');
+        }
+        for statement in body {
+            builder.push_str(&statement.accept(self));
+            builder.push(' ');
+        }
+
+        builder.push_str(name);
+        if let Some(return_type) = return_type {
+            builder.push_str(&format!(" ->{}", return_type));
+        }
+        builder
+    }
+
+    fn sequence(&mut self, statements: &[Stmt], keyword: &str) -> String {
+        let mut builder = String::new();
+        for statement in statements {
+            builder.push_str(&statement.accept(self));
+            builder.push(' ');
+        }
+        builder.push_str(keyword);
+        builder
+    }
+
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> String {
         let mut builder = String::new();
 
         // builder.push('(');
@@ -47,3 +392,18 @@ impl RPNAstPrinter {
         builder
     }
 }
+
+// `break` or `outer break`
+fn labelled(keyword: &str, label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{} {}", label, keyword),
+        None => keyword.to_string(),
+    }
+}
+
+fn annotated(name: &str, annotation: &Option<TypeAnnotation>) -> String {
+    match annotation {
+        Some(annotation) => format!("{}:{}", name, annotation),
+        None => name.to_string(),
+    }
+}
@@ -0,0 +1,561 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Conditional, Const, Continue, Enum, Export, Expr,
+    ExprVisitor, Expression, ForIn, Function, Get, Grouping, If, Import, Index, IndexSet, Lambda,
+    List, Literal, LiteralValue, Map, Match, Parameter, Postfix, Print, Return, Set, Slice, Stmt,
+    StmtVisitor, Store, Super, This, Throw, Try, TypeAnnotation, Unary, Var, Variable, While,
+    Yield,
+};
+use crate::ast_printer::literal;
+use crate::parser::{Associativity, Fixity, Operator, OPERATORS};
+
+// Prints a tree back as source code that parses to the same tree, apart from the spans, the
+// ids and the parentheses. Operands only get parentheses the operator table says they need,
+// so a desugared `x *= a + b` prints as `x = x * (a + b)`. Lambdas are always written with
+// `fun` and a block, and a `for` loop as the block and the `while` it is made of
+#[derive(Default)]
+pub struct SourcePrinter {
+    depth: usize,
+}
+
+impl ExprVisitor for SourcePrinter {
+    type Output = String;
+    fn visit_assign_expr(&mut self, expr: &Assign) -> Self::Output {
+        let value = self.right(&expr.value, infix("="));
+        format!("{} = {}", expr.name, value)
+    }
+
+    fn visit_binary_expr(&mut self, expr: &Binary) -> Self::Output {
+        let operator = infix(expr.operator.symbol());
+        let left = self.left(&expr.left, operator);
+        let right = self.right(&expr.right, operator);
+        match operator.symbol {
+            "," => format!("{}, {}", left, right),
+            symbol => format!("{} {} {}", left, symbol, right),
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> Self::Output {
+        let mut arguments: Vec<String> = expr
+            .arguments
+            .iter()
+            .map(|argument| self.item(argument))
+            .collect();
+        for argument in &expr.named {
+            let value = self.item(&argument.value);
+            arguments.push(format!("{}: {}", argument.name, value));
+        }
+        format!("{}({})", self.object(&expr.callee), arguments.join(", "))
+    }
+
+    fn visit_conditional_expr(&mut self, expr: &Conditional) -> Self::Output {
+        format!(
+            "{} ? {} : {}",
+            self.left(&expr.condition, infix("? :")),
+            self.item(&expr.then_branch),
+            self.item(&expr.else_branch)
+        )
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> Self::Output {
+        format!("{}.{}", self.object(&expr.object), expr.name)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> Self::Output {
+        format!("({})", expr.expression.accept(self))
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) -> Self::Output {
+        format!("{}[{}]", self.object(&expr.object), self.item(&expr.index))
+    }
+
+    fn visit_index_set_expr(&mut self, expr: &IndexSet) -> Self::Output {
+        let target = format!("{}[{}]", self.object(&expr.object), self.item(&expr.index));
+        self.store(expr.store, target, &expr.value)
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &Lambda) -> Self::Output {
+        self.function("fun ", &expr.params, &expr.return_type, &expr.body)
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> Self::Output {
+        let elements: Vec<String> = expr.elements.iter().map(|e| self.item(e)).collect();
+        format!("[{}]", elements.join(", "))
+    }
+
+    fn visit_literal_expr(&mut self, expr: &Literal) -> Self::Output {
+        literal(&expr.value)
+    }
+
+    fn visit_map_expr(&mut self, expr: &Map) -> Self::Output {
+        let entries: Vec<String> = expr
+            .keys
+            .iter()
+            .zip(&expr.values)
+            .map(|(key, value)| format!("{}: {}", self.item(key), self.item(value)))
+            .collect();
+        format!("{{{}}}", entries.join(", "))
+    }
+
+    // One arm per line, each followed by a comma
+    fn visit_match_expr(&mut self, expr: &Match) -> Self::Output {
+        let mut builder = format!("match {} {{\n", self.item(&expr.subject));
+        self.depth += 1;
+        for arm in &expr.arms {
+            builder.push_str(&format!("{}{}", self.indent(), arm.pattern));
+            if let Some(guard) = &arm.guard {
+                // `if (a = 1) =>` would read as an arrow lambda
+                let guard = match self.item(guard) {
+                    guard if guard.ends_with(')') => format!("({})", guard),
+                    guard => guard,
+                };
+                builder.push_str(&format!(" if {}", guard));
+            }
+            builder.push_str(&format!(" => {},\n", self.item(&arm.value)));
+        }
+        self.depth -= 1;
+        builder.push_str(&format!("{}}}", self.indent()));
+        builder
+    }
+
+    fn visit_postfix_expr(&mut self, expr: &Postfix) -> Self::Output {
+        format!("{}{}", expr.name, expr.operator.symbol())
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> Self::Output {
+        let target = format!("{}.{}", self.object(&expr.object), expr.name);
+        self.store(expr.store, target, &expr.value)
+    }
+
+    fn visit_slice_expr(&mut self, expr: &Slice) -> Self::Output {
+        let start = expr.start.as_ref().map(|start| self.item(start));
+        let end = expr.end.as_ref().map(|end| self.item(end));
+        format!(
+            "{}[{}:{}]",
+            self.object(&expr.object),
+            start.unwrap_or_default(),
+            end.unwrap_or_default()
+        )
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) -> Self::Output {
+        format!("super.{}", expr.method)
+    }
+
+    fn visit_this_expr(&mut self, _expr: &This) -> Self::Output {
+        String::from("this")
+    }
+
+    // `- -x` keeps its space, `--x` would be a decrement
+    fn visit_unary_expr(&mut self, expr: &Unary) -> Self::Output {
+        let symbol = expr.operator.symbol();
+        let operator = prefix(symbol);
+        let right = self.wrap(&expr.right, precedence(&expr.right) < operator.precedence);
+        if symbol == "-" && right.starts_with('-') {
+            format!("{} {}", symbol, right)
+        } else {
+            format!("{}{}", symbol, right)
+        }
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) -> Self::Output {
+        expr.name.clone()
+    }
+}
+
+impl StmtVisitor for SourcePrinter {
+    type Output = String;
+    fn visit_block_stmt(&mut self, stmt: &Block) -> Self::Output {
+        self.block(&stmt.statements)
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &Break) -> Self::Output {
+        labelled("break", &stmt.label)
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) -> Self::Output {
+        let mut builder = format!("class {}", stmt.name);
+        if let Some(superclass) = &stmt.superclass {
+            builder.push_str(&format!(" < {}", superclass.name));
+        }
+        if stmt.methods.is_empty() {
+            builder.push_str(" {}");
+            return builder;
+        }
+
+        builder.push_str(" {\n");
+        self.depth += 1;
+        for method in &stmt.methods {
+            let method = self.function(
+                &method.name,
+                &method.params,
+                &method.return_type,
+                &method.body,
+            );
+            builder.push_str(&format!("{}{}\n", self.indent(), method));
+        }
+        self.depth -= 1;
+        builder.push_str(&format!("{}}}", self.indent()));
+        builder
+    }
+
+    fn visit_const_stmt(&mut self, stmt: &Const) -> Self::Output {
+        format!(
+            "const {} = {};",
+            annotated(&stmt.name, &stmt.annotation),
+            self.item(&stmt.initializer)
+        )
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &Continue) -> Self::Output {
+        labelled("continue", &stmt.label)
+    }
+
+    fn visit_enum_stmt(&mut self, stmt: &Enum) -> Self::Output {
+        let variants: Vec<String> = stmt
+            .variants
+            .iter()
+            .map(|variant| match variant.fields.len() {
+                0 => variant.name.clone(),
+                _ => format!("{}({})", variant.name, variant.fields.join(", ")),
+            })
+            .collect();
+        match variants.len() {
+            0 => format!("enum {} {{}}", stmt.name),
+            _ => format!("enum {} {{ {} }}", stmt.name, variants.join(", ")),
+        }
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &Export) -> Self::Output {
+        format!("export {}", stmt.declaration.accept(self))
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> Self::Output {
+        format!("{};", stmt.expression.accept(self))
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) -> Self::Output {
+        format!(
+            "{}for ({} in {}) {}",
+            label(&stmt.label),
+            stmt.names.join(", "),
+            stmt.iterable.accept(self),
+            stmt.body.accept(self)
+        )
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) -> Self::Output {
+        let name = format!("fun {}", stmt.name);
+        self.function(&name, &stmt.params, &stmt.return_type, &stmt.body)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) -> Self::Output {
+        let mut builder = format!(
+            "if ({}) {}",
+            stmt.condition.accept(self),
+            stmt.then_branch.accept(self)
+        );
+        if let Some(else_branch) = &stmt.else_branch {
+            builder.push_str(&format!(" else {}", else_branch.accept(self)));
+        }
+        builder
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &Import) -> Self::Output {
+        match &stmt.alias {
+            Some(alias) => format!("import \"{}\" as {};", stmt.path, alias),
+            None => format!("from \"{}\" import {};", stmt.path, stmt.names.join(", ")),
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) -> Self::Output {
+        format!("print {};", stmt.expression.accept(self))
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) -> Self::Output {
+        match &stmt.value {
+            Some(value) => format!("return {};", value.accept(self)),
+            None => String::from("return;"),
+        }
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &Throw) -> Self::Output {
+        format!("throw {};", stmt.value.accept(self))
+    }
+
+    fn visit_try_stmt(&mut self, stmt: &Try) -> Self::Output {
+        let mut builder = format!("try {}", self.block(&stmt.body));
+        if let Some(clause) = &stmt.catch_clause {
+            let body = self.block(&clause.body);
+            builder.push_str(&format!(" catch ({}) {}", clause.name, body));
+        }
+        // Without a catch the try needs its finally, even an empty one
+        if !stmt.finally_body.is_empty() || stmt.catch_clause.is_none() {
+            builder.push_str(&format!(" finally {}", self.block(&stmt.finally_body)));
+        }
+        builder
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) -> Self::Output {
+        let name = annotated(&stmt.name, &stmt.annotation);
+        match &stmt.initializer {
+            Some(initializer) => format!("var {} = {};", name, self.item(initializer)),
+            None => format!("var {};", name),
+        }
+    }
+
+    // A loop with a step is what's left of a `for`
+    fn visit_while_stmt(&mut self, stmt: &While) -> Self::Output {
+        let condition = stmt.condition.accept(self);
+        let head = match &stmt.increment {
+            Some(increment) => format!("for (; {}; {})", condition, increment.accept(self)),
+            None => format!("while ({})", condition),
+        };
+        format!("{}{} {}", label(&stmt.label), head, stmt.body.accept(self))
+    }
+
+    fn visit_yield_stmt(&mut self, stmt: &Yield) -> Self::Output {
+        format!("yield {};", stmt.value.accept(self))
+    }
+}
+
+impl SourcePrinter {
+    pub fn new() -> Self {
+        SourcePrinter::default()
+    }
+
+    pub fn print(&mut self, expr: &Expr) -> String {
+        expr.accept(self)
+    }
+
+    // One line per top-level statement, the ones inside blocks are indented by four spaces
+    pub fn print_program(&mut self, statements: &[Stmt]) -> String {
+        statements
+            .iter()
+            .map(|statement| statement.accept(self))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn indent(&self) -> String {
+        "    ".repeat(self.depth)
+    }
+
+    fn block(&mut self, statements: &[Stmt]) -> String {
+        if statements.is_empty() {
+            return String::from("{}");
+        }
+
+        let mut builder = String::from("{\n");
+        self.depth += 1;
+        for statement in statements {
+            let statement = statement.accept(self);
+            builder.push_str(&format!("{}{}\n", self.indent(), statement));
+        }
+        self.depth -= 1;
+        builder.push_str(&format!("{}}}", self.indent()));
+        builder
+    }
+
+    // `name(a: Int = 1, ...rest) -> Int { body }`, `name` includes the `fun` when there is one
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[Parameter],
+        return_type: &Option<TypeAnnotation>,
+        body: &[Stmt],
+    ) -> String {
+        let params: Vec<String> = params.iter().map(|param| self.parameter(param)).collect();
+        let mut builder = format!("{}({})", name, params.join(", "));
+        if let Some(return_type) = return_type {
+            builder.push_str(&format!(" -> {}", return_type));
+        }
+        builder.push(' ');
+        builder.push_str(&self.block(body));
+        builder
+    }
+
+    fn parameter(&mut self, param: &Parameter) -> String {
+        let mut builder = annotated(&param.name, &param.annotation);
+        if param.rest {
+            builder.insert_str(0, "...");
+        }
+        if let Some(default) = &param.default {
+            builder.push_str(&format!(" = {}", self.item(default)));
+        }
+        builder
+    }
+
+    // `target = value`, `target += value` or `target++`
+    fn store(&mut self, store: Store, target: String, value: &Expr) -> String {
+        match store {
+            Store::Postfix(_) => format!("{}{}", target, store.symbol()),
+            _ => {
+                let value = self.right(value, infix("="));
+                format!("{} {} {}", target, store.symbol(), value)
+            }
+        }
+    }
+
+    fn wrap(&mut self, expr: &Expr, parenthesize: bool) -> String {
+        if parenthesize {
+            format!("({})", expr.accept(self))
+        } else {
+            expr.accept(self)
+        }
+    }
+
+    // An operand binding as tight as its operator only stays on the side the operator
+    // groups to
+    fn left(&mut self, expr: &Expr, operator: &Operator) -> String {
+        let own = precedence(expr);
+        let parenthesize = own < operator.precedence
+            || own == operator.precedence && operator.associativity != Associativity::Left;
+        self.wrap(expr, parenthesize)
+    }
+
+    fn right(&mut self, expr: &Expr, operator: &Operator) -> String {
+        let own = precedence(expr);
+        let parenthesize = own < operator.precedence
+            || own == operator.precedence && operator.associativity != Associativity::Right;
+        self.wrap(expr, parenthesize)
+    }
+
+    // What a call, an index or a property access is applied to
+    fn object(&mut self, expr: &Expr) -> String {
+        let parenthesize = precedence(expr) < postfix("( )").precedence;
+        self.wrap(expr, parenthesize)
+    }
+
+    // An argument, an element or anything else the parser reads up to the next comma
+    fn item(&mut self, expr: &Expr) -> String {
+        let parenthesize = precedence(expr) <= infix(",").precedence;
+        self.wrap(expr, parenthesize)
+    }
+}
+
+// How tightly the expression's outermost operator binds, anything that isn't an operator
+// can go anywhere
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Assign(_) => infix("=").precedence,
+        Expr::Binary(binary) => infix(binary.operator.symbol()).precedence,
+        Expr::Conditional(_) => infix("? :").precedence,
+        Expr::IndexSet(IndexSet { store, .. }) | Expr::Set(Set { store, .. }) => match store {
+            Store::Postfix(_) => postfix("++").precedence,
+            _ => infix("=").precedence,
+        },
+        Expr::Postfix(_) => postfix("++").precedence,
+        Expr::Unary(unary) => prefix(unary.operator.symbol()).precedence,
+        // A folded constant can be negative, which reads back as a negation
+        Expr::Literal(Literal {
+            value: LiteralValue::Integer(n),
+            ..
+        }) if *n < 0 => prefix("-").precedence,
+        Expr::Literal(Literal {
+            value: LiteralValue::Float(n),
+            ..
+        }) if n.is_sign_negative() => prefix("-").precedence,
+        _ => u8::MAX,
+    }
+}
+
+fn operator(symbol: &str, fixity: Fixity) -> &'static Operator {
+    OPERATORS
+        .iter()
+        .find(|operator| operator.fixity == fixity && operator.symbol == symbol)
+        .unwrap_or_else(|| panic!("'{}' is missing from the operator table", symbol))
+}
+
+fn infix(symbol: &str) -> &'static Operator {
+    operator(symbol, Fixity::Infix)
+}
+
+fn prefix(symbol: &str) -> &'static Operator {
+    operator(symbol, Fixity::Prefix)
+}
+
+fn postfix(symbol: &str) -> &'static Operator {
+    operator(symbol, Fixity::Postfix)
+}
+
+fn annotated(name: &str, annotation: &Option<TypeAnnotation>) -> String {
+    match annotation {
+        Some(annotation) => format!("{}: {}", name, annotation),
+        None => name.to_string(),
+    }
+}
+
+fn label(label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{}: ", label),
+        None => String::new(),
+    }
+}
+
+// `break;` or `break outer;`
+fn labelled(keyword: &str, label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{} {};", keyword, label),
+        None => format!("{};", keyword),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::Scanner;
+    use parser::Parser;
+
+    #[test]
+    fn a_program_prints_as_source_with_only_the_parentheses_it_needs() {
+        let code = "import \"shapes\" as shapes; from \"util\" import pad, trim;\n\
+                    export const LIMIT: Int = 1 << 4;\n\
+                    enum Shape { Circle(r), Empty }\n\
+                    class Box < Base { init(size = 1, ...rest) { super.init(); this.size = size; } }\n\
+                    outer: while (!done) { m[\"a\"][1] += xs[1:]; box.size--; break outer; }\n\
+                    for (var i = 0; i < 3; ++i) print i, - -i, 2.0 ** (-1), (a = 1) + 2;\n\
+                    x *= a + b; y = (c ? 1 : 2) ? [3, f(k: 4)] : {5: (6)};\n\
+                    try { throw shapes.error(\"x\"); } catch (e) { print e; } finally {}\n\
+                    var f = (n) => match n { Shape.Circle(r) if r > 0 => r, [1, ..] | _ => -1 };";
+        let mut scanner = Scanner::new(code.to_string());
+        let statements = Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .expect("program should parse");
+
+        assert_eq!(
+            SourcePrinter::new().print_program(&statements),
+            "import \"shapes\" as shapes;\n\
+             from \"util\" import pad, trim;\n\
+             export const LIMIT: Int = 1 << 4;\n\
+             enum Shape { Circle(r), Empty }\n\
+             class Box < Base {\n    \
+                 init(size = 1, ...rest) {\n        \
+                     super.init();\n        \
+                     this.size = size;\n    \
+                 }\n\
+             }\n\
+             outer: while (!done) {\n    \
+                 m[\"a\"][1] += xs[1:];\n    \
+                 box.size--;\n    \
+                 break outer;\n\
+             }\n\
+             {\n    \
+                 var i = 0;\n    \
+                 for (; i < 3; i = i + 1) print i, - -i, 2.0 ** (-1), (a = 1) + 2;\n\
+             }\n\
+             x = x * (a + b);\n\
+             y = (c ? 1 : 2) ? [3, f(k: 4)] : {5: (6)};\n\
+             try {\n    \
+                 throw shapes.error(\"x\");\n\
+             } catch (e) {\n    \
+                 print e;\n\
+             }\n\
+             var f = fun (n) {\n    \
+                 return match n {\n        \
+                     Shape.Circle(r) if r > 0 => r,\n        \
+                     [1, .._] | _ => -1,\n    \
+                 };\n\
+             };"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use parser::OPERATORS;
+use std::collections::HashSet;
+use tools::generate_programs::{Grammar, Node};
+
+// Compares grammar.txt with the parser so the two can't drift apart silently. A rule is
+// covered when the parser has a method named after it (optionally with a `parse_` prefix), or
+// when every symbol it spells is in the operator table, which is how the Pratt parser handles
+// the precedence levels. Table operators the grammar never mentions are reported as well
+pub fn check_grammar(grammar: &Grammar, parser_source: &str) -> Vec<String> {
+    let methods = parser_methods(parser_source);
+    let table: HashSet<&str> = OPERATORS
+        .iter()
+        .flat_map(|operator| operator.symbol.split_whitespace())
+        .collect();
+    let mut problems = Vec::new();
+    let mut spelled = HashSet::new();
+
+    for rule in grammar.rules() {
+        let mut literals = Vec::new();
+        collect_literals(&rule.body, &mut literals);
+        spelled.extend(literals.iter().cloned());
+
+        let is_precedence_level = !literals.is_empty()
+            && literals
+                .iter()
+                .all(|literal| table.contains(literal.as_str()));
+        let has_method = methods.contains(rule.name.as_str())
+            || methods.contains(format!("parse_{}", rule.name).as_str());
+        if !has_method && !is_precedence_level {
+            problems.push(format!(
+                "Rule '{}' has no parser method and isn't covered by the operator table.",
+                rule.name
+            ));
+        }
+    }
+
+    for operator in OPERATORS {
+        let missing = operator
+            .symbol
+            .split_whitespace()
+            .any(|symbol| !spelled.contains(symbol));
+        if missing {
+            problems.push(format!(
+                "Operator '{}' is in the operator table but not in the grammar.",
+                operator.symbol
+            ));
+        }
+    }
+
+    problems
+}
+
+// The names of every `fn` in the parser source
+fn parser_methods(source: &str) -> HashSet<&str> {
+    source
+        .split("fn ")
+        .skip(1)
+        .filter_map(|rest| {
+            rest.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .next()
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn collect_literals(node: &Node, literals: &mut Vec<String>) {
+    match node {
+        Node::Alternatives(nodes) | Node::Sequence(nodes) => {
+            for node in nodes {
+                collect_literals(node, literals);
+            }
+        }
+        Node::Optional(node) | Node::Repeat(node) | Node::RepeatAtLeastOnce(node) => {
+            collect_literals(node, literals)
+        }
+        Node::Literal(literal) => literals.push(literal.clone()),
+        Node::Terminal(_) | Node::Rule(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAMMAR: &str = include_str!("../../grammar.txt");
+    const PARSER: &str = include_str!("../parser.rs");
+
+    #[test]
+    fn grammar_matches_the_parser() {
+        let grammar = Grammar::parse(GRAMMAR).unwrap();
+        assert_eq!(check_grammar(&grammar, PARSER), Vec::<String>::new());
+    }
+
+    #[test]
+    fn drift_is_reported() {
+        let grammar = Grammar::parse(
+            "primary → NUMBER ;\nloop_statement → \"loop\" primary ;\nsum → primary ( \"+\" primary )* ;",
+        )
+        .unwrap();
+        let problems = check_grammar(&grammar, "fn primary(&mut self) {}");
+
+        assert_eq!(
+            problems[0],
+            "Rule 'loop_statement' has no parser method and isn't covered by the operator table."
+        );
+        assert!(problems.contains(&String::from(
+            "Operator '*' is in the operator table but not in the grammar."
+        )));
+        assert!(!problems.iter().any(|problem| problem.contains("'sum'")));
+    }
+}
@@ -0,0 +1,550 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A parsed grammar.txt, every rule is `name → alternatives ;` written in the book's EBNF flavour
+pub struct Grammar {
+    rules: Vec<Rule>,
+    index: HashMap<String, usize>,
+}
+
+pub struct Rule {
+    pub name: String,
+    pub body: Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Alternatives(Vec<Node>),
+    Sequence(Vec<Node>),
+    // `?`, `*` and `+` suffixes
+    Optional(Box<Node>),
+    Repeat(Box<Node>),
+    RepeatAtLeastOnce(Box<Node>),
+    // A quoted terminal like "(" or "true"
+    Literal(String),
+    // An upper case terminal like NUMBER, filled in by the generator
+    Terminal(String),
+    Rule(String),
+}
+
+impl Grammar {
+    pub fn parse(text: &str) -> Result<Grammar, String> {
+        let symbols = tokenize(text)?;
+        let mut rules = Vec::new();
+        let mut index = HashMap::new();
+        let mut current = 0;
+
+        while current < symbols.len() {
+            let name = match &symbols[current] {
+                Symbol::Name(name) => name.clone(),
+                other => return Err(format!("Expect rule name, found {:?}.", other)),
+            };
+            if symbols.get(current + 1) != Some(&Symbol::Arrow) {
+                return Err(format!("Expect '→' after rule '{}'.", name));
+            }
+            current += 2;
+
+            let body = parse_alternatives(&symbols, &mut current)?;
+            if symbols.get(current) != Some(&Symbol::Semicolon) {
+                return Err(format!("Expect ';' at the end of rule '{}'.", name));
+            }
+            current += 1;
+
+            if index.insert(name.clone(), rules.len()).is_some() {
+                return Err(format!("Rule '{}' is defined twice.", name));
+            }
+            rules.push(Rule { name, body });
+        }
+
+        let grammar = Grammar { rules, index };
+        for rule in &grammar.rules {
+            grammar.check_references(&rule.body)?;
+        }
+        Ok(grammar)
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn rule(&self, name: &str) -> Option<&Rule> {
+        self.index.get(name).map(|&i| &self.rules[i])
+    }
+
+    // The first rule is where a program starts
+    pub fn start(&self) -> &str {
+        &self.rules[0].name
+    }
+
+    fn check_references(&self, node: &Node) -> Result<(), String> {
+        match node {
+            Node::Alternatives(nodes) | Node::Sequence(nodes) => nodes
+                .iter()
+                .try_for_each(|node| self.check_references(node)),
+            Node::Optional(node) | Node::Repeat(node) | Node::RepeatAtLeastOnce(node) => {
+                self.check_references(node)
+            }
+            Node::Rule(name) if !self.index.contains_key(name) => {
+                Err(format!("Rule '{}' is used but never defined.", name))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Symbol {
+    Name(String),
+    Quoted(String),
+    Arrow,
+    Semicolon,
+    Pipe,
+    LeftParen,
+    RightParen,
+    Question,
+    Star,
+    Plus,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Symbol>, String> {
+    let mut symbols = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '→' => symbols.push(Symbol::Arrow),
+            ';' => symbols.push(Symbol::Semicolon),
+            '|' => symbols.push(Symbol::Pipe),
+            '(' => symbols.push(Symbol::LeftParen),
+            ')' => symbols.push(Symbol::RightParen),
+            '?' => symbols.push(Symbol::Question),
+            '*' => symbols.push(Symbol::Star),
+            '+' => symbols.push(Symbol::Plus),
+            '"' => {
+                let mut literal = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => literal.push(c),
+                        None => return Err(String::from("Unterminated quoted terminal.")),
+                    }
+                }
+                symbols.push(Symbol::Quoted(literal));
+            }
+            // Comments run until the end of the line, like in our own language
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                symbols.push(Symbol::Name(name));
+            }
+            c if c.is_whitespace() => {}
+            c => return Err(format!("Unexpected character '{}' in grammar.", c)),
+        }
+    }
+
+    Ok(symbols)
+}
+
+fn parse_alternatives(symbols: &[Symbol], current: &mut usize) -> Result<Node, String> {
+    let mut alternatives = vec![parse_sequence(symbols, current)?];
+
+    while symbols.get(*current) == Some(&Symbol::Pipe) {
+        *current += 1;
+        alternatives.push(parse_sequence(symbols, current)?);
+    }
+
+    if alternatives.len() == 1 {
+        return Ok(alternatives.remove(0));
+    }
+    Ok(Node::Alternatives(alternatives))
+}
+
+fn parse_sequence(symbols: &[Symbol], current: &mut usize) -> Result<Node, String> {
+    let mut items = Vec::new();
+
+    loop {
+        let item = match symbols.get(*current) {
+            Some(Symbol::Name(name)) => {
+                *current += 1;
+                // Terminals are written in upper case, rules in lower case
+                if name.chars().all(|c| c.is_uppercase() || c == '_') {
+                    Node::Terminal(name.clone())
+                } else {
+                    Node::Rule(name.clone())
+                }
+            }
+            Some(Symbol::Quoted(literal)) => {
+                *current += 1;
+                Node::Literal(literal.clone())
+            }
+            Some(Symbol::LeftParen) => {
+                *current += 1;
+                let group = parse_alternatives(symbols, current)?;
+                if symbols.get(*current) != Some(&Symbol::RightParen) {
+                    return Err(String::from("Expect ')' after group."));
+                }
+                *current += 1;
+                group
+            }
+            _ => break,
+        };
+
+        let item = match symbols.get(*current) {
+            Some(Symbol::Question) => Node::Optional(Box::new(item)),
+            Some(Symbol::Star) => Node::Repeat(Box::new(item)),
+            Some(Symbol::Plus) => Node::RepeatAtLeastOnce(Box::new(item)),
+            _ => {
+                items.push(item);
+                continue;
+            }
+        };
+        *current += 1;
+        items.push(item);
+    }
+
+    match items.len() {
+        0 => Err(format!(
+            "Expect a symbol, found {:?}.",
+            symbols.get(*current)
+        )),
+        1 => Ok(items.remove(0)),
+        _ => Ok(Node::Sequence(items)),
+    }
+}
+
+// Every binary precedence level can repeat its operands, so without a cap on the length the
+// programs grow exponentially with the depth
+const SOFT_WORD_LIMIT: usize = 120;
+
+// Generates random programs that follow the grammar, once a derivation is deeper than
+// `max_depth` (or the program is long enough) only the alternatives that finish the fastest
+// are picked
+pub struct ProgramGenerator<'g> {
+    grammar: &'g Grammar,
+    rng: Rng,
+    max_depth: usize,
+    // How many rule expansions each rule needs at least before it only has terminals left
+    min_depth: HashMap<String, usize>,
+}
+
+impl<'g> ProgramGenerator<'g> {
+    pub fn new(grammar: &'g Grammar, seed: u64, max_depth: usize) -> Self {
+        let min_depth = min_depths(grammar);
+        ProgramGenerator {
+            grammar,
+            rng: Rng::new(seed),
+            max_depth,
+            min_depth,
+        }
+    }
+
+    pub fn generate(&mut self) -> String {
+        let start = self.grammar.start().to_string();
+        self.generate_rule(&start)
+    }
+
+    // Like `generate`, but starting from any rule instead of the first one
+    pub fn generate_rule(&mut self, rule: &str) -> String {
+        let mut words = Vec::new();
+        self.expand(&Node::Rule(rule.to_string()), 0, &mut words);
+        words.join(" ")
+    }
+
+    fn expand(&mut self, node: &Node, depth: usize, words: &mut Vec<String>) {
+        let limited = depth >= self.max_depth || words.len() >= SOFT_WORD_LIMIT;
+
+        match node {
+            Node::Alternatives(alternatives) => {
+                let candidates: Vec<&Node> = if limited {
+                    let cheapest = alternatives
+                        .iter()
+                        .map(|node| self.cost(node))
+                        .min()
+                        .unwrap_or(0);
+                    alternatives
+                        .iter()
+                        .filter(|node| self.cost(node) == cheapest)
+                        .collect()
+                } else {
+                    alternatives.iter().collect()
+                };
+                let choice = candidates[self.rng.below(candidates.len())];
+                self.expand(choice, depth, words);
+            }
+            Node::Sequence(items) => {
+                for item in items {
+                    self.expand(item, depth, words);
+                }
+            }
+            Node::Optional(node) => {
+                if !limited && self.rng.below(2) == 0 {
+                    self.expand(node, depth, words);
+                }
+            }
+            Node::Repeat(node) => {
+                let times = if limited { 0 } else { self.rng.below(3) };
+                for _ in 0..times {
+                    self.expand(node, depth, words);
+                }
+            }
+            Node::RepeatAtLeastOnce(node) => {
+                let times = if limited { 1 } else { 1 + self.rng.below(2) };
+                for _ in 0..times {
+                    self.expand(node, depth, words);
+                }
+            }
+            Node::Literal(literal) => words.push(literal.clone()),
+            Node::Terminal(name) => words.push(self.terminal(name)),
+            Node::Rule(name) => {
+                let grammar = self.grammar;
+                if let Some(rule) = grammar.rule(name) {
+                    self.expand(&rule.body, depth + 1, words);
+                }
+            }
+        }
+    }
+
+    fn cost(&self, node: &Node) -> usize {
+        node_cost(node, &self.min_depth)
+    }
+
+    fn terminal(&mut self, name: &str) -> String {
+        match name {
+            "NUMBER" => {
+                if self.rng.below(2) == 0 {
+                    self.rng.below(1000).to_string()
+                } else {
+                    // `2.0` too, which has to stay a float
+                    format!("{}.{}", self.rng.below(100), self.rng.below(10))
+                }
+            }
+            "STRING" => format!(
+                "\"{}\"",
+                pick(&mut self.rng, &["", "a", "hello", "two words"])
+            ),
+            "IDENTIFIER" => {
+                pick(&mut self.rng, &["a", "b", "x", "y", "count", "total_1"]).to_string()
+            }
+            other => other.to_lowercase(),
+        }
+    }
+}
+
+fn pick<'s>(rng: &mut Rng, options: &[&'s str]) -> &'s str {
+    options[rng.below(options.len())]
+}
+
+fn min_depths(grammar: &Grammar) -> HashMap<String, usize> {
+    let mut depths: HashMap<String, usize> = grammar
+        .rules()
+        .iter()
+        .map(|rule| (rule.name.clone(), usize::MAX))
+        .collect();
+
+    // Keep relaxing until no rule finds a shorter way out
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for rule in grammar.rules() {
+            let cost = node_cost(&rule.body, &depths);
+            if cost < depths[&rule.name] {
+                depths.insert(rule.name.clone(), cost);
+                changed = true;
+            }
+        }
+    }
+
+    depths
+}
+
+fn node_cost(node: &Node, depths: &HashMap<String, usize>) -> usize {
+    match node {
+        Node::Alternatives(nodes) => nodes
+            .iter()
+            .map(|node| node_cost(node, depths))
+            .min()
+            .unwrap_or(0),
+        Node::Sequence(nodes) => nodes
+            .iter()
+            .map(|node| node_cost(node, depths))
+            .max()
+            .unwrap_or(0),
+        Node::Optional(_) | Node::Repeat(_) => 0,
+        Node::RepeatAtLeastOnce(node) => node_cost(node, depths),
+        Node::Literal(_) | Node::Terminal(_) => 0,
+        Node::Rule(name) => depths
+            .get(name)
+            .copied()
+            .unwrap_or(usize::MAX)
+            .saturating_add(1),
+    }
+}
+
+// A small xorshift generator, good enough to pick grammar alternatives reproducibly
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on zero
+        Rng(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+pub fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::{Expr, Stmt};
+    use lexer::Scanner;
+    use parser::Parser;
+    use serde_json::Value;
+    use source_printer::SourcePrinter;
+    use std::panic;
+
+    const GRAMMAR: &str = include_str!("../../grammar.txt");
+
+    #[test]
+    fn generated_programs_parse_and_print_back_to_the_same_tree() {
+        let grammar = Grammar::parse(GRAMMAR).expect("grammar.txt should parse");
+        let mut unused = vec!["&", "|", "^", "~", "<<", ">>", "%", "**", "++", "--", "%="];
+        let mut integral_floats = false;
+
+        for seed in 0..300 {
+            let program = ProgramGenerator::new(&grammar, seed, 40).generate_rule("comma");
+            unused.retain(|operator| !program.split(' ').any(|lexeme| lexeme == *operator));
+            integral_floats |= program.split(' ').any(|lexeme| lexeme.ends_with(".0"));
+
+            let (original, printed) = panic::catch_unwind(|| {
+                let expression = parse_expression(&program)
+                    .unwrap_or_else(|| panic!("seed {} did not parse: {}", seed, program));
+                let printed = SourcePrinter::new().print(&expression);
+                (expression, printed)
+            })
+            .unwrap_or_else(|_| panic!("seed {} panicked on: {}", seed, program));
+
+            let reparsed = parse_expression(&printed)
+                .unwrap_or_else(|| panic!("seed {} printed {} which doesn't parse", seed, printed));
+            assert_eq!(
+                shape(&original),
+                shape(&reparsed),
+                "seed {} printed {} as {}",
+                seed,
+                program,
+                printed
+            );
+        }
+
+        // Every operator gets its precedence and associativity checked at least once, and a
+        // float that looks like an integer has to stay a float
+        assert!(unused.is_empty(), "never generated {:?}", unused);
+        assert!(integral_floats, "never generated a float like 2.0");
+    }
+
+    #[test]
+    fn generator_respects_the_depth_bound() {
+        let grammar = Grammar::parse(GRAMMAR).unwrap();
+
+        // With no depth to spare every rule takes its shallowest way out, for an expression
+        // that is an empty arrow lambda `( ) => { }`
+        for seed in 0..20 {
+            let shallow = ProgramGenerator::new(&grammar, seed, 0).generate_rule("comma");
+            assert_eq!(shallow, "( ) => { }");
+        }
+    }
+
+    #[test]
+    fn generated_statements_parse_and_print_back_to_the_same_tree() {
+        let grammar = Grammar::parse(GRAMMAR).unwrap();
+
+        for seed in 0..100 {
+            let program = ProgramGenerator::new(&grammar, seed, 20).generate();
+            let original = parse_program(&program)
+                .unwrap_or_else(|| panic!("seed {} did not parse: {}", seed, program));
+
+            let printed = SourcePrinter::new().print_program(&original);
+            let reparsed = parse_program(&printed)
+                .unwrap_or_else(|| panic!("seed {} printed {} which doesn't parse", seed, printed));
+            assert_eq!(
+                shape(&original),
+                shape(&reparsed),
+                "seed {} printed {} as {}",
+                seed,
+                program,
+                printed
+            );
+        }
+    }
+
+    #[test]
+    fn undefined_rules_are_rejected() {
+        let error = Grammar::parse("start → missing ;").err().unwrap();
+        assert_eq!(error, "Rule 'missing' is used but never defined.");
+    }
+
+    fn parse_expression(code: &str) -> Option<Box<Expr>> {
+        let tokens = Scanner::new(code.to_string()).scan_tokens().clone();
+        Parser::new(&tokens).parse()
+    }
+
+    fn parse_program(code: &str) -> Option<Vec<Stmt>> {
+        let tokens = Scanner::new(code.to_string()).scan_tokens().clone();
+        Parser::new(&tokens).parse_program()
+    }
+
+    // The tree as JSON without what printing it back as source can change: the ids, the spans
+    // and the parentheses
+    fn shape<T: serde::Serialize>(tree: &T) -> Value {
+        strip(serde_json::to_value(tree).expect("trees serialize"))
+    }
+
+    fn strip(value: Value) -> Value {
+        match value {
+            Value::Object(mut fields) => {
+                if fields.contains_key("line") && fields.contains_key("column") {
+                    return Value::Null;
+                }
+                if let Some(mut grouping) = fields.remove("Grouping") {
+                    return strip(grouping["expression"].take());
+                }
+                fields.remove("id");
+                fields.remove("span");
+                Value::Object(
+                    fields
+                        .into_iter()
+                        .map(|(key, value)| (key, strip(value)))
+                        .collect(),
+                )
+            }
+            Value::Array(items) => Value::Array(items.into_iter().map(strip).collect()),
+            other => other,
+        }
+    }
+}
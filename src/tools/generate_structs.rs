@@ -1,79 +1,290 @@
 use std::io::{Result, Write};
+use std::mem;
 
-pub fn define_ast<W: Write>(writer: &mut W, base_name: &str, types: &[(&str, &str)]) -> Result<()> {
-    // Write the trait (base)
-    writeln!(writer, "pub trait {} {{", base_name)?;
-    writeln!(
-        writer,
-        "    fn accept<R>(&self, visitor: &mut dyn {}Visitor<R>) -> R;",
-        base_name
-    )?;
-    writeln!(writer, "}}\n")?;
+// What ast.spec describes: the lines copied to the top of the file, the derive put on every
+// type and the node kinds
+pub struct AstSpec {
+    pub imports: Vec<String>,
+    pub derive: String,
+    pub bases: Vec<BaseType>,
+}
 
-    // Write the visitor trait
-    writeln!(writer, "pub trait {}Visitor<R> {{", base_name)?;
-    for (class_name, _) in types {
-        writeln!(
-            writer,
-            "    fn visit_{}_{}(&mut self, expr: &{}) -> R;",
-            class_name.to_lowercase(),
-            base_name.to_lowercase(),
-            class_name
-        )?;
+// A node kind like `Expr`, generated as an enum plus a visitor trait
+pub struct BaseType {
+    pub name: String,
+    pub comments: Vec<String>,
+    pub types: Vec<NodeType>,
+}
+
+pub struct NodeType {
+    pub name: String,
+    pub comments: Vec<String>,
+    // (type, name) pairs, with the type as written in the spec
+    pub fields: Vec<(String, String)>,
+}
+
+impl AstSpec {
+    pub fn parse(text: &str) -> std::result::Result<AstSpec, String> {
+        let mut spec = AstSpec {
+            imports: Vec::new(),
+            derive: String::new(),
+            bases: Vec::new(),
+        };
+        let mut comments: Vec<String> = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+            let indented = line.starts_with(' ') || line.starts_with('\t');
+
+            if trimmed.is_empty() {
+                comments.clear();
+            } else if let Some(comment) = trimmed.strip_prefix("//") {
+                comments.push(comment.trim().to_string());
+            } else if trimmed.starts_with("use ") {
+                spec.imports.push(trimmed.to_string());
+            } else if trimmed.starts_with("#[") {
+                spec.derive = trimmed.to_string();
+            } else if !indented {
+                spec.bases.push(BaseType {
+                    name: trimmed.to_string(),
+                    comments: mem::take(&mut comments),
+                    types: Vec::new(),
+                });
+            } else {
+                let node = parse_node(trimmed, mem::take(&mut comments))
+                    .map_err(|e| format!("[line {}] {}", index + 1, e))?;
+                match spec.bases.last_mut() {
+                    Some(base) => base.types.push(node),
+                    None => {
+                        return Err(format!(
+                            "[line {}] Node '{}' comes before any node kind.",
+                            index + 1,
+                            node.name
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(spec)
+    }
+}
+
+fn parse_node(line: &str, comments: Vec<String>) -> std::result::Result<NodeType, String> {
+    let (name, field_list) = match line.split_once(':') {
+        Some((name, field_list)) => (name.trim(), field_list.trim()),
+        None => return Err(format!("Expect ':' after node name in '{}'.", line)),
+    };
+
+    let mut fields = Vec::new();
+    for field in field_list.split(',') {
+        let parts: Vec<&str> = field.split_whitespace().collect();
+        match parts.as_slice() {
+            [typ, name] => fields.push((typ.to_string(), name.to_string())),
+            _ => return Err(format!("Expect 'Type name' but got '{}'.", field.trim())),
+        }
+    }
+
+    Ok(NodeType {
+        name: name.to_string(),
+        comments,
+        fields,
+    })
+}
+
+// Writes the whole generated module for a spec
+pub fn generate_ast<W: Write>(writer: &mut W, spec: &AstSpec) -> Result<()> {
+    writeln!(writer, "// Generated by build.rs from ast.spec")?;
+    for import in &spec.imports {
+        writeln!(writer, "{}", import)?;
     }
-    writeln!(writer, "}}\n")?;
 
-    // Write each struct and impl
-    for (class_name, field_list) in types {
-        define_type(writer, base_name, class_name, field_list)?;
+    for base in &spec.bases {
+        writeln!(writer)?;
+        define_ast(writer, &spec.derive, base)?;
     }
 
     Ok(())
 }
 
-fn define_type<W: Write>(
-    writer: &mut W,
-    base_name: &str,
-    class_name: &str,
-    field_list: &str,
-) -> Result<()> {
-    // Define struct
-    writeln!(writer, "#[derive(Debug, Clone)]")?;
-    writeln!(writer, "pub struct {} {{", class_name)?;
-
-    let fields: Vec<&str> = field_list.split(", ").collect();
-    for field in &fields {
-        let parts: Vec<&str> = field.split_whitespace().collect();
-        let (typ, name) = (parts[0], parts[1]);
-        writeln!(writer, "    pub {}: {},", name, map_type(typ))?;
+pub fn define_ast<W: Write>(writer: &mut W, derive: &str, base: &BaseType) -> Result<()> {
+    let base_name = &base.name;
+    let parameter = base_name.to_lowercase();
+
+    // The enum
+    write_comments(writer, &base.comments)?;
+    writeln!(writer, "{}", derive)?;
+    writeln!(writer, "pub enum {} {{", base_name)?;
+    for node in &base.types {
+        writeln!(writer, "    {}({}),", node.name, node.name)?;
     }
+    writeln!(writer, "}}\n")?;
 
+    // The visitor trait
+    writeln!(writer, "pub trait {}Visitor {{", base_name)?;
+    writeln!(writer, "    type Output;")?;
+    for node in &base.types {
+        writeln!(
+            writer,
+            "    fn visit_{}_{}(&mut self, {}: &{}) -> Self::Output;",
+            snake_case(&node.name),
+            parameter,
+            parameter,
+            node.name
+        )?;
+    }
     writeln!(writer, "}}\n")?;
 
-    // Implement base trait
-    writeln!(writer, "impl {} for {} {{", base_name, class_name)?;
+    // `accept`, plus `id` and `span` when every node has them
+    writeln!(writer, "impl {} {{", base_name)?;
     writeln!(
         writer,
-        "    fn accept<R>(&self, visitor: &mut dyn {}Visitor<R>) -> R {{",
+        "    pub fn accept<V: {}Visitor>(&self, visitor: &mut V) -> V::Output {{",
         base_name
     )?;
-    writeln!(
-        writer,
-        "        visitor.visit_{}_{}(self)",
-        class_name.to_lowercase(),
-        base_name.to_lowercase()
-    )?;
+    writeln!(writer, "        match self {{")?;
+    for node in &base.types {
+        writeln!(
+            writer,
+            "            {}::{}({}) => visitor.visit_{}_{}({}),",
+            base_name,
+            node.name,
+            binding(&node.name),
+            snake_case(&node.name),
+            parameter,
+            binding(&node.name)
+        )?;
+    }
+    writeln!(writer, "        }}")?;
     writeln!(writer, "    }}")?;
-    writeln!(writer, "}}\n")?;
+    for (field, typ) in &[("id", "NodeId"), ("span", "Span")] {
+        if base.types.iter().all(|node| has_field(node, typ, field)) {
+            define_getter(writer, base, field, typ)?;
+        }
+    }
+    writeln!(writer, "}}")?;
+
+    for node in &base.types {
+        writeln!(writer)?;
+        define_type(writer, derive, node)?;
+    }
+
+    Ok(())
+}
+
+fn define_getter<W: Write>(writer: &mut W, base: &BaseType, field: &str, typ: &str) -> Result<()> {
+    writeln!(writer)?;
+    writeln!(writer, "    pub fn {}(&self) -> {} {{", field, typ)?;
+    writeln!(writer, "        match self {{")?;
+    for node in &base.types {
+        writeln!(
+            writer,
+            "            {}::{}({}) => {}.{},",
+            base.name,
+            node.name,
+            binding(&node.name),
+            binding(&node.name),
+            field
+        )?;
+    }
+    writeln!(writer, "        }}")?;
+    writeln!(writer, "    }}")?;
+    Ok(())
+}
+
+fn define_type<W: Write>(writer: &mut W, derive: &str, node: &NodeType) -> Result<()> {
+    write_comments(writer, &node.comments)?;
+    writeln!(writer, "{}", derive)?;
+    writeln!(writer, "pub struct {} {{", node.name)?;
+    for (typ, name) in &node.fields {
+        writeln!(writer, "    pub {}: {},", name, map_type(typ))?;
+    }
+    writeln!(writer, "}}")?;
+
+    Ok(())
+}
 
+fn write_comments<W: Write>(writer: &mut W, comments: &[String]) -> Result<()> {
+    for comment in comments {
+        writeln!(writer, "// {}", comment)?;
+    }
     Ok(())
 }
 
-fn map_type(typ: &str) -> &str {
+fn has_field(node: &NodeType, typ: &str, name: &str) -> bool {
+    node.fields.iter().any(|(t, n)| t == typ && n == name)
+}
+
+// Child expressions and statements are boxed since the enum would otherwise contain itself
+fn map_type(typ: &str) -> String {
+    if let Some(inner) = typ.strip_suffix('?') {
+        return format!("Option<{}>", map_type(inner));
+    }
+    if let Some(inner) = typ.strip_suffix('*') {
+        return format!("Vec<{}>", inner);
+    }
+
     match typ {
-        "Expr" => "Box<Expr>",
-        "Token" => "Token",
-        "Object" => "Literal", // You can define `Literal` as a custom enum
-        _ => typ,
+        "Expr" => String::from("Box<Expr>"),
+        "Stmt" => String::from("Box<Stmt>"),
+        _ => typ.to_string(),
+    }
+}
+
+// The name a node is bound to in the generated match arms
+fn binding(name: &str) -> String {
+    name.chars().next().unwrap_or('n').to_lowercase().collect()
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nodes_get_a_struct_a_visitor_method_and_accessors() {
+        let spec = "#[derive(Debug)]\nExpr\n    // A literal\n    Literal: NodeId id, Expr? inner, Span span\n";
+        let spec = AstSpec::parse(spec).expect("spec should parse");
+        let mut generated = Vec::new();
+        generate_ast(&mut generated, &spec).unwrap();
+        let generated = String::from_utf8(generated).unwrap();
+
+        for line in &[
+            "pub enum Expr {",
+            "    Literal(Literal),",
+            "    fn visit_literal_expr(&mut self, expr: &Literal) -> Self::Output;",
+            "            Expr::Literal(l) => visitor.visit_literal_expr(l),",
+            "    pub fn span(&self) -> Span {",
+            "// A literal\n#[derive(Debug)]\npub struct Literal {",
+            "    pub inner: Option<Box<Expr>>,",
+        ] {
+            assert!(
+                generated.contains(line),
+                "missing {:?} in\n{}",
+                line,
+                generated
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_fields_are_rejected() {
+        let error = AstSpec::parse("Expr\n    Literal: NodeId\n").err();
+        assert_eq!(
+            error,
+            Some(String::from(
+                "[line 2] Expect 'Type name' but got 'NodeId'."
+            ))
+        );
     }
 }
@@ -1 +1,3 @@
+pub mod check_grammar;
+pub mod generate_programs;
 pub mod generate_structs;
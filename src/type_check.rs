@@ -0,0 +1,1054 @@
+use crate::ast::{
+    Assign, Binary, BinaryOperator, Block, Break, Call, Class, Conditional, Const, Continue, Enum,
+    Export, Expr, ExprVisitor, Expression, ForIn, Function, Get, Grouping, If, Import, Index,
+    IndexSet, Lambda, List, Literal, LiteralValue, Map, Match, Parameter, Postfix, Print, Return,
+    Set, Slice, Stmt, StmtVisitor, Store, Super, This, Throw, Try, TypeAnnotation, Unary,
+    UnaryOperator, Var, Variable, While, Yield,
+};
+use crate::function::{Arity, Slot};
+use crate::generator;
+use crate::lexer::Span;
+use crate::prelude;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// What the checker knows about a value before the program runs. Un-annotated code produces
+// Any, which fits every type and which every type fits, so it is never reported
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Any,
+    Int,
+    Float,
+    // An Int or a Float
+    Num,
+    String,
+    Bool,
+    Nil,
+    List,
+    Map,
+    Range,
+    Generator,
+    // The signature is known when the function is declared in the program
+    Fun(Option<Signature>),
+    // The class itself, calling it makes an instance
+    Class(String),
+    Instance(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub params: Vec<Type>,
+    pub arity: Arity,
+    pub returns: Box<Type>,
+}
+
+impl Type {
+    // Whether a value of this type might be used where `expected` is asked for. A Num is let
+    // through where an Int is expected, only values that can't possibly fit are reported
+    pub fn fits(&self, expected: &Type) -> bool {
+        match (self, expected) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Int, Type::Num) | (Type::Float, Type::Num) => true,
+            (Type::Num, Type::Int) | (Type::Num, Type::Float) => true,
+            (Type::Fun(_), Type::Fun(_)) => true,
+            (actual, expected) => actual == expected,
+        }
+    }
+
+    fn is_number(&self) -> bool {
+        self.fits(&Type::Num)
+    }
+
+    fn is_string(&self) -> bool {
+        self.fits(&Type::String)
+    }
+
+    // What both branches of a ternary or all arms of a match can produce
+    fn join(self, other: Type) -> Type {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Type::Fun(_), Type::Fun(_)) => Type::Fun(None),
+            (a, b) if a.is_number() && b.is_number() && a != Type::Any && b != Type::Any => {
+                Type::Num
+            }
+            _ => Type::Any,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "Any"),
+            Type::Int => write!(f, "Int"),
+            Type::Float => write!(f, "Float"),
+            Type::Num => write!(f, "Num"),
+            Type::String => write!(f, "String"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Nil => write!(f, "Nil"),
+            Type::List => write!(f, "List"),
+            Type::Map => write!(f, "Map"),
+            Type::Range => write!(f, "Range"),
+            Type::Generator => write!(f, "Generator"),
+            Type::Fun(_) => write!(f, "Fun"),
+            Type::Class(name) => write!(f, "class {}", name),
+            Type::Instance(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}, column {}] Error: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+// Checks the annotated parts of a program before it runs: initializers and assignments of
+// typed variables, arguments of typed parameters, returns of typed functions, and operators
+// whose operands can't work whatever happens at runtime
+pub fn check_types(statements: &[Stmt]) -> Vec<TypeError> {
+    let mut checker = TypeChecker {
+        scopes: vec![HashMap::new()],
+        classes: HashSet::new(),
+        superclasses: HashMap::new(),
+        initializers: HashMap::new(),
+        returns: Vec::new(),
+        this: Vec::new(),
+        errors: Vec::new(),
+    };
+    // The prelude's classes can be used like the program's own
+    checker.check_program(&prelude::statements());
+    checker.check_program(statements);
+    checker.errors
+}
+
+// A variable keeps the type it was declared with, only annotated ones check what is
+// assigned to them later
+struct Binding {
+    ty: Type,
+    annotated: bool,
+}
+
+struct TypeChecker {
+    scopes: Vec<HashMap<String, Binding>>,
+    // Every class name seen so far is also a type name
+    classes: HashSet<String>,
+    superclasses: HashMap<String, String>,
+    initializers: HashMap<String, Signature>,
+    // What the enclosing functions were declared to return, innermost last
+    returns: Vec<Type>,
+    this: Vec<String>,
+    errors: Vec<TypeError>,
+}
+
+impl TypeChecker {
+    fn check_program(&mut self, statements: &[Stmt]) {
+        // Classes can be named in annotations before their declaration
+        for statement in statements {
+            if let Stmt::Class(class) = statement {
+                self.classes.insert(class.name.clone());
+            }
+        }
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    fn check(&mut self, expr: &Expr) -> Type {
+        expr.accept(self)
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(TypeError { span, message });
+    }
+
+    // An instance of a subclass fits where its superclass is expected
+    fn fits(&self, actual: &Type, expected: &Type) -> bool {
+        match (actual, expected) {
+            (Type::Instance(class), Type::Instance(ancestor)) => self.inherits(class, ancestor),
+            _ => actual.fits(expected),
+        }
+    }
+
+    // Whether `class` is `ancestor` or one of its subclasses
+    fn inherits(&self, class: &str, ancestor: &str) -> bool {
+        let mut class = Some(class);
+        while let Some(name) = class {
+            if name == ancestor {
+                return true;
+            }
+            class = self.superclasses.get(name).map(String::as_str);
+        }
+        false
+    }
+
+    // A class without an `init` of its own takes the arguments of the one it inherits
+    fn initializer(&self, class: &str) -> Signature {
+        let mut class = Some(class);
+        while let Some(name) = class {
+            if let Some(signature) = self.initializers.get(name) {
+                return signature.clone();
+            }
+            class = self.superclasses.get(name).map(String::as_str);
+        }
+        Signature {
+            params: Vec::new(),
+            arity: Arity::of(&[]),
+            returns: Box::new(Type::Nil),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str, ty: Type, annotated: bool) {
+        self.scopes
+            .last_mut()
+            .expect("there is always a global scope")
+            .insert(name.to_string(), Binding { ty, annotated });
+    }
+
+    // Names the checker never saw declared, like the natives, can be anything
+    fn lookup(&mut self, name: &str) -> Option<&mut Binding> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+    }
+
+    fn resolve(&mut self, annotation: &Option<TypeAnnotation>) -> Type {
+        let annotation = match annotation {
+            Some(annotation) => annotation,
+            None => return Type::Any,
+        };
+        match annotation.name.as_str() {
+            "Any" => Type::Any,
+            "Int" => Type::Int,
+            "Float" => Type::Float,
+            "Num" => Type::Num,
+            "String" => Type::String,
+            "Bool" => Type::Bool,
+            "Nil" => Type::Nil,
+            "List" => Type::List,
+            "Map" => Type::Map,
+            "Range" => Type::Range,
+            "Generator" => Type::Generator,
+            "Fun" => Type::Fun(None),
+            name if self.classes.contains(name) => Type::Instance(name.to_string()),
+            name => {
+                self.error(annotation.span, format!("Unknown type '{}'.", name));
+                Type::Any
+            }
+        }
+    }
+
+    // Calling a function whose body yields gives a generator, whatever its return type says. A
+    // rest parameter is always a list
+    fn signature(
+        &mut self,
+        params: &[Parameter],
+        return_type: &Option<TypeAnnotation>,
+        body: &[Stmt],
+    ) -> Signature {
+        let mut returns = self.resolve(return_type);
+        if generator::yields(body) {
+            if let (Some(annotation), false) = (return_type, returns.fits(&Type::Generator)) {
+                let message = format!("A generator returns a Generator, not {}.", returns);
+                self.error(annotation.span, message);
+            }
+            returns = Type::Generator;
+        }
+        let mut types = Vec::new();
+        for param in params {
+            let mut ty = self.resolve(&param.annotation);
+            if param.rest {
+                if let (Some(annotation), false) = (&param.annotation, ty.fits(&Type::List)) {
+                    let message = format!("A rest parameter is a List, not {}.", ty);
+                    self.error(annotation.span, message);
+                }
+                ty = Type::List;
+            }
+            types.push(ty);
+        }
+        Signature {
+            params: types,
+            arity: Arity::of(params),
+            returns: Box::new(returns),
+        }
+    }
+
+    // A function declared to return something other than nil has to return it on every path.
+    // The span points at the function's name or at the lambda
+    fn check_function(
+        &mut self,
+        params: &[Parameter],
+        signature: &Signature,
+        body: &[Stmt],
+        span: Span,
+    ) {
+        self.begin_scope();
+        for (param, ty) in params.iter().zip(&signature.params) {
+            if let Some(default) = &param.default {
+                let value = self.check(default);
+                if !self.fits(&value, ty) {
+                    let message =
+                        format!("Can't assign {} to '{}' of type {}.", value, param.name, ty);
+                    self.error(default.span(), message);
+                }
+            }
+            self.declare(&param.name, ty.clone(), param.annotation.is_some());
+        }
+        // A generator's `return` only finishes it, so any value goes
+        let generator = generator::yields(body);
+        if generator {
+            self.returns.push(Type::Any);
+        } else {
+            self.returns.push((*signature.returns).clone());
+        }
+        self.check_program(body);
+        self.returns.pop();
+        self.end_scope();
+
+        if !generator && !Type::Nil.fits(&signature.returns) && completes(body) {
+            let message = format!(
+                "A function declared to return {} can reach the end of its body without \
+                 returning.",
+                signature.returns
+            );
+            self.error(span, message);
+        }
+    }
+
+    // A variable or a constant, an annotated one has to fit its initializer
+    fn declare_variable(
+        &mut self,
+        name: &str,
+        annotation: &Option<TypeAnnotation>,
+        initializer: Option<&Expr>,
+    ) {
+        let mut declared = self.resolve(annotation);
+
+        if let Some(initializer) = initializer {
+            let value = self.check(initializer);
+            if !self.fits(&value, &declared) {
+                let message = format!("Can't assign {} to '{}' of type {}.", value, name, declared);
+                self.error(initializer.span(), message);
+            }
+            // Like a declared function, a lambda's signature checks the calls made through
+            // the name it is given
+            if let (None, Type::Fun(Some(_))) = (annotation, &value) {
+                declared = value;
+            }
+        }
+        self.declare(name, declared, annotation.is_some());
+    }
+
+    // The named arguments come after the positional ones, `names` holds their names. Those a
+    // rest parameter collects can be anything
+    fn check_arguments(
+        &mut self,
+        signature: &Signature,
+        arguments: &[(Type, Span)],
+        names: &[&str],
+        span: Span,
+    ) {
+        let positional = arguments.len() - names.len();
+        let slots = match signature.arity.bind(positional, names) {
+            Ok(slots) => slots,
+            Err(message) => {
+                self.error(span, message);
+                return;
+            }
+        };
+
+        let params = signature.params.iter().zip(&signature.arity.names);
+        for ((expected, name), slot) in params.zip(slots) {
+            let i = match slot {
+                Slot::Argument(i) => i,
+                Slot::Default | Slot::Rest(_) => continue,
+            };
+            let (actual, span) = &arguments[i];
+            if !self.fits(actual, expected) {
+                let argument = if i < positional {
+                    (i + 1).to_string()
+                } else {
+                    format!("'{}'", name)
+                };
+                let message = format!(
+                    "Argument {} must be {}, found {}.",
+                    argument, expected, actual
+                );
+                self.error(*span, message);
+            }
+        }
+    }
+
+    fn arithmetic(&mut self, expr: &Binary, left: Type, right: Type) -> Type {
+        let symbol = expr.operator.symbol();
+
+        if expr.operator == BinaryOperator::Add {
+            if left.is_string() && right.is_string() && (left != Type::Any || right != Type::Any) {
+                return Type::String;
+            }
+            if !numbers_or_strings(&left, &right) {
+                let message = format!(
+                    "Operands of '{}' must be two numbers or two strings, found {} and {}.",
+                    symbol, left, right
+                );
+                self.error(expr.span, message);
+                return Type::Any;
+            }
+        } else if !(left.is_number() && right.is_number()) {
+            let message = format!(
+                "Operands of '{}' must be numbers, found {} and {}.",
+                symbol, left, right
+            );
+            self.error(expr.span, message);
+            return Type::Any;
+        }
+
+        match (left, right) {
+            (Type::Any, Type::Any) => Type::Any,
+            (Type::Int, Type::Int) => Type::Int,
+            (Type::Float, _) | (_, Type::Float) => Type::Float,
+            _ => Type::Num,
+        }
+    }
+
+    fn comparison(&mut self, expr: &Binary, left: Type, right: Type) -> Type {
+        if !numbers_or_strings(&left, &right) {
+            let message = format!(
+                "Operands of '{}' must be two numbers or two strings, found {} and {}.",
+                expr.operator.symbol(),
+                left,
+                right
+            );
+            self.error(expr.span, message);
+        }
+        Type::Bool
+    }
+
+    fn bitwise(&mut self, expr: &Binary, left: Type, right: Type) -> Type {
+        if !left.fits(&Type::Int) || !right.fits(&Type::Int) {
+            let message = format!(
+                "Operands of '{}' must be integers, found {} and {}.",
+                expr.operator.symbol(),
+                left,
+                right
+            );
+            self.error(expr.span, message);
+        }
+        Type::Int
+    }
+
+    fn check_container(&mut self, object: Type, span: Span) {
+        if !object.fits(&Type::List) && !object.fits(&Type::Map) {
+            let message = format!("Only lists and maps can be indexed, found {}.", object);
+            self.error(span, message);
+        }
+    }
+}
+
+// What `+` and the comparisons work on
+fn numbers_or_strings(left: &Type, right: &Type) -> bool {
+    (left.is_number() && right.is_number()) || (left.is_string() && right.is_string())
+}
+
+// Whether running the statements can get past the last one instead of returning, throwing or
+// looping forever. A `try` can get past when its body does or when it catches
+fn completes(statements: &[Stmt]) -> bool {
+    statements.iter().all(statement_completes)
+}
+
+fn statement_completes(statement: &Stmt) -> bool {
+    match statement {
+        Stmt::Return(_) | Stmt::Throw(_) => false,
+        Stmt::Block(block) => completes(&block.statements),
+        Stmt::If(stmt) => match &stmt.else_branch {
+            Some(else_branch) => {
+                statement_completes(&stmt.then_branch) || statement_completes(else_branch)
+            }
+            None => true,
+        },
+        Stmt::While(stmt) => !is_true(&stmt.condition) || breaks(&stmt.body, &stmt.label, false),
+        Stmt::Try(stmt) => {
+            (completes(&stmt.body)
+                || stmt
+                    .catch_clause
+                    .as_ref()
+                    .is_some_and(|clause| completes(&clause.body)))
+                && completes(&stmt.finally_body)
+        }
+        _ => true,
+    }
+}
+
+// `while (true)` and `for (;;)`
+fn is_true(condition: &Expr) -> bool {
+    matches!(
+        condition,
+        Expr::Literal(Literal {
+            value: LiteralValue::Boolean(true),
+            ..
+        })
+    )
+}
+
+// Whether the statement has a `break` that leaves the loop with the label, `nested` once it
+// is inside another loop that an unlabelled one would leave instead
+fn breaks(statement: &Stmt, label: &Option<String>, nested: bool) -> bool {
+    match statement {
+        Stmt::Break(stmt) => match &stmt.label {
+            Some(_) => stmt.label == *label,
+            None => !nested,
+        },
+        Stmt::Block(block) => block
+            .statements
+            .iter()
+            .any(|statement| breaks(statement, label, nested)),
+        Stmt::If(stmt) => {
+            breaks(&stmt.then_branch, label, nested)
+                || stmt
+                    .else_branch
+                    .as_deref()
+                    .is_some_and(|statement| breaks(statement, label, nested))
+        }
+        Stmt::While(stmt) => breaks(&stmt.body, label, true),
+        Stmt::ForIn(stmt) => breaks(&stmt.body, label, true),
+        Stmt::Try(stmt) => stmt
+            .body
+            .iter()
+            .chain(stmt.catch_clause.iter().flat_map(|clause| &clause.body))
+            .chain(&stmt.finally_body)
+            .any(|statement| breaks(statement, label, nested)),
+        _ => false,
+    }
+}
+
+// Elements and fields aren't typed, so what `op=` makes of one isn't known either
+fn stored(store: Store, value: Type) -> Type {
+    match store {
+        Store::Assign => value,
+        Store::Compound(_) | Store::Postfix(_) => Type::Any,
+    }
+}
+
+impl ExprVisitor for TypeChecker {
+    type Output = Type;
+
+    fn visit_assign_expr(&mut self, expr: &Assign) -> Type {
+        let value = self.check(&expr.value);
+
+        let mismatch = match self.lookup(&expr.name) {
+            Some(binding) if binding.annotated => Some(binding.ty.clone()),
+            // It no longer holds what it was declared with
+            Some(binding) => {
+                binding.ty = Type::Any;
+                None
+            }
+            None => None,
+        }
+        .filter(|ty| !self.fits(&value, ty));
+        if let Some(declared) = mismatch {
+            let message = format!(
+                "Can't assign {} to '{}' of type {}.",
+                value, expr.name, declared
+            );
+            self.error(expr.value.span(), message);
+        }
+        value
+    }
+
+    fn visit_binary_expr(&mut self, expr: &Binary) -> Type {
+        let left = self.check(&expr.left);
+        let right = self.check(&expr.right);
+
+        match expr.operator {
+            BinaryOperator::Comma => right,
+            BinaryOperator::Equal | BinaryOperator::NotEqual => Type::Bool,
+            BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual
+            | BinaryOperator::Less
+            | BinaryOperator::LessEqual => self.comparison(expr, left, right),
+            BinaryOperator::In => {
+                if ![Type::List, Type::Map, Type::Range]
+                    .iter()
+                    .any(|container| right.fits(container))
+                {
+                    let message = format!(
+                        "Right operand of 'in' must be a list, a map or a range, found {}.",
+                        right
+                    );
+                    self.error(expr.span, message);
+                }
+                Type::Bool
+            }
+            BinaryOperator::Range | BinaryOperator::RangeInclusive => {
+                if !left.fits(&Type::Int) || !right.fits(&Type::Int) {
+                    let message = format!(
+                        "Range bounds must be integers, found {} and {}.",
+                        left, right
+                    );
+                    self.error(expr.span, message);
+                }
+                Type::Range
+            }
+            BinaryOperator::BitAnd
+            | BinaryOperator::BitOr
+            | BinaryOperator::BitXor
+            | BinaryOperator::ShiftLeft
+            | BinaryOperator::ShiftRight => self.bitwise(expr, left, right),
+            _ => self.arithmetic(expr, left, right),
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> Type {
+        let callee = self.check(&expr.callee);
+        let arguments: Vec<(Type, Span)> = expr
+            .arguments
+            .iter()
+            .chain(expr.named.iter().map(|argument| &argument.value))
+            .map(|argument| (self.check(argument), argument.span()))
+            .collect();
+        let names: Vec<&str> = expr
+            .named
+            .iter()
+            .map(|argument| argument.name.as_str())
+            .collect();
+
+        match callee {
+            Type::Fun(Some(signature)) => {
+                self.check_arguments(&signature, &arguments, &names, expr.span);
+                *signature.returns
+            }
+            Type::Class(name) => {
+                let signature = self.initializer(&name);
+                self.check_arguments(&signature, &arguments, &names, expr.span);
+                Type::Instance(name)
+            }
+            Type::Any | Type::Fun(None) => Type::Any,
+            other => {
+                let message = format!("Can only call functions and classes, found {}.", other);
+                self.error(expr.span, message);
+                Type::Any
+            }
+        }
+    }
+
+    fn visit_conditional_expr(&mut self, expr: &Conditional) -> Type {
+        self.check(&expr.condition);
+        let then = self.check(&expr.then_branch);
+        then.join(self.check(&expr.else_branch))
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> Type {
+        self.check(&expr.object);
+        Type::Any
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> Type {
+        self.check(&expr.expression)
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) -> Type {
+        let object = self.check(&expr.object);
+        self.check_container(object, expr.span);
+        self.check(&expr.index);
+        Type::Any
+    }
+
+    fn visit_index_set_expr(&mut self, expr: &IndexSet) -> Type {
+        let object = self.check(&expr.object);
+        self.check_container(object, expr.span);
+        self.check(&expr.index);
+        let value = self.check(&expr.value);
+        stored(expr.store, value)
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &Lambda) -> Type {
+        let signature = self.signature(&expr.params, &expr.return_type, &expr.body);
+        self.check_function(&expr.params, &signature, &expr.body, expr.span);
+        Type::Fun(Some(signature))
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> Type {
+        for element in &expr.elements {
+            self.check(element);
+        }
+        Type::List
+    }
+
+    fn visit_literal_expr(&mut self, expr: &Literal) -> Type {
+        match expr.value {
+            LiteralValue::Integer(_) => Type::Int,
+            LiteralValue::Float(_) => Type::Float,
+            LiteralValue::String(_) => Type::String,
+            LiteralValue::Boolean(_) => Type::Bool,
+            LiteralValue::Nil => Type::Nil,
+        }
+    }
+
+    fn visit_map_expr(&mut self, expr: &Map) -> Type {
+        for (key, value) in expr.keys.iter().zip(&expr.values) {
+            self.check(key);
+            self.check(value);
+        }
+        Type::Map
+    }
+
+    fn visit_match_expr(&mut self, expr: &Match) -> Type {
+        self.check(&expr.subject);
+
+        let mut result: Option<Type> = None;
+        for arm in &expr.arms {
+            self.begin_scope();
+            let mut names = Vec::new();
+            arm.pattern.bindings(&mut names);
+            for name in names {
+                self.declare(&name, Type::Any, false);
+            }
+            if let Some(guard) = &arm.guard {
+                self.check(guard);
+            }
+            let value = self.check(&arm.value);
+            self.end_scope();
+
+            result = Some(match result {
+                Some(result) => result.join(value),
+                None => value,
+            });
+        }
+        result.unwrap_or(Type::Any)
+    }
+
+    fn visit_postfix_expr(&mut self, expr: &Postfix) -> Type {
+        let ty = self
+            .lookup(&expr.name)
+            .map_or(Type::Any, |binding| binding.ty.clone());
+        if !ty.is_number() {
+            let message = format!(
+                "Operand of '{}' must be a number, found {}.",
+                expr.operator.symbol(),
+                ty
+            );
+            self.error(expr.span, message);
+        }
+        ty
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> Type {
+        self.check(&expr.object);
+        let value = self.check(&expr.value);
+        stored(expr.store, value)
+    }
+
+    fn visit_slice_expr(&mut self, expr: &Slice) -> Type {
+        let object = self.check(&expr.object);
+        if !object.fits(&Type::List) {
+            let message = format!("Only lists can be sliced, found {}.", object);
+            self.error(expr.span, message);
+        }
+        for bound in expr.start.iter().chain(&expr.end) {
+            self.check(bound);
+        }
+        Type::List
+    }
+
+    fn visit_super_expr(&mut self, _expr: &Super) -> Type {
+        Type::Any
+    }
+
+    fn visit_this_expr(&mut self, _expr: &This) -> Type {
+        match self.this.last() {
+            Some(class) => Type::Instance(class.clone()),
+            None => Type::Any,
+        }
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) -> Type {
+        let right = self.check(&expr.right);
+        let (expected, description) = match expr.operator {
+            UnaryOperator::Not => return Type::Bool,
+            UnaryOperator::Negate => (Type::Num, "a number"),
+            UnaryOperator::BitNot => (Type::Int, "an integer"),
+        };
+
+        if !right.fits(&expected) {
+            let message = format!(
+                "Operand of '{}' must be {}, found {}.",
+                expr.operator.symbol(),
+                description,
+                right
+            );
+            self.error(expr.span, message);
+            return Type::Any;
+        }
+        match expr.operator {
+            UnaryOperator::BitNot => Type::Int,
+            _ => right,
+        }
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) -> Type {
+        self.lookup(&expr.name)
+            .map_or(Type::Any, |binding| binding.ty.clone())
+    }
+}
+
+impl StmtVisitor for TypeChecker {
+    type Output = ();
+
+    fn visit_block_stmt(&mut self, stmt: &Block) {
+        self.begin_scope();
+        self.check_program(&stmt.statements);
+        self.end_scope();
+    }
+
+    fn visit_break_stmt(&mut self, _stmt: &Break) {}
+
+    fn visit_class_stmt(&mut self, stmt: &Class) {
+        self.classes.insert(stmt.name.clone());
+        if let Some(superclass) = &stmt.superclass {
+            // Redeclared classes could make the chain loop, such a link is left out
+            if superclass.name == stmt.name {
+                let message = "A class can't inherit from itself.".to_string();
+                self.error(superclass.span, message);
+            } else if !self.inherits(&superclass.name, &stmt.name) {
+                self.superclasses
+                    .insert(stmt.name.clone(), superclass.name.clone());
+            }
+        }
+        self.declare(&stmt.name, Type::Class(stmt.name.clone()), false);
+
+        let signatures: Vec<Signature> = stmt
+            .methods
+            .iter()
+            .map(|method| self.signature(&method.params, &method.return_type, &method.body))
+            .collect();
+        for (method, signature) in stmt.methods.iter().zip(&signatures) {
+            if method.name == "init" {
+                self.initializers
+                    .insert(stmt.name.clone(), signature.clone());
+            }
+        }
+
+        self.this.push(stmt.name.clone());
+        for (method, signature) in stmt.methods.iter().zip(&signatures) {
+            self.check_function(&method.params, signature, &method.body, method.span);
+        }
+        self.this.pop();
+    }
+
+    fn visit_const_stmt(&mut self, stmt: &Const) {
+        self.declare_variable(&stmt.name, &stmt.annotation, Some(&stmt.initializer));
+    }
+
+    fn visit_continue_stmt(&mut self, _stmt: &Continue) {}
+
+    // The enum's variants aren't tracked, they are Any like everything reached through `.`
+    fn visit_enum_stmt(&mut self, stmt: &Enum) {
+        self.declare(&stmt.name, Type::Any, false);
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &Export) {
+        stmt.declaration.accept(self)
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &Expression) {
+        self.check(&stmt.expression);
+    }
+
+    // What the loop goes through is only known for lists, ranges and maps, so its names are Any
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) {
+        let iterable = self.check(&stmt.iterable);
+        if !matches!(
+            iterable,
+            Type::Any | Type::List | Type::Map | Type::Range | Type::Generator | Type::Instance(_)
+        ) {
+            let message = format!("Can't iterate over {}.", iterable);
+            self.error(stmt.iterable.span(), message);
+        }
+        self.begin_scope();
+        for name in &stmt.names {
+            self.declare(name, Type::Any, false);
+        }
+        stmt.body.accept(self);
+        self.end_scope();
+    }
+
+    // Declared before its body is checked, so it can call itself
+    fn visit_function_stmt(&mut self, stmt: &Function) {
+        let signature = self.signature(&stmt.params, &stmt.return_type, &stmt.body);
+        self.declare(&stmt.name, Type::Fun(Some(signature.clone())), false);
+        self.check_function(&stmt.params, &signature, &stmt.body, stmt.span);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) {
+        self.check(&stmt.condition);
+        stmt.then_branch.accept(self);
+        if let Some(else_branch) = &stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    // Nothing is known about what other modules hold
+    fn visit_import_stmt(&mut self, stmt: &Import) {
+        for name in stmt.alias.iter().chain(&stmt.names) {
+            self.declare(name, Type::Any, false);
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) {
+        self.check(&stmt.expression);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) {
+        let (value, span) = match &stmt.value {
+            Some(value) => (self.check(value), value.span()),
+            None => (Type::Nil, stmt.span),
+        };
+
+        if let Some(expected) = self.returns.last().cloned() {
+            if !self.fits(&value, &expected) {
+                let message = format!(
+                    "Can't return {} from a function declared to return {}.",
+                    value, expected
+                );
+                self.error(span, message);
+            }
+        }
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &Throw) {
+        self.check(&stmt.value);
+    }
+
+    // Anything can be thrown, so nothing is known about what is caught
+    fn visit_try_stmt(&mut self, stmt: &Try) {
+        self.begin_scope();
+        self.check_program(&stmt.body);
+        self.end_scope();
+        if let Some(clause) = &stmt.catch_clause {
+            self.begin_scope();
+            self.declare(&clause.name, Type::Any, false);
+            self.check_program(&clause.body);
+            self.end_scope();
+        }
+        self.begin_scope();
+        self.check_program(&stmt.finally_body);
+        self.end_scope();
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) {
+        self.declare_variable(&stmt.name, &stmt.annotation, stmt.initializer.as_deref());
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) {
+        self.check(&stmt.condition);
+        stmt.body.accept(self);
+        if let Some(increment) = &stmt.increment {
+            self.check(increment);
+        }
+    }
+
+    fn visit_yield_stmt(&mut self, stmt: &Yield) {
+        self.check(&stmt.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    fn errors(code: &str) -> Vec<String> {
+        let mut scanner = Scanner::new(code.to_string());
+        let statements = Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .expect("program should parse");
+        check_types(&statements)
+            .iter()
+            .map(TypeError::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn reports_mismatches_and_leaves_untyped_code_alone() {
+        assert_eq!(
+            errors(
+                "var x: Int = \"one\";\n\
+                 fun add(a: Num, b: Num) -> Num { return a + b; }\n\
+                 var s: String = add(1, 2.5);\n\
+                 add(\"a\", 1); add(1);\n\
+                 print \"a\" - 1;\n\
+                 fun name() -> String { return 1; }\n\
+                 var p: Point;\n\
+                 class Loop < Loop {}\n\
+                 fun count(n) -> Int { yield n; } var g: Generator = count(1); var h: Int = g;\n\
+                 fun pad(s: String, width: Int = \"8\", ...rest: Int) {} pad(width: 2, s: 1); pad(w: 1);"
+            ),
+            vec![
+                "[line 1, column 14] Error: Can't assign String to 'x' of type Int.",
+                "[line 3, column 20] Error: Can't assign Num to 's' of type String.",
+                "[line 4, column 5] Error: Argument 1 must be Num, found String.",
+                "[line 4, column 17] Error: Expected 2 arguments but got 1.",
+                "[line 5, column 11] Error: Operands of '-' must be numbers, found String and Int.",
+                "[line 6, column 31] Error: Can't return Int from a function declared to return String.",
+                "[line 7, column 8] Error: Unknown type 'Point'.",
+                "[line 8, column 14] Error: A class can't inherit from itself.",
+                "[line 9, column 17] Error: A generator returns a Generator, not Int.",
+                "[line 9, column 76] Error: Can't assign Generator to 'h' of type Int.",
+                "[line 10, column 47] Error: A rest parameter is a List, not Int.",
+                "[line 10, column 33] Error: Can't assign String to 'width' of type Int.",
+                "[line 10, column 72] Error: Argument 's' must be String, found Int.",
+                "[line 10, column 79] Error: No parameter named 'w'.",
+            ]
+        );
+
+        let untyped = "var x = \"one\"; x = 1; fun f(a) { return a - 1; } f(\"s\");\n\
+                       var y: Num = f(2); var n: Int = 1; n = n * 2.0 ? 0 : n;\n\
+                       var e: Error = TypeError(\"bad\");";
+        assert_eq!(errors(untyped), Vec::<String>::new());
+    }
+
+    #[test]
+    fn typed_functions_return_on_every_path_and_typed_lambdas_check_their_calls() {
+        assert_eq!(
+            errors(
+                "fun none() -> Int {}\n\
+                 fun branches(x) -> Int { if (x) return 1; else { throw Error(\"no\"); } }\n\
+                 fun spins() -> Int { while (true) { for (;;) break; } }\n\
+                 fun leaves() -> Int { l: while (true) { while (true) break l; } }\n\
+                 fun recovers() -> Int { try { return 1; } catch (e) { print e; } }\n\
+                 var twice = fun (n: Int) -> Int { return n * 2; }; twice(\"s\");\n\
+                 var p: Float = 2 ** 3; fun log(x) -> Nil {}"
+            ),
+            vec![
+                "[line 1, column 5] Error: A function declared to return Int can reach the end of \
+                 its body without returning.",
+                "[line 4, column 5] Error: A function declared to return Int can reach the end of \
+                 its body without returning.",
+                "[line 5, column 5] Error: A function declared to return Int can reach the end of \
+                 its body without returning.",
+                "[line 6, column 58] Error: Argument 1 must be Int, found String.",
+                "[line 7, column 18] Error: Can't assign Int to 'p' of type Float.",
+            ]
+        );
+    }
+}
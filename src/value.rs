@@ -0,0 +1,234 @@
+use crate::ast::LiteralValue;
+use crate::class::{Class, Instance};
+use crate::enumeration::{Enum, Tagged};
+use crate::function::{Function, NativeFunction};
+use crate::generator::Generator;
+use crate::map::Map;
+use crate::module::Module;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::thread::LocalKey;
+
+// The values our programs produce while running, integers and floats are kept apart so the
+// integer-only operators can reject floats. Lists, maps and instances are shared, a copy of one
+// sees the changes made through the original
+#[derive(Debug, Clone)]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Function(Rc<Function>),
+    NativeFunction(Rc<NativeFunction>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Map>>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+    Module(Rc<Module>),
+    // `start..end`, the end is left out. `0..=9` is the same range as `0..10`
+    Range(i64, i64),
+    Enum(Rc<Enum>),
+    // `Shape.Circle`, calling it makes the variant out of its fields
+    Constructor(Rc<Enum>, usize),
+    Tagged(Rc<Tagged>),
+    Generator(Rc<Generator>),
+    // A generator's `next` method, calling it runs the generator up to its next `yield`
+    Resume(Rc<Generator>),
+    Nil,
+}
+
+impl Value {
+    // Only nil and false are falsey, everything else counts as true
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Function(_)
+            | Value::NativeFunction(_)
+            | Value::Constructor(..)
+            | Value::Resume(_) => "function",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::Module(_) => "module",
+            Value::Range(..) => "range",
+            Value::Enum(_) => "enum",
+            Value::Tagged(_) => "variant",
+            Value::Generator(_) => "generator",
+            Value::Nil => "nil",
+        }
+    }
+
+    // How the value is shown inside a list or a map, or in an error message. Strings keep their
+    // quotes, so `["1"]` and `[1]` differ
+    pub fn repr(&self) -> String {
+        match self {
+            Value::String(s) => format!("\"{}\"", s),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Integer(a), Value::Float(b)) | (Value::Float(b), Value::Integer(a)) => {
+                integral(*b) == Some(*a)
+            }
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            // Functions are only equal to themselves
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::NativeFunction(a), Value::NativeFunction(b)) => Rc::ptr_eq(a, b),
+            // Lists and maps are equal when their contents are. A pair met again while its own
+            // contents are being compared holds itself, and is only equal by pointer
+            (Value::List(a), Value::List(b)) => {
+                Rc::ptr_eq(a, b)
+                    || Visit::enter(&COMPARING, (address(a), address(b)))
+                        .is_some_and(|_visit| *a.borrow() == *b.borrow())
+            }
+            (Value::Map(a), Value::Map(b)) => {
+                Rc::ptr_eq(a, b)
+                    || Visit::enter(&COMPARING, (address(a), address(b)))
+                        .is_some_and(|_visit| *a.borrow() == *b.borrow())
+            }
+            // Classes and instances only equal themselves, like functions
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::Module(a), Value::Module(b)) => Rc::ptr_eq(a, b),
+            (Value::Range(a, b), Value::Range(c, d)) => a == c && b == d,
+            (Value::Enum(a), Value::Enum(b)) => Rc::ptr_eq(a, b),
+            (Value::Constructor(a, i), Value::Constructor(b, j)) => Rc::ptr_eq(a, b) && i == j,
+            // Variants are compared by value, like lists
+            (Value::Tagged(a), Value::Tagged(b)) => a == b,
+            (Value::Generator(a), Value::Generator(b)) => Rc::ptr_eq(a, b),
+            (Value::Resume(a), Value::Resume(b)) => Rc::ptr_eq(a, b),
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<&LiteralValue> for Value {
+    fn from(literal: &LiteralValue) -> Value {
+        match literal {
+            LiteralValue::Integer(n) => Value::Integer(*n),
+            LiteralValue::Float(n) => Value::Float(*n),
+            LiteralValue::String(s) => Value::String(s.clone()),
+            LiteralValue::Boolean(b) => Value::Boolean(*b),
+            LiteralValue::Nil => Value::Nil,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Float(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::NativeFunction(function) => write!(f, "{}", function),
+            // A list or a map that holds itself is shown as `[...]` or `{...}` the second time
+            Value::List(list) => {
+                let _visit = match Visit::enter(&PRINTING, address(list)) {
+                    Some(visit) => visit,
+                    None => return write!(f, "[...]"),
+                };
+                write!(f, "[")?;
+                for (i, element) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element.repr())?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                let _visit = match Visit::enter(&PRINTING, address(map)) {
+                    Some(visit) => visit,
+                    None => return write!(f, "{{...}}"),
+                };
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key.repr(), value.repr())?;
+                }
+                write!(f, "}}")
+            }
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Value::Module(module) => write!(f, "{}", module),
+            Value::Range(start, end) => write!(f, "{}..{}", start, end),
+            Value::Enum(enumeration) => write!(f, "{}", enumeration),
+            Value::Constructor(enumeration, variant) => write!(
+                f,
+                "<fn {}.{}>",
+                enumeration.name, enumeration.variants[*variant].name
+            ),
+            Value::Tagged(tagged) => write!(f, "{}", tagged),
+            Value::Generator(generator) => write!(f, "{}", generator),
+            Value::Resume(_) => write!(f, "<fn next>"),
+            Value::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+thread_local! {
+    // The lists and maps being printed, and the pairs being compared, right now
+    static PRINTING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+    static COMPARING: RefCell<Vec<(usize, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+// The integer a float is exactly equal to, if any. Comparing through `as f64` instead would
+// make 2^53 + 1 equal to 2^53 as a float
+pub fn integral(n: f64) -> Option<i64> {
+    // `i64::MAX as f64` rounds up to 2^63, which no longer fits
+    if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 {
+        Some(n as i64)
+    } else {
+        None
+    }
+}
+
+fn address<T>(shared: &Rc<T>) -> usize {
+    Rc::as_ptr(shared) as usize
+}
+
+// Marks a container as being visited until it's dropped. Entering one that is already being
+// visited gives None, it contains itself
+struct Visit<K: 'static> {
+    stack: &'static LocalKey<RefCell<Vec<K>>>,
+}
+
+impl<K: PartialEq> Visit<K> {
+    fn enter(stack: &'static LocalKey<RefCell<Vec<K>>>, key: K) -> Option<Visit<K>> {
+        stack.with(|visiting| {
+            let mut visiting = visiting.borrow_mut();
+            if visiting.contains(&key) {
+                return None;
+            }
+            visiting.push(key);
+            Some(Visit { stack })
+        })
+    }
+}
+
+impl<K> Drop for Visit<K> {
+    fn drop(&mut self) {
+        self.stack.with(|visiting| visiting.borrow_mut().pop());
+    }
+}
@@ -0,0 +1,324 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Conditional, Const, Continue, Enum, Export, Expr,
+    Expression, ForIn, Function, Get, Grouping, If, Import, Index, IndexSet, Lambda, List, Literal,
+    Map, Match, Parameter, Postfix, Print, Return, Set, Slice, Stmt, Super, This, Throw, Try,
+    Unary, Var, Variable, While, Yield,
+};
+
+// Walks a tree in place through mutable references. Every method defaults to visiting the
+// children, so a pass only overrides the nodes it changes. `visit_expr_mut` and
+// `visit_stmt_mut` get the whole node and can replace it with another kind
+pub trait VisitorMut {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_assign_mut(&mut self, expr: &mut Assign) {
+        self.visit_expr_mut(&mut expr.value)
+    }
+
+    fn visit_binary_mut(&mut self, expr: &mut Binary) {
+        self.visit_expr_mut(&mut expr.left);
+        self.visit_expr_mut(&mut expr.right);
+    }
+
+    fn visit_call_mut(&mut self, expr: &mut Call) {
+        self.visit_expr_mut(&mut expr.callee);
+        for argument in &mut expr.arguments {
+            self.visit_expr_mut(argument);
+        }
+        for argument in &mut expr.named {
+            self.visit_expr_mut(&mut argument.value);
+        }
+    }
+
+    fn visit_conditional_mut(&mut self, expr: &mut Conditional) {
+        self.visit_expr_mut(&mut expr.condition);
+        self.visit_expr_mut(&mut expr.then_branch);
+        self.visit_expr_mut(&mut expr.else_branch);
+    }
+
+    fn visit_get_mut(&mut self, expr: &mut Get) {
+        self.visit_expr_mut(&mut expr.object)
+    }
+
+    fn visit_grouping_mut(&mut self, expr: &mut Grouping) {
+        self.visit_expr_mut(&mut expr.expression)
+    }
+
+    fn visit_index_mut(&mut self, expr: &mut Index) {
+        self.visit_expr_mut(&mut expr.object);
+        self.visit_expr_mut(&mut expr.index);
+    }
+
+    fn visit_index_set_mut(&mut self, expr: &mut IndexSet) {
+        self.visit_expr_mut(&mut expr.object);
+        self.visit_expr_mut(&mut expr.index);
+        self.visit_expr_mut(&mut expr.value);
+    }
+
+    fn visit_lambda_mut(&mut self, expr: &mut Lambda) {
+        visit_params_mut(self, &mut expr.params);
+        visit_program_mut(self, expr.body.statements_mut())
+    }
+
+    fn visit_list_mut(&mut self, expr: &mut List) {
+        for element in &mut expr.elements {
+            self.visit_expr_mut(element);
+        }
+    }
+
+    fn visit_literal_mut(&mut self, _expr: &mut Literal) {}
+
+    fn visit_map_mut(&mut self, expr: &mut Map) {
+        for (key, value) in expr.keys.iter_mut().zip(&mut expr.values) {
+            self.visit_expr_mut(key);
+            self.visit_expr_mut(value);
+        }
+    }
+
+    fn visit_match_mut(&mut self, expr: &mut Match) {
+        self.visit_expr_mut(&mut expr.subject);
+        for arm in &mut expr.arms {
+            if let Some(guard) = &mut arm.guard {
+                self.visit_expr_mut(guard);
+            }
+            self.visit_expr_mut(&mut arm.value);
+        }
+    }
+
+    fn visit_postfix_mut(&mut self, _expr: &mut Postfix) {}
+
+    fn visit_set_mut(&mut self, expr: &mut Set) {
+        self.visit_expr_mut(&mut expr.object);
+        self.visit_expr_mut(&mut expr.value);
+    }
+
+    fn visit_slice_mut(&mut self, expr: &mut Slice) {
+        self.visit_expr_mut(&mut expr.object);
+        if let Some(start) = &mut expr.start {
+            self.visit_expr_mut(start);
+        }
+        if let Some(end) = &mut expr.end {
+            self.visit_expr_mut(end);
+        }
+    }
+
+    fn visit_super_mut(&mut self, _expr: &mut Super) {}
+
+    fn visit_this_mut(&mut self, _expr: &mut This) {}
+
+    fn visit_unary_mut(&mut self, expr: &mut Unary) {
+        self.visit_expr_mut(&mut expr.right)
+    }
+
+    fn visit_variable_mut(&mut self, _expr: &mut Variable) {}
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt)
+    }
+
+    fn visit_block_mut(&mut self, stmt: &mut Block) {
+        for statement in &mut stmt.statements {
+            self.visit_stmt_mut(statement);
+        }
+    }
+
+    fn visit_break_mut(&mut self, _stmt: &mut Break) {}
+
+    fn visit_class_mut(&mut self, stmt: &mut Class) {
+        if let Some(superclass) = &mut stmt.superclass {
+            self.visit_variable_mut(superclass);
+        }
+        for method in &mut stmt.methods {
+            self.visit_function_mut(method);
+        }
+    }
+
+    fn visit_const_mut(&mut self, stmt: &mut Const) {
+        self.visit_expr_mut(&mut stmt.initializer)
+    }
+
+    fn visit_continue_mut(&mut self, _stmt: &mut Continue) {}
+
+    fn visit_enum_mut(&mut self, _stmt: &mut Enum) {}
+
+    fn visit_export_mut(&mut self, stmt: &mut Export) {
+        self.visit_stmt_mut(&mut stmt.declaration)
+    }
+
+    fn visit_expression_mut(&mut self, stmt: &mut Expression) {
+        self.visit_expr_mut(&mut stmt.expression)
+    }
+
+    fn visit_for_in_mut(&mut self, stmt: &mut ForIn) {
+        self.visit_expr_mut(&mut stmt.iterable);
+        self.visit_stmt_mut(&mut stmt.body);
+    }
+
+    fn visit_function_mut(&mut self, stmt: &mut Function) {
+        visit_params_mut(self, &mut stmt.params);
+        visit_program_mut(self, stmt.body.statements_mut())
+    }
+
+    fn visit_if_mut(&mut self, stmt: &mut If) {
+        self.visit_expr_mut(&mut stmt.condition);
+        self.visit_stmt_mut(&mut stmt.then_branch);
+        if let Some(else_branch) = &mut stmt.else_branch {
+            self.visit_stmt_mut(else_branch);
+        }
+    }
+
+    fn visit_import_mut(&mut self, _stmt: &mut Import) {}
+
+    fn visit_print_mut(&mut self, stmt: &mut Print) {
+        self.visit_expr_mut(&mut stmt.expression)
+    }
+
+    fn visit_return_mut(&mut self, stmt: &mut Return) {
+        if let Some(value) = &mut stmt.value {
+            self.visit_expr_mut(value);
+        }
+    }
+
+    fn visit_throw_mut(&mut self, stmt: &mut Throw) {
+        self.visit_expr_mut(&mut stmt.value)
+    }
+
+    fn visit_try_mut(&mut self, stmt: &mut Try) {
+        visit_program_mut(self, &mut stmt.body);
+        if let Some(clause) = &mut stmt.catch_clause {
+            visit_program_mut(self, &mut clause.body);
+        }
+        visit_program_mut(self, &mut stmt.finally_body);
+    }
+
+    fn visit_var_mut(&mut self, stmt: &mut Var) {
+        if let Some(initializer) = &mut stmt.initializer {
+            self.visit_expr_mut(initializer);
+        }
+    }
+
+    fn visit_while_mut(&mut self, stmt: &mut While) {
+        self.visit_expr_mut(&mut stmt.condition);
+        self.visit_stmt_mut(&mut stmt.body);
+        if let Some(increment) = &mut stmt.increment {
+            self.visit_expr_mut(increment);
+        }
+    }
+
+    fn visit_yield_mut(&mut self, stmt: &mut Yield) {
+        self.visit_expr_mut(&mut stmt.value)
+    }
+}
+
+pub fn visit_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statements: &mut [Stmt]) {
+    for statement in statements {
+        visitor.visit_stmt_mut(statement);
+    }
+}
+
+// Only the defaults of the parameters are expressions
+pub fn visit_params_mut<V: VisitorMut + ?Sized>(visitor: &mut V, params: &mut [Parameter]) {
+    for default in params.iter_mut().filter_map(|param| param.default.as_mut()) {
+        visitor.visit_expr_mut(default);
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Assign(a) => visitor.visit_assign_mut(a),
+        Expr::Binary(b) => visitor.visit_binary_mut(b),
+        Expr::Call(c) => visitor.visit_call_mut(c),
+        Expr::Conditional(c) => visitor.visit_conditional_mut(c),
+        Expr::Get(g) => visitor.visit_get_mut(g),
+        Expr::Grouping(g) => visitor.visit_grouping_mut(g),
+        Expr::Index(i) => visitor.visit_index_mut(i),
+        Expr::IndexSet(i) => visitor.visit_index_set_mut(i),
+        Expr::Lambda(l) => visitor.visit_lambda_mut(l),
+        Expr::List(l) => visitor.visit_list_mut(l),
+        Expr::Literal(l) => visitor.visit_literal_mut(l),
+        Expr::Map(m) => visitor.visit_map_mut(m),
+        Expr::Match(m) => visitor.visit_match_mut(m),
+        Expr::Postfix(p) => visitor.visit_postfix_mut(p),
+        Expr::Set(s) => visitor.visit_set_mut(s),
+        Expr::Slice(s) => visitor.visit_slice_mut(s),
+        Expr::Super(s) => visitor.visit_super_mut(s),
+        Expr::This(t) => visitor.visit_this_mut(t),
+        Expr::Unary(u) => visitor.visit_unary_mut(u),
+        Expr::Variable(v) => visitor.visit_variable_mut(v),
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Block(b) => visitor.visit_block_mut(b),
+        Stmt::Break(b) => visitor.visit_break_mut(b),
+        Stmt::Class(c) => visitor.visit_class_mut(c),
+        Stmt::Const(c) => visitor.visit_const_mut(c),
+        Stmt::Continue(c) => visitor.visit_continue_mut(c),
+        Stmt::Enum(e) => visitor.visit_enum_mut(e),
+        Stmt::Export(e) => visitor.visit_export_mut(e),
+        Stmt::Expression(e) => visitor.visit_expression_mut(e),
+        Stmt::ForIn(f) => visitor.visit_for_in_mut(f),
+        Stmt::Function(f) => visitor.visit_function_mut(f),
+        Stmt::If(i) => visitor.visit_if_mut(i),
+        Stmt::Import(i) => visitor.visit_import_mut(i),
+        Stmt::Print(p) => visitor.visit_print_mut(p),
+        Stmt::Return(r) => visitor.visit_return_mut(r),
+        Stmt::Throw(t) => visitor.visit_throw_mut(t),
+        Stmt::Try(t) => visitor.visit_try_mut(t),
+        Stmt::Var(v) => visitor.visit_var_mut(v),
+        Stmt::While(w) => visitor.visit_while_mut(w),
+        Stmt::Yield(y) => visitor.visit_yield_mut(y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_printer::AstPrinter;
+    use crate::lexer::Scanner;
+    use crate::parser::Parser;
+
+    // Renames a variable everywhere it is declared, read or written, and drops groupings
+    struct Rename;
+
+    impl VisitorMut for Rename {
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            if let Expr::Grouping(grouping) = expr {
+                *expr = (*grouping.expression).clone();
+            }
+            walk_expr_mut(self, expr)
+        }
+
+        fn visit_assign_mut(&mut self, expr: &mut Assign) {
+            expr.name = expr.name.replace("old", "new");
+            self.visit_expr_mut(&mut expr.value)
+        }
+
+        fn visit_variable_mut(&mut self, expr: &mut Variable) {
+            expr.name = expr.name.replace("old", "new");
+        }
+
+        fn visit_var_mut(&mut self, stmt: &mut Var) {
+            stmt.name = stmt.name.replace("old", "new");
+        }
+    }
+
+    #[test]
+    fn overridden_methods_edit_the_tree_in_place() {
+        let code = "var old = 1; { old = (old * 2); print old; }";
+        let mut scanner = Scanner::new(code.to_string());
+        let mut statements = Parser::new(scanner.scan_tokens())
+            .parse_program()
+            .expect("program should parse");
+
+        visit_program_mut(&mut Rename, &mut statements);
+
+        assert_eq!(
+            AstPrinter.print_program(&statements),
+            "(var new 1)\n(block (; (= new (* new 2))) (print new))"
+        );
+    }
+}